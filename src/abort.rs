@@ -0,0 +1,169 @@
+//! Aborting merges, cherry-picks and rebases that stopped with conflicts.
+
+use git2::{
+    ObjectType,
+    RepositoryState,
+    ResetType,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+impl GitHelper {
+    /// Abort the merge in progress. HEAD, the index and the working directory
+    /// are reset to the commit that was checked out before the merge and the
+    /// merge state is removed. Fails with Error::NoOperationInProgress if no
+    /// merge is in progress.
+    pub fn merge_abort(&self) -> Result<(), Error> {
+        self.abort_to_head(&[RepositoryState::Merge])
+    }
+
+    /// Abort the cherry-pick in progress. HEAD, the index and the working
+    /// directory are reset to the commit that was checked out before the
+    /// cherry-pick and the cherry-pick state is removed. Fails with
+    /// Error::NoOperationInProgress if no cherry-pick is in progress.
+    pub fn cherry_pick_abort(&self) -> Result<(), Error> {
+        self.abort_to_head(&[
+            RepositoryState::CherryPick,
+            RepositoryState::CherryPickSequence,
+        ])
+    }
+
+    /// Abort the rebase in progress. The rebased branch is checked out again
+    /// at the commit it pointed to before the rebase and the rebase state is
+    /// removed. Fails with Error::NoOperationInProgress if no rebase is in
+    /// progress.
+    pub fn rebase_abort(&self) -> Result<(), Error> {
+        match self.repository_state() {
+            RepositoryState::Rebase
+            | RepositoryState::RebaseInteractive
+            | RepositoryState::RebaseMerge => {}
+            _ => return Err(Error::NoOperationInProgress),
+        }
+
+        let mut rebase = self
+            .repository()
+            .open_rebase(None)
+            .map_err(Error::RebaseAbort)?;
+        rebase.abort().map_err(Error::RebaseAbort)?;
+
+        Ok(())
+    }
+
+    fn abort_to_head(&self, states: &[RepositoryState]) -> Result<(), Error> {
+        let repository = self.repository();
+        if !states.contains(&self.repository_state()) {
+            return Err(Error::NoOperationInProgress);
+        }
+
+        let head = repository
+            .head()
+            .and_then(|head| head.peel(ObjectType::Commit))
+            .map_err(Error::RepositoryHead)?;
+        repository
+            .reset(&head, ResetType::Hard, None)
+            .map_err(Error::Reset)?;
+        repository.cleanup_state().map_err(Error::CleanupState)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::RepositoryState;
+    use std::fs;
+    use tempfile::tempdir;
+
+    use crate::{
+        test_util::{
+            conflicted_cherry_pick,
+            conflicted_merge,
+            conflicted_rebase,
+        },
+        GitHelper,
+    };
+
+    fn assert_pre_operation(dir: &tempfile::TempDir) {
+        let helper = GitHelper::open(dir).unwrap();
+        let repository = helper.repository();
+
+        assert_eq!(RepositoryState::Clean, repository.state());
+        assert_eq!(
+            "refs/heads/master",
+            repository.head().unwrap().name().unwrap()
+        );
+        assert_eq!(
+            "ours",
+            repository
+                .head()
+                .unwrap()
+                .peel_to_commit()
+                .unwrap()
+                .message()
+                .unwrap()
+        );
+        assert_eq!(
+            "ours\n",
+            fs::read_to_string(dir.path().join("conflict")).unwrap()
+        );
+        assert!(!crate::has_conflicts(dir).unwrap());
+        assert!(crate::status(dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn merge_abort() {
+        let dir = conflicted_merge();
+
+        crate::merge_abort(&dir).unwrap();
+        assert_pre_operation(&dir);
+    }
+
+    #[test]
+    fn cherry_pick_abort() {
+        let dir = conflicted_cherry_pick();
+
+        crate::cherry_pick_abort(&dir).unwrap();
+        assert_pre_operation(&dir);
+    }
+
+    #[test]
+    fn rebase_abort() {
+        let dir = conflicted_rebase();
+        assert_eq!(
+            RepositoryState::RebaseMerge,
+            crate::repository_state(&dir).unwrap()
+        );
+
+        crate::rebase_abort(&dir).unwrap();
+        assert_pre_operation(&dir);
+    }
+
+    #[test]
+    fn abort_without_operation() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added first_file").unwrap();
+
+        for result in &[
+            crate::merge_abort(&dir),
+            crate::cherry_pick_abort(&dir),
+            crate::rebase_abort(&dir),
+        ] {
+            match result {
+                Err(crate::Error::NoOperationInProgress) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        let dir = conflicted_cherry_pick();
+        match crate::merge_abort(&dir) {
+            Err(crate::Error::NoOperationInProgress) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! Application of diffs to the index and the working directory, like `git
+//! apply`.
+
+use std::{
+    cell::Cell,
+    path::PathBuf,
+};
+
+use git2::{
+    ApplyLocation,
+    ApplyOptions,
+    Diff,
+    Patch,
+};
+
+use crate::{
+    repo::path_from_bytes,
+    DiffConfig,
+    Error,
+    GitHelper,
+};
+
+/// Source of the diff applied by apply_diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSpec {
+    /// Changes between two revisions given as range like `main..feature` or
+    /// the changes of a single commit compared to its first parent.
+    Range(String),
+
+    /// Patch in the format of `git diff`.
+    Patch(Vec<u8>),
+}
+
+/// Hunk of a diff that does not apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyFailure {
+    /// Path of the file the hunk changes.
+    pub path: PathBuf,
+
+    /// Index of the hunk in the changes of the file, counted from zero in
+    /// the order `git diff` shows them. None if the change of the file has
+    /// no hunks, e.g. for binary files.
+    pub hunk: Option<usize>,
+}
+
+impl GitHelper {
+    /// Apply the diff to the index, the working directory or both. Nothing
+    /// is changed if a single hunk does not apply; the failing hunks are
+    /// returned with Error::ApplyConflict. With `check_only` only checks
+    /// whether the diff applies cleanly without changing anything.
+    pub fn apply_diff(
+        &self,
+        source: &DiffSpec,
+        location: ApplyLocation,
+        check_only: bool,
+    ) -> Result<(), Error> {
+        let diff = match source {
+            DiffSpec::Range(range) => self.range_diff(range)?,
+            DiffSpec::Patch(patch) => Diff::from_buffer(patch).map_err(Error::PatchParse)?,
+        };
+
+        let mut options = ApplyOptions::new();
+        options.check(check_only);
+
+        match self.repository().apply(&diff, location, Some(&mut options)) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                let failures = self.failed_hunks(&diff, location)?;
+                if failures.is_empty() {
+                    Err(Error::Apply(err))
+                } else {
+                    Err(Error::ApplyConflict(failures))
+                }
+            }
+        }
+    }
+
+    /// Return the diff of a range or of a single commit to its first parent.
+    fn range_diff(&self, range: &str) -> Result<Diff<'_>, Error> {
+        let revspec = self
+            .repository()
+            .revparse(range)
+            .map_err(|_| Error::RevisionNotFound(range.to_string()))?;
+
+        let (old, new) = match (revspec.from(), revspec.to()) {
+            (Some(from), Some(to)) => (from.id().to_string(), to.id().to_string()),
+            (Some(from), None) => (format!("{}^", from.id()), from.id().to_string()),
+            _ => return Err(Error::RevisionNotFound(range.to_string())),
+        };
+
+        self.commits_diff(&old, &new, &DiffConfig::default())
+    }
+
+    /// Check every hunk of the diff on its own and return the ones that do
+    /// not apply.
+    fn failed_hunks(
+        &self,
+        diff: &Diff<'_>,
+        location: ApplyLocation,
+    ) -> Result<Vec<ApplyFailure>, Error> {
+        let mut failures = Vec::new();
+
+        for (delta_index, delta) in diff.deltas().enumerate() {
+            let path = delta
+                .new_file()
+                .path_bytes()
+                .or_else(|| delta.old_file().path_bytes())
+                .map(path_from_bytes)
+                .unwrap_or_default();
+
+            let hunk_count = Patch::from_diff(diff, delta_index)
+                .map_err(Error::Diff)?
+                .map_or(0, |patch| patch.num_hunks());
+
+            if hunk_count == 0 {
+                if !self.hunk_applies(diff, location, delta_index, None) {
+                    failures.push(ApplyFailure { path, hunk: None });
+                }
+                continue;
+            }
+
+            for hunk_index in 0..hunk_count {
+                if !self.hunk_applies(diff, location, delta_index, Some(hunk_index)) {
+                    failures.push(ApplyFailure {
+                        path: path.clone(),
+                        hunk: Some(hunk_index),
+                    });
+                }
+            }
+        }
+
+        Ok(failures)
+    }
+
+    /// Check whether only the given hunk of the given delta applies. Without
+    /// a hunk index all hunks of the delta are checked.
+    fn hunk_applies(
+        &self,
+        diff: &Diff<'_>,
+        location: ApplyLocation,
+        delta_index: usize,
+        hunk_index: Option<usize>,
+    ) -> bool {
+        let current_delta = Cell::new(0);
+        let current_hunk = Cell::new(0);
+
+        let mut options = ApplyOptions::new();
+        options
+            .check(true)
+            .delta_callback(|delta| {
+                if delta.is_none() {
+                    return true;
+                }
+
+                let selected = current_delta.get() == delta_index;
+                current_delta.set(current_delta.get() + 1);
+                selected
+            })
+            .hunk_callback(|hunk| {
+                if hunk.is_none() {
+                    return true;
+                }
+
+                let selected = hunk_index.is_none_or(|index| current_hunk.get() == index);
+                current_hunk.set(current_hunk.get() + 1);
+                selected
+            });
+
+        self.repository()
+            .apply(diff, location, Some(&mut options))
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::ApplyLocation;
+    use std::fs;
+    use tempfile::{
+        tempdir,
+        TempDir,
+    };
+
+    use super::{
+        ApplyFailure,
+        DiffSpec,
+    };
+    use crate::{
+        CheckoutOptions,
+        GitHelper,
+    };
+
+    const BASE: &str = concat!(
+        "one\n",
+        "two\n",
+        "three\n",
+        "four\n",
+        "five\n",
+        "six\n",
+        "seven\n",
+        "eight\n",
+        "nine\n",
+        "ten\n",
+        "eleven\n",
+        "twelve\n",
+        "thirteen\n",
+        "fourteen\n",
+    );
+    const CHANGED: &str = concat!(
+        "one\n",
+        "TWO\n",
+        "three\n",
+        "four\n",
+        "five\n",
+        "six\n",
+        "seven\n",
+        "eight\n",
+        "nine\n",
+        "ten\n",
+        "eleven\n",
+        "TWELVE\n",
+        "thirteen\n",
+        "fourteen\n",
+    );
+
+    #[test]
+    fn fixtures() {
+        assert!(BASE.contains("\neleven\ntwelve\nthirteen\n"));
+        assert!(CHANGED.contains("\neleven\nTWELVE\nthirteen\n"));
+        assert_eq!(14, BASE.lines().count());
+        assert_eq!(14, CHANGED.lines().count());
+        assert!(!BASE.contains('\\') && !CHANGED.contains('\\'));
+    }
+
+    /// Create a repository with a base commit and a commit changing two
+    /// distant lines of the file `numbers` on `master`. HEAD is detached at
+    /// the base commit.
+    fn at_base() -> TempDir {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        for content in &[BASE, CHANGED] {
+            fs::write(dir.path().join("numbers"), content).unwrap();
+            helper.stage(&["numbers"]).unwrap();
+            helper.commit("Changed numbers").unwrap();
+        }
+        helper
+            .checkout_commit("HEAD~1", &CheckoutOptions::default())
+            .unwrap();
+
+        dir
+    }
+
+    fn staged(dir: &TempDir) -> Vec<u8> {
+        let helper = GitHelper::open(dir).unwrap();
+        let index = helper.repository().index().unwrap();
+        let entry = index.get_path(std::path::Path::new("numbers"), 0).unwrap();
+
+        let blob = helper.repository().find_blob(entry.id).unwrap();
+        blob.content().to_vec()
+    }
+
+    #[test]
+    fn apply_diff_range() {
+        let dir = at_base();
+        let source = DiffSpec::Range("master~1..master".to_string());
+
+        crate::apply_diff(&dir, &source, ApplyLocation::WorkDir, false).unwrap();
+        assert_eq!(
+            CHANGED,
+            fs::read_to_string(dir.path().join("numbers")).unwrap()
+        );
+        assert_eq!(BASE.as_bytes(), staged(&dir).as_slice());
+
+        let dir = at_base();
+        crate::apply_diff(
+            &dir,
+            &DiffSpec::Range("master".to_string()),
+            ApplyLocation::Both,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            CHANGED,
+            fs::read_to_string(dir.path().join("numbers")).unwrap()
+        );
+        assert_eq!(CHANGED.as_bytes(), staged(&dir).as_slice());
+    }
+
+    #[test]
+    fn apply_diff_patch() {
+        let source = at_base();
+        let patch = crate::diff_text(
+            &source,
+            "master~1",
+            "master",
+            &crate::DiffConfig::default(),
+            &crate::RenderOptions::default(),
+        )
+        .unwrap();
+
+        let dir = at_base();
+        crate::apply_diff(
+            &dir,
+            &DiffSpec::Patch(patch.into_bytes()),
+            ApplyLocation::Index,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            BASE,
+            fs::read_to_string(dir.path().join("numbers")).unwrap()
+        );
+        assert_eq!(CHANGED.as_bytes(), staged(&dir).as_slice());
+    }
+
+    #[test]
+    fn apply_diff_check_only() {
+        let dir = at_base();
+        let source = DiffSpec::Range("master~1..master".to_string());
+
+        crate::apply_diff(&dir, &source, ApplyLocation::Both, true).unwrap();
+        assert_eq!(
+            BASE,
+            fs::read_to_string(dir.path().join("numbers")).unwrap()
+        );
+        assert_eq!(BASE.as_bytes(), staged(&dir).as_slice());
+    }
+
+    #[test]
+    fn apply_diff_conflict() {
+        let dir = at_base();
+        let conflicting = BASE.replace("twelve", "zwoelf");
+        fs::write(dir.path().join("numbers"), &conflicting).unwrap();
+        let source = DiffSpec::Range("master~1..master".to_string());
+
+        for check_only in &[true, false] {
+            match crate::apply_diff(&dir, &source, ApplyLocation::WorkDir, *check_only) {
+                Err(crate::Error::ApplyConflict(failures)) => assert_eq!(
+                    vec![ApplyFailure {
+                        path: "numbers".into(),
+                        hunk: Some(1),
+                    }],
+                    failures
+                ),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        assert_eq!(
+            conflicting,
+            fs::read_to_string(dir.path().join("numbers")).unwrap()
+        );
+
+        match crate::apply_diff(
+            &dir,
+            &DiffSpec::Patch(b"garbage".to_vec()),
+            ApplyLocation::WorkDir,
+            false,
+        ) {
+            Err(crate::Error::PatchParse(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
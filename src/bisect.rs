@@ -0,0 +1,442 @@
+//! Finding the commit that introduced a change by bisecting the history.
+//!
+//! The state of a bisect is kept in `githelper-bisect` in the git directory
+//! and is not compatible with the state of `git bisect`. The file has one
+//! entry per line made of a key and a value separated by a space:
+//!
+//! - `original <ref or oid>`: what HEAD pointed to before the bisect started
+//! - `bad <oid>`: the newest commit known to be bad
+//! - `good <oid>`: a commit known to be good, may appear multiple times
+//! - `skip <oid>`: a commit that can not be tested, may appear multiple times
+//! - `current <oid>`: the candidate that is checked out for testing
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+};
+
+use git2::{
+    Oid,
+    Sort,
+};
+
+use crate::{
+    CheckoutOptions,
+    Error,
+    GitHelper,
+};
+
+/// Name of the bisect state file in the git directory.
+const STATE_FILE: &str = "githelper-bisect";
+
+/// Result of testing the checked out candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The candidate does not have the change.
+    Good,
+    /// The candidate has the change.
+    Bad,
+    /// The candidate can not be tested.
+    Skip,
+}
+
+/// Outcome of a bisect step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BisectStep {
+    /// The commit that is checked out and has to be tested next.
+    Candidate(Oid),
+    /// The first bad commit.
+    Culprit(Oid),
+}
+
+/// State of a bisect in progress.
+#[derive(Debug, Default)]
+struct BisectState {
+    original: String,
+    bad: Option<Oid>,
+    good: Vec<Oid>,
+    skip: Vec<Oid>,
+    current: Option<Oid>,
+}
+
+impl BisectState {
+    fn parse(content: &str) -> Result<Self, Error> {
+        let mut state = Self::default();
+
+        for line in content.lines().filter(|line| !line.is_empty()) {
+            let invalid = || Error::BisectStateInvalid(line.to_string());
+            let (key, value) = line.split_once(' ').ok_or_else(invalid)?;
+            let oid = || Oid::from_str(value).map_err(|_| invalid());
+
+            match key {
+                "original" => state.original = value.to_string(),
+                "bad" => state.bad = Some(oid()?),
+                "good" => state.good.push(oid()?),
+                "skip" => state.skip.push(oid()?),
+                "current" => state.current = Some(oid()?),
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(state)
+    }
+
+    fn serialize(&self) -> String {
+        let mut content = format!("original {}\n", self.original);
+        if let Some(bad) = self.bad {
+            content.push_str(&format!("bad {}\n", bad));
+        }
+        for good in &self.good {
+            content.push_str(&format!("good {}\n", good));
+        }
+        for skip in &self.skip {
+            content.push_str(&format!("skip {}\n", skip));
+        }
+        if let Some(current) = self.current {
+            content.push_str(&format!("current {}\n", current));
+        }
+
+        content
+    }
+}
+
+impl GitHelper {
+    /// Start bisecting between a bad and a good revision. The first candidate
+    /// is checked out with a detached HEAD and returned, or the culprit if
+    /// there is nothing left to test. Restarting a bisect in progress keeps
+    /// the HEAD bisect_reset returns to.
+    pub fn bisect_start(&self, bad: &str, good: &str) -> Result<BisectStep, Error> {
+        self.ensure_clean_state()?;
+
+        let original = match self.read_bisect_state()? {
+            Some(state) => state.original,
+            None => {
+                let head = self.repository().head().map_err(Error::RepositoryHead)?;
+                if head.is_branch() {
+                    String::from_utf8_lossy(head.name_bytes()).into_owned()
+                } else {
+                    head.target().ok_or(Error::HeadNotBranch)?.to_string()
+                }
+            }
+        };
+
+        let mut state = BisectState {
+            original,
+            bad: Some(self.find_commit(bad)?.id()),
+            good: vec![self.find_commit(good)?.id()],
+            ..BisectState::default()
+        };
+
+        self.bisect_next(&mut state)
+    }
+
+    /// Record the verdict for the checked out candidate and check out the
+    /// next candidate. Returns the next candidate or the culprit once it is
+    /// found. Fails with Error::NoBisectInProgress if no bisect is in
+    /// progress and with Error::BisectInconclusive if only skipped commits
+    /// are left.
+    pub fn bisect_step(&self, verdict: Verdict) -> Result<BisectStep, Error> {
+        let mut state = self.read_bisect_state()?.ok_or(Error::NoBisectInProgress)?;
+
+        if let Some(current) = state.current.take() {
+            match verdict {
+                Verdict::Good => state.good.push(current),
+                Verdict::Bad => state.bad = Some(current),
+                Verdict::Skip => state.skip.push(current),
+            }
+        }
+
+        self.bisect_next(&mut state)
+    }
+
+    /// Stop bisecting, check out what HEAD pointed to before the bisect
+    /// started and remove the bisect state. Fails with
+    /// Error::NoBisectInProgress if no bisect is in progress.
+    pub fn bisect_reset(&self) -> Result<(), Error> {
+        let state = self.read_bisect_state()?.ok_or(Error::NoBisectInProgress)?;
+
+        let options = CheckoutOptions::default();
+        match state.original.strip_prefix("refs/heads/") {
+            Some(branch) => self.checkout_branch(branch, &options)?,
+            None => self.checkout_commit(&state.original, &options)?,
+        };
+
+        fs::remove_file(self.bisect_state_path()).map_err(Error::BisectState)?;
+
+        Ok(())
+    }
+
+    /// Pick the candidate that splits the remaining commits most evenly,
+    /// check it out and store the state.
+    fn bisect_next(&self, state: &mut BisectState) -> Result<BisectStep, Error> {
+        let bad = state
+            .bad
+            .ok_or_else(|| Error::BisectStateInvalid("missing bad commit".to_string()))?;
+
+        // commits that may have introduced the change, bad one included
+        let remaining = self.bisect_ancestors(bad, &state.good)?;
+        if remaining.len() <= 1 {
+            self.write_bisect_state(state)?;
+            return Ok(BisectStep::Culprit(bad));
+        }
+
+        let counts = self.bisect_counts(&remaining)?;
+        let mut best = None;
+        for (oid, ancestors) in remaining.iter().zip(counts).rev() {
+            if *oid == bad || state.skip.contains(oid) {
+                continue;
+            }
+
+            let score = ancestors.min(remaining.len() - ancestors);
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((*oid, score));
+            }
+        }
+
+        let candidate = match best {
+            Some((candidate, _)) => candidate,
+            None => return Err(Error::BisectInconclusive(remaining)),
+        };
+
+        self.checkout_commit(&candidate.to_string(), &CheckoutOptions::default())?;
+        state.current = Some(candidate);
+        self.write_bisect_state(state)?;
+
+        Ok(BisectStep::Candidate(candidate))
+    }
+
+    /// Return the commits reachable from the commit but not from any of the
+    /// good commits, the commit itself included. Parents come before their
+    /// children.
+    fn bisect_ancestors(&self, oid: Oid, good: &[Oid]) -> Result<Vec<Oid>, Error> {
+        let mut revwalk = self
+            .repository()
+            .revwalk()
+            .map_err(Error::RepositoryRevwalk)?;
+        revwalk
+            .set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)
+            .map_err(Error::RepositoryRevwalk)?;
+        revwalk.push(oid).map_err(Error::RepositoryRevwalk)?;
+        for good in good {
+            revwalk.hide(*good).map_err(Error::RepositoryRevwalk)?;
+        }
+
+        revwalk
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::RepositoryRevwalk)
+    }
+
+    /// Count for every commit how many of the commits are reachable from it,
+    /// itself included. The commits have to be sorted parents first, so a
+    /// single pass can build the set of reachable commits of each commit
+    /// from the sets of its parents.
+    fn bisect_counts(&self, commits: &[Oid]) -> Result<Vec<usize>, Error> {
+        let positions = commits
+            .iter()
+            .enumerate()
+            .map(|(position, oid)| (*oid, position))
+            .collect::<HashMap<_, _>>();
+        let words = commits.len().div_ceil(64);
+
+        let mut reachable: Vec<Vec<u64>> = Vec::with_capacity(commits.len());
+        for (position, oid) in commits.iter().enumerate() {
+            let commit = self
+                .repository()
+                .find_commit(*oid)
+                .map_err(Error::RepositoryFindCommit)?;
+
+            let mut set = vec![0; words];
+            set[position / 64] |= 1 << (position % 64);
+            for parent in commit.parent_ids() {
+                if let Some(parent) = positions.get(&parent) {
+                    for (word, parent_word) in set.iter_mut().zip(&reachable[*parent]) {
+                        *word |= parent_word;
+                    }
+                }
+            }
+            reachable.push(set);
+        }
+
+        Ok(reachable
+            .iter()
+            .map(|set| set.iter().map(|word| word.count_ones() as usize).sum())
+            .collect())
+    }
+
+    fn bisect_state_path(&self) -> PathBuf {
+        self.repository().path().join(STATE_FILE)
+    }
+
+    fn read_bisect_state(&self) -> Result<Option<BisectState>, Error> {
+        let path = self.bisect_state_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path).map_err(Error::BisectState)?;
+        BisectState::parse(&content).map(Some)
+    }
+
+    fn write_bisect_state(&self, state: &BisectState) -> Result<(), Error> {
+        fs::write(self.bisect_state_path(), state.serialize()).map_err(Error::BisectState)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::Oid;
+    use std::fs;
+    use tempfile::tempdir;
+
+    use super::{
+        BisectStep,
+        Verdict,
+    };
+    use crate::GitHelper;
+
+    /// Create a linear history of eight commits of the file `state`. The
+    /// commit with the given index and all later ones have the content
+    /// `broken`.
+    fn linear_history(helper: &GitHelper, broken_from: usize) -> Vec<Oid> {
+        let workdir = helper.workdir().unwrap().to_path_buf();
+
+        (0..8)
+            .map(|index| {
+                let content = if index >= broken_from { "broken" } else { "ok" };
+                fs::write(workdir.join("state"), content).unwrap();
+                fs::write(workdir.join("counter"), index.to_string()).unwrap();
+                helper.stage_all().unwrap();
+                helper.commit(&format!("Commit {}", index)).unwrap();
+
+                helper.repository().head().unwrap().target().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bisect() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        let commits = linear_history(&helper, 5);
+
+        let mut step = crate::bisect_start(&dir, "HEAD", &commits[0].to_string()).unwrap();
+        let mut steps = 0;
+        let culprit = loop {
+            match step {
+                BisectStep::Candidate(candidate) => {
+                    assert_eq!(
+                        candidate,
+                        helper.repository().head().unwrap().target().unwrap()
+                    );
+                    let verdict = match fs::read_to_string(dir.path().join("state")).unwrap() {
+                        content if content == "broken" => Verdict::Bad,
+                        _ => Verdict::Good,
+                    };
+                    step = crate::bisect_step(&dir, verdict).unwrap();
+                    steps += 1;
+                }
+                BisectStep::Culprit(culprit) => break culprit,
+            }
+        };
+
+        assert_eq!(commits[5], culprit);
+        assert!(steps <= 3, "took {} steps", steps);
+
+        crate::bisect_reset(&dir).unwrap();
+        let head = helper.repository().head().unwrap();
+        assert_eq!("refs/heads/master", head.name().unwrap());
+        assert_eq!(Some(commits[7]), head.target());
+        assert!(!helper.repository().path().join("githelper-bisect").exists());
+    }
+
+    #[test]
+    fn bisect_skip() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        let commits = linear_history(&helper, 2);
+
+        let step =
+            crate::bisect_start(&dir, &commits[3].to_string(), &commits[0].to_string()).unwrap();
+        let candidate = match step {
+            BisectStep::Candidate(candidate) => candidate,
+            other => panic!("unexpected step: {:?}", other),
+        };
+
+        // skipping the only other candidate leaves nothing to test
+        let step = crate::bisect_step(&dir, Verdict::Skip).unwrap();
+        let other = match step {
+            BisectStep::Candidate(other) => other,
+            other => panic!("unexpected step: {:?}", other),
+        };
+        assert_ne!(candidate, other);
+
+        match crate::bisect_step(&dir, Verdict::Skip) {
+            Err(crate::Error::BisectInconclusive(remaining)) => assert_eq!(3, remaining.len()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bisect_counts_with_merge() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        let repository = helper.repository();
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree = repository
+            .find_tree(repository.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let commit = |message: &str, parents: &[Oid]| {
+            let parents = parents
+                .iter()
+                .map(|parent| repository.find_commit(*parent).unwrap())
+                .collect::<Vec<_>>();
+            repository
+                .commit(
+                    None,
+                    &signature,
+                    &signature,
+                    message,
+                    &tree,
+                    &parents.iter().collect::<Vec<_>>(),
+                )
+                .unwrap()
+        };
+
+        // good -> base -> left, right -> merge -> tip
+        let good = commit("good", &[]);
+        let base = commit("base", &[good]);
+        let left = commit("left", &[base]);
+        let right = commit("right", &[base]);
+        let merge = commit("merge", &[left, right]);
+        let tip = commit("tip", &[merge]);
+
+        let remaining = helper.bisect_ancestors(tip, &[good]).unwrap();
+        assert_eq!(5, remaining.len());
+        let counts = remaining
+            .iter()
+            .copied()
+            .zip(helper.bisect_counts(&remaining).unwrap())
+            .collect::<std::collections::HashMap<_, _>>();
+        assert_eq!(1, counts[&base]);
+        assert_eq!(2, counts[&left]);
+        assert_eq!(2, counts[&right]);
+        assert_eq!(4, counts[&merge]);
+        assert_eq!(5, counts[&tip]);
+    }
+
+    #[test]
+    fn bisect_without_start() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        match crate::bisect_step(&dir, Verdict::Good) {
+            Err(crate::Error::NoBisectInProgress) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match crate::bisect_reset(&dir) {
+            Err(crate::Error::NoBisectInProgress) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,425 @@
+//! Listing of the local branches of a repository.
+
+use git2::{
+    BranchType,
+    Oid,
+};
+
+use crate::{
+    time::Timestamp,
+    Error,
+    GitHelper,
+};
+
+/// Options for delete_merged_branches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchCleanupOptions {
+    /// Branch the deleted branches have to be merged into. If None HEAD is
+    /// used.
+    pub into: Option<String>,
+
+    /// Only return the branches that would be deleted.
+    pub dry_run: bool,
+
+    /// Branches that are never deleted even if they are merged.
+    pub protected: Vec<String>,
+}
+
+impl Default for BranchCleanupOptions {
+    fn default() -> Self {
+        Self {
+            into: None,
+            dry_run: false,
+            protected: vec![
+                "main".to_string(),
+                "master".to_string(),
+                "develop".to_string(),
+            ],
+        }
+    }
+}
+
+/// Local branch with its upstream and tip commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchInfo {
+    /// Short name of the branch, e.g. `master`.
+    pub name: String,
+
+    /// Id of the commit the branch points to.
+    pub oid: Oid,
+
+    /// True if HEAD points to the branch.
+    pub is_head: bool,
+
+    /// Full name of the configured upstream reference, e.g.
+    /// `refs/remotes/origin/master`. None if no upstream is configured.
+    pub upstream: Option<String>,
+
+    /// Number of commits on the branch that are not on the upstream and
+    /// number of commits on the upstream that are not on the branch. None if
+    /// there is no upstream or the upstream reference does not exist, e.g.
+    /// because it was not fetched yet.
+    pub ahead_behind: Option<(usize, usize)>,
+
+    /// First line of the message of the tip commit.
+    pub summary: String,
+
+    /// Time the tip commit was committed at.
+    pub committed_at: Timestamp,
+}
+
+impl GitHelper {
+    /// List the local branches sorted by name together with their upstream,
+    /// how far they are ahead of and behind their upstream and their tip
+    /// commit.
+    pub fn branch_details(&self) -> Result<Vec<BranchInfo>, Error> {
+        let repository = self.repository();
+        let branches = repository
+            .branches(Some(BranchType::Local))
+            .map_err(Error::RepositoryReferences)?;
+
+        let mut details = Vec::new();
+        for branch in branches {
+            let (branch, _) = branch.map_err(Error::RepositoryReferences)?;
+            let reference = branch.get();
+            let refname = String::from_utf8_lossy(reference.name_bytes()).into_owned();
+            let name =
+                String::from_utf8_lossy(branch.name_bytes().map_err(Error::RepositoryReferences)?)
+                    .into_owned();
+            let commit = reference
+                .peel_to_commit()
+                .map_err(Error::RepositoryFindCommit)?;
+
+            let upstream = repository
+                .branch_upstream_name(&refname)
+                .ok()
+                .map(|name| String::from_utf8_lossy(&name).into_owned());
+
+            let ahead_behind = upstream
+                .as_ref()
+                .and_then(|upstream| repository.refname_to_id(upstream).ok())
+                .and_then(|upstream| repository.graph_ahead_behind(commit.id(), upstream).ok());
+
+            details.push(BranchInfo {
+                name,
+                oid: commit.id(),
+                is_head: branch.is_head(),
+                upstream,
+                ahead_behind,
+                summary: String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default())
+                    .into_owned(),
+                committed_at: commit.committer().when().into(),
+            });
+        }
+
+        details.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(details)
+    }
+
+    /// True if the commit the revision resolves to is the tip of the local
+    /// branch or one of its ancestors. Fails with Error::BranchNotFound if
+    /// the branch does not exist.
+    pub fn branch_contains(&self, branch: &str, revision: &str) -> Result<bool, Error> {
+        let commit = self.find_commit(revision)?;
+        let tip = self
+            .repository()
+            .find_branch(branch, BranchType::Local)
+            .and_then(|branch| branch.get().peel_to_commit())
+            .map_err(|_| Error::BranchNotFound(branch.to_string()))?;
+
+        self.contains(tip.id(), commit.id())
+    }
+
+    /// List the names of the branches whose tip is the commit the revision
+    /// resolves to or one of its descendants, sorted by name. With
+    /// `include_remote` remote-tracking branches like `origin/master` are
+    /// listed too.
+    pub fn branches_containing(
+        &self,
+        revision: &str,
+        include_remote: bool,
+    ) -> Result<Vec<String>, Error> {
+        let commit = self.find_commit(revision)?.id();
+        let branch_type = if include_remote {
+            None
+        } else {
+            Some(BranchType::Local)
+        };
+
+        let mut names = Vec::new();
+        for branch in self
+            .repository()
+            .branches(branch_type)
+            .map_err(Error::RepositoryReferences)?
+        {
+            let (branch, _) = branch.map_err(Error::RepositoryReferences)?;
+            // symbolic references like origin/HEAD only point to another branch
+            let tip = match branch.get().target() {
+                Some(tip) => tip,
+                None => continue,
+            };
+
+            if self.contains(tip, commit)? {
+                let name = branch.name_bytes().map_err(Error::RepositoryReferences)?;
+                names.push(String::from_utf8_lossy(name).into_owned());
+            }
+        }
+
+        names.sort();
+
+        Ok(names)
+    }
+
+    /// List the local branches whose tip is an ancestor of the given branch or
+    /// of HEAD if no branch is given, sorted by name, like `git branch
+    /// --merged`. The current branch and the given branch are not listed.
+    pub fn merged_branches(&self, into: Option<&str>) -> Result<Vec<String>, Error> {
+        let repository = self.repository();
+        let target = match into {
+            Some(into) => repository
+                .find_branch(into, BranchType::Local)
+                .and_then(|branch| branch.get().peel_to_commit())
+                .map_err(|_| Error::BranchNotFound(into.to_string()))?,
+            None => match self.head_commit() {
+                Some(commit) => commit,
+                None => return Ok(Vec::new()),
+            },
+        };
+
+        let mut merged = Vec::new();
+        for branch in repository
+            .branches(Some(BranchType::Local))
+            .map_err(Error::RepositoryReferences)?
+        {
+            let (branch, _) = branch.map_err(Error::RepositoryReferences)?;
+            let name =
+                String::from_utf8_lossy(branch.name_bytes().map_err(Error::RepositoryReferences)?)
+                    .into_owned();
+            if branch.is_head() || Some(name.as_str()) == into {
+                continue;
+            }
+
+            if let Some(tip) = branch.get().target() {
+                if self.contains(target.id(), tip)? {
+                    merged.push(name);
+                }
+            }
+        }
+
+        merged.sort();
+
+        Ok(merged)
+    }
+
+    /// Delete the local branches `merged_branches` lists except for the
+    /// protected ones. Returns the names of the deleted branches or of the
+    /// branches that would be deleted when `dry_run` is set.
+    pub fn delete_merged_branches(
+        &self,
+        options: &BranchCleanupOptions,
+    ) -> Result<Vec<String>, Error> {
+        let deleted = self
+            .merged_branches(options.into.as_deref())?
+            .into_iter()
+            .filter(|name| !options.protected.contains(name))
+            .collect::<Vec<_>>();
+
+        if options.dry_run {
+            return Ok(deleted);
+        }
+
+        for name in &deleted {
+            self.repository()
+                .find_branch(name, BranchType::Local)
+                .and_then(|mut branch| branch.delete())
+                .map_err(Error::BranchDelete)?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// True if the commit is the tip or an ancestor of the tip.
+    fn contains(&self, tip: Oid, commit: Oid) -> Result<bool, Error> {
+        if tip == commit {
+            return Ok(true);
+        }
+
+        self.repository()
+            .graph_descendant_of(tip, commit)
+            .map_err(Error::RepositoryRevwalk)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::tempdir;
+
+    use super::BranchCleanupOptions;
+    use crate::GitHelper;
+
+    #[test]
+    fn branch_details() {
+        let upstream = tempdir().unwrap();
+        let upstream_helper = GitHelper::init(&upstream).unwrap();
+        fs::write(upstream.path().join("first_file"), "first data").unwrap();
+        upstream_helper.stage_all().unwrap();
+        upstream_helper.commit("First commit").unwrap();
+
+        let local = tempdir().unwrap();
+        let helper = GitHelper::init(&local).unwrap();
+        helper
+            .repository()
+            .remote("origin", upstream.path().to_str().unwrap())
+            .unwrap();
+        helper.fetch(&crate::FetchOptions::default()).unwrap();
+        helper.checkout_remote_branch("origin", "master").unwrap();
+
+        // one commit ahead locally, two commits behind upstream
+        helper.commit("Local commit").unwrap();
+        upstream_helper.commit("Upstream commit").unwrap();
+        upstream_helper.commit("Another upstream commit").unwrap();
+        helper.fetch(&crate::FetchOptions::default()).unwrap();
+
+        let head = helper.head_commit().unwrap();
+        helper
+            .repository()
+            .branch("untracked", &head, false)
+            .unwrap();
+
+        let details = crate::branch_details(&local).unwrap();
+        assert_eq!(2, details.len());
+
+        let master = &details[0];
+        assert_eq!("master", master.name);
+        assert!(master.is_head);
+        assert_eq!(head.id(), master.oid);
+        assert_eq!(
+            Some("refs/remotes/origin/master".to_string()),
+            master.upstream
+        );
+        assert_eq!(Some((1, 2)), master.ahead_behind);
+        assert_eq!("Local commit", master.summary);
+
+        let untracked = &details[1];
+        assert_eq!("untracked", untracked.name);
+        assert!(!untracked.is_head);
+        assert_eq!(None, untracked.upstream);
+        assert_eq!(None, untracked.ahead_behind);
+    }
+
+    #[test]
+    fn branches_containing() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.commit_empty("Initial commit").unwrap();
+        let base = helper.head_commit().unwrap();
+        let repository = helper.repository();
+        repository.branch("release", &base, false).unwrap();
+
+        fs::write(dir.path().join("fix"), "fix").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Fix").unwrap();
+        let fix = helper.head_commit().unwrap().id().to_string();
+
+        // the fix is merged into develop but not into release
+        helper.commit("Develop commit").unwrap();
+        let develop = helper.head_commit().unwrap();
+        repository.branch("develop", &develop, false).unwrap();
+
+        assert!(crate::branch_contains(&dir, "develop", &fix).unwrap());
+        assert!(crate::branch_contains(&dir, "master", &fix).unwrap());
+        assert!(!crate::branch_contains(&dir, "release", &fix).unwrap());
+        assert!(crate::branch_contains(&dir, "release", "HEAD~2").unwrap());
+
+        assert_eq!(
+            vec!["develop".to_string(), "master".to_string()],
+            crate::branches_containing(&dir, &fix, false).unwrap()
+        );
+
+        repository
+            .reference("refs/remotes/origin/release", develop.id(), false, "test")
+            .unwrap();
+        repository
+            .reference_symbolic(
+                "refs/remotes/origin/HEAD",
+                "refs/remotes/origin/release",
+                false,
+                "test",
+            )
+            .unwrap();
+        assert_eq!(
+            vec![
+                "develop".to_string(),
+                "master".to_string(),
+                "origin/release".to_string()
+            ],
+            crate::branches_containing(&dir, &fix, true).unwrap()
+        );
+
+        match crate::branch_contains(&dir, "missing", &fix) {
+            Err(crate::Error::BranchNotFound(name)) => assert_eq!("missing", name),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn delete_merged_branches() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.commit_empty("Initial commit").unwrap();
+        let repository = helper.repository();
+        let base = helper.head_commit().unwrap();
+        repository.branch("develop", &base, false).unwrap();
+        repository.branch("unmerged", &base, false).unwrap();
+
+        // commit on feature and fast-forward master to it
+        repository.branch("feature", &base, false).unwrap();
+        repository.set_head("refs/heads/feature").unwrap();
+        helper.commit("Feature commit").unwrap();
+        let feature = helper.head_commit().unwrap();
+        repository.set_head("refs/heads/master").unwrap();
+        repository
+            .reference("refs/heads/master", feature.id(), true, "merge feature")
+            .unwrap();
+
+        repository.set_head("refs/heads/unmerged").unwrap();
+        helper.commit("Unmerged commit").unwrap();
+        repository.set_head("refs/heads/master").unwrap();
+
+        assert_eq!(
+            vec!["develop".to_string(), "feature".to_string()],
+            crate::merged_branches(&dir, None).unwrap()
+        );
+        // the current branch is never listed
+        assert_eq!(
+            vec!["develop".to_string()],
+            crate::merged_branches(&dir, Some("feature")).unwrap()
+        );
+
+        let options = BranchCleanupOptions {
+            dry_run: true,
+            ..BranchCleanupOptions::default()
+        };
+        assert_eq!(
+            vec!["feature".to_string()],
+            crate::delete_merged_branches(&dir, &options).unwrap()
+        );
+        assert!(repository
+            .find_branch("feature", git2::BranchType::Local)
+            .is_ok());
+
+        assert_eq!(
+            vec!["feature".to_string()],
+            crate::delete_merged_branches(&dir, &BranchCleanupOptions::default()).unwrap()
+        );
+        let mut names = repository
+            .branches(Some(git2::BranchType::Local))
+            .unwrap()
+            .map(|branch| branch.unwrap().0.name().unwrap().unwrap().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(vec!["develop", "master", "unmerged"], names);
+    }
+}
@@ -0,0 +1,208 @@
+//! Opt-in cache of opened repositories for the free functions.
+//!
+//! git2 repositories can not be shared between threads so every thread has
+//! its own cache. The cache of a thread is disabled until a capacity is set
+//! with [`crate::set_repo_cache_capacity`].
+
+use std::{
+    cell::RefCell,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+    time::SystemTime,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+thread_local! {
+    static CACHE: RefCell<RepoCache> = RefCell::new(RepoCache::new(0));
+}
+
+/// Least recently used cache of opened repositories keyed by their
+/// canonicalized path. A cached repository is opened again when the
+/// modification time of its git directory changed since it was opened.
+/// Errors while looking up the cache, e.g. paths that can not be
+/// canonicalized, open the repository without caching it.
+pub struct RepoCache {
+    capacity: usize,
+    // most recently used entry first
+    entries: Vec<CacheEntry>,
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    git_dir: PathBuf,
+    modified: SystemTime,
+    helper: Rc<GitHelper>,
+}
+
+impl RepoCache {
+    /// Create an empty cache holding at most `capacity` repositories. A
+    /// capacity of 0 disables caching.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Change the capacity of the cache, dropping the least recently used
+    /// repositories that do not fit anymore.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.entries.truncate(capacity);
+    }
+
+    /// Return the number of cached repositories.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return true if no repository is cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop all cached repositories.
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+
+    /// Return the cached repository for the path or open and cache it.
+    pub fn get<P: AsRef<Path>>(&mut self, repo_path: P) -> Result<Rc<GitHelper>, Error> {
+        if self.capacity == 0 {
+            return GitHelper::open(repo_path).map(Rc::new);
+        }
+
+        let path = match repo_path.as_ref().canonicalize() {
+            Ok(path) => path,
+            Err(_) => return GitHelper::open(repo_path).map(Rc::new),
+        };
+
+        if let Some(position) = self.entries.iter().position(|entry| entry.path == path) {
+            let entry = self.entries.remove(position);
+            if modified(&entry.git_dir) == Some(entry.modified) {
+                let helper = Rc::clone(&entry.helper);
+                self.entries.insert(0, entry);
+                return Ok(helper);
+            }
+        }
+
+        let helper = Rc::new(GitHelper::open(&path)?);
+        let git_dir = helper.repository().path().to_path_buf();
+        if let Some(modified) = modified(&git_dir) {
+            self.entries.insert(
+                0,
+                CacheEntry {
+                    path,
+                    git_dir,
+                    modified,
+                    helper: Rc::clone(&helper),
+                },
+            );
+            self.entries.truncate(self.capacity);
+        }
+
+        Ok(helper)
+    }
+}
+
+/// Set how many repositories the free functions keep open on the current
+/// thread. 0, the default, disables the cache.
+pub(crate) fn set_capacity(capacity: usize) {
+    CACHE.with(|cache| cache.borrow_mut().set_capacity(capacity))
+}
+
+/// Open the repository through the cache of the current thread.
+pub(crate) fn open<P: AsRef<Path>>(repo_path: P) -> Result<Rc<GitHelper>, Error> {
+    CACHE.with(|cache| match cache.try_borrow_mut() {
+        Ok(mut cache) => cache.get(repo_path),
+        Err(_) => GitHelper::open(repo_path).map(Rc::new),
+    })
+}
+
+/// Return the modification time of the path or None if it can not be read.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::PathBuf,
+        rc::Rc,
+        thread,
+    };
+    use tempfile::tempdir;
+
+    use super::RepoCache;
+
+    #[test]
+    fn repo_cache_reuse_and_invalidate() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        let mut cache = RepoCache::new(1);
+
+        let first = cache.get(&dir).unwrap();
+        assert!(Rc::ptr_eq(&first, &cache.get(&dir).unwrap()));
+        assert!(Rc::ptr_eq(
+            &first,
+            &cache.get(dir.path().join(".")).unwrap()
+        ));
+
+        // staging replaces the index in the git directory which changes its
+        // modification time
+        fs::write(dir.path().join("file"), "data").unwrap();
+        first.stage(&["file"]).unwrap();
+        assert!(!Rc::ptr_eq(&first, &cache.get(&dir).unwrap()));
+
+        let other = tempdir().unwrap();
+        crate::init(&other).unwrap();
+        cache.get(&other).unwrap();
+        assert_eq!(1, cache.len());
+
+        let mut disabled = RepoCache::new(0);
+        disabled.get(&dir).unwrap();
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn repo_cache_threads() {
+        let threads = (0..2)
+            .map(|number| {
+                thread::spawn(move || {
+                    let dir = tempdir().unwrap();
+                    crate::init(&dir).unwrap();
+                    crate::set_repo_cache_capacity(4);
+
+                    for index in 0..20 {
+                        let name = format!("file_{}_{:02}", number, index);
+                        fs::write(dir.path().join(&name), "data").unwrap();
+                        crate::stage(&dir, &[&name]).unwrap();
+                        crate::commit(&dir, &name).unwrap();
+
+                        assert!(crate::status(&dir).unwrap().is_empty());
+                        assert_eq!(
+                            PathBuf::from(&name),
+                            crate::index_entries(&dir).unwrap()[index].path
+                        );
+                    }
+                    assert_eq!(1, super::CACHE.with(|cache| cache.borrow().len()));
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
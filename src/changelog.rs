@@ -0,0 +1,408 @@
+//! Markdown changelogs generated from conventional commit messages between
+//! version tags.
+
+use git2::Sort;
+
+use crate::{
+    log::LogEntry,
+    Error,
+    GitHelper,
+};
+
+/// Options for changelog.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangelogOptions {
+    /// Link the short hash of every entry to this url with `{hash}` replaced
+    /// by the full hash of the commit, e.g.
+    /// `https://github.com/owner/repo/commit/{hash}`. If None the hashes are
+    /// not linked.
+    pub commit_url: Option<String>,
+}
+
+/// Commit message following the conventional commits specification, e.g.
+/// `feat(parser)!: support arrays`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    /// Type of the change, e.g. `feat` or `fix`.
+    pub kind: String,
+
+    /// Scope of the change, e.g. `parser`.
+    pub scope: Option<String>,
+
+    /// True if the type is followed by `!` or the message has a
+    /// `BREAKING CHANGE` footer.
+    pub breaking: bool,
+
+    /// Description after the colon.
+    pub description: String,
+}
+
+impl ConventionalCommit {
+    /// Parse a commit message. Returns None if the summary line does not
+    /// have the form `type(scope)!: description` with optional scope and
+    /// `!`.
+    pub fn parse(message: &str) -> Option<Self> {
+        let summary = message.lines().next()?;
+        let (prefix, description) = summary.split_once(": ")?;
+
+        let (prefix, breaking) = match prefix.strip_suffix('!') {
+            Some(prefix) => (prefix, true),
+            None => (prefix, false),
+        };
+        let (kind, scope) = match prefix.split_once('(') {
+            Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?)),
+            None => (prefix, None),
+        };
+
+        let valid = |word: &str| {
+            !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '/')
+        };
+        if !valid(kind) || !scope.is_none_or(valid) || description.trim().is_empty() {
+            return None;
+        }
+
+        let breaking_footer = message.lines().skip(1).any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        });
+
+        Some(Self {
+            kind: kind.to_lowercase(),
+            scope: scope.map(str::to_string),
+            breaking: breaking || breaking_footer,
+            description: description.trim().to_string(),
+        })
+    }
+}
+
+/// Sections of a changelog in the order they are rendered.
+const SECTIONS: [&str; 4] = ["Breaking Changes", "Features", "Fixes", "Other"];
+
+/// Version of a tag like `v1.2.3` or `1.2.3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Version(u64, u64, u64);
+
+impl Version {
+    /// Parse the name of a tag. Pre-release and build suffixes are not
+    /// supported, such tags are not treated as versions.
+    fn parse(tag: &str) -> Option<Self> {
+        let tag = tag.strip_prefix('v').unwrap_or(tag);
+        let mut parts = tag.split('.').map(|part| {
+            if part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            part.parse().ok()
+        });
+
+        let version = Version(parts.next()??, parts.next()??, parts.next()??);
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(version)
+    }
+}
+
+impl GitHelper {
+    /// Return the tag with the highest semantic version, e.g. `v1.2.3`,
+    /// whose commit is reachable from the revision. Tags with pre-release or
+    /// build suffixes are ignored. Returns None if there is no such tag.
+    pub fn latest_version_tag(&self, revision: &str) -> Result<Option<String>, Error> {
+        self.latest_version_tag_where(revision, |_| true)
+    }
+
+    /// Generate a Markdown changelog of the commits reachable from `to_tag`
+    /// but not from `from_tag`. Without `from_tag` the changelog starts at
+    /// the version tag before `to_tag` as found by latest_version_tag,
+    /// ignoring tags on the commit of `to_tag`, or at the first commit if
+    /// there is none.
+    ///
+    /// Commits are grouped into Breaking Changes, Features (`feat`), Fixes
+    /// (`fix`) and Other, including messages that are not conventional
+    /// commits. Empty groups are left out, merge commits are skipped. Within
+    /// a group commits are listed newest first with children before their
+    /// parents, so the output is the same for the same history.
+    pub fn changelog(
+        &self,
+        from_tag: Option<&str>,
+        to_tag: &str,
+        options: &ChangelogOptions,
+    ) -> Result<String, Error> {
+        let to = self.find_commit(to_tag)?.id();
+        let from = match from_tag {
+            Some(from_tag) => Some(from_tag.to_string()),
+            None => self.latest_version_tag_where(to_tag, |oid| oid != to)?,
+        };
+
+        let repository = self.repository();
+        let mut revwalk = repository.revwalk().map_err(Error::RepositoryRevwalk)?;
+        revwalk
+            .set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+            .and_then(|_| revwalk.push(to))
+            .map_err(Error::RepositoryRevwalk)?;
+        if let Some(from) = &from {
+            let from = self.find_commit(from)?.id();
+            revwalk.hide(from).map_err(Error::RepositoryRevwalk)?;
+        }
+
+        let mut sections = vec![Vec::new(); SECTIONS.len()];
+        for oid in revwalk {
+            let oid = oid.map_err(Error::RepositoryRevwalk)?;
+            let commit = repository
+                .find_commit(oid)
+                .map_err(Error::RepositoryFindCommit)?;
+            if commit.parent_count() > 1 {
+                continue;
+            }
+
+            let entry = LogEntry::from_commit(&commit, &commit.author());
+            let (section, line) = match ConventionalCommit::parse(&entry.message) {
+                Some(conventional) => {
+                    let section = match conventional.kind.as_str() {
+                        _ if conventional.breaking => 0,
+                        "feat" => 1,
+                        "fix" => 2,
+                        _ => 3,
+                    };
+                    let line = match &conventional.scope {
+                        Some(scope) => format!("**{}:** {}", scope, conventional.description),
+                        None => conventional.description,
+                    };
+
+                    (section, line)
+                }
+                None => (3, entry.summary),
+            };
+
+            let hash = oid.to_string();
+            let short = &hash[..7];
+            let reference = match &options.commit_url {
+                Some(url) => format!("[{}]({})", short, url.replace("{hash}", &hash)),
+                None => short.to_string(),
+            };
+            sections[section].push(format!("- {} ({})\n", line, reference));
+        }
+
+        let mut changelog = format!("## {}\n", to_tag);
+        for (title, entries) in SECTIONS.iter().zip(sections) {
+            if entries.is_empty() {
+                continue;
+            }
+
+            changelog.push_str(&format!("\n### {}\n\n", title));
+            changelog.extend(entries);
+        }
+
+        Ok(changelog)
+    }
+
+    /// Return the version tag with the highest version that is reachable
+    /// from the revision and whose commit matches the filter. Version tags
+    /// that do not point at a commit are skipped.
+    fn latest_version_tag_where<F: Fn(git2::Oid) -> bool>(
+        &self,
+        revision: &str,
+        filter: F,
+    ) -> Result<Option<String>, Error> {
+        let names = self
+            .repository()
+            .tag_names(None)
+            .map_err(Error::RepositoryTags)?;
+
+        let mut latest = None;
+        for name in names.iter_bytes() {
+            let name = String::from_utf8_lossy(name);
+            let version = match Version::parse(&name) {
+                Some(version) => version,
+                None => continue,
+            };
+            if latest
+                .as_ref()
+                .is_some_and(|(latest, _)| *latest >= version)
+            {
+                continue;
+            }
+
+            let reference = format!("refs/tags/{}", name);
+            // tags of trees and blobs can not be part of the history
+            let oid = match self.find_commit(&reference) {
+                Ok(commit) => commit.id(),
+                Err(_) => continue,
+            };
+            if filter(oid) && self.is_ancestor(&reference, revision)? {
+                latest = Some((version, name.into_owned()));
+            }
+        }
+
+        Ok(latest.map(|(_, name)| name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::{
+        tempdir,
+        TempDir,
+    };
+
+    use super::{
+        ChangelogOptions,
+        ConventionalCommit,
+        Version,
+    };
+    use crate::GitHelper;
+
+    /// Create a repository with a history of conventional and other commits
+    /// and the tags `v0.9.0`, `v1.0.0`, `v1.1.0` and `not-a-version`.
+    /// Returns the directory and the short hashes of the commits after
+    /// `v1.0.0` oldest first.
+    fn history() -> (TempDir, Vec<String>) {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        let repository = helper.repository();
+
+        let tag = |name: &str| {
+            let head = repository.head().unwrap().peel_to_commit().unwrap();
+            repository
+                .tag_lightweight(name, head.as_object(), false)
+                .unwrap();
+        };
+
+        helper.commit_empty("Initial commit").unwrap();
+        tag("v0.9.0");
+        helper.commit("feat: first feature").unwrap();
+        tag("v1.0.0");
+
+        let mut hashes = Vec::new();
+        for message in &[
+            "fix(parser): handle empty input",
+            "feat: add export",
+            "Update readme",
+            "refactor!: drop the old api",
+            "chore: bump dependencies",
+            "feat(cli): add flag\n\nBREAKING CHANGE: the default changed",
+        ] {
+            helper.commit(message).unwrap();
+            let head = repository.head().unwrap().target().unwrap();
+            hashes.push(head.to_string()[..7].to_string());
+        }
+        tag("v1.1.0");
+        tag("not-a-version");
+
+        (dir, hashes)
+    }
+
+    #[test]
+    fn parse_conventional_commit() {
+        assert_eq!(
+            Some(ConventionalCommit {
+                kind: "feat".to_string(),
+                scope: Some("parser".to_string()),
+                breaking: true,
+                description: "support arrays".to_string(),
+            }),
+            ConventionalCommit::parse("feat(parser)!: support arrays")
+        );
+        assert_eq!(
+            Some(true),
+            ConventionalCommit::parse("fix: x\n\nBREAKING-CHANGE: y").map(|commit| commit.breaking)
+        );
+
+        for message in &[
+            "Update readme",
+            "feat:missing space",
+            "feat(: x",
+            "a b: c",
+            "fix: ",
+        ] {
+            assert_eq!(None, ConventionalCommit::parse(message), "{}", message);
+        }
+    }
+
+    #[test]
+    fn parse_version() {
+        assert_eq!(Some(Version(1, 2, 3)), Version::parse("v1.2.3"));
+        assert_eq!(Some(Version(10, 0, 0)), Version::parse("10.0.0"));
+        assert!(Version(1, 10, 0) > Version(1, 9, 0));
+
+        for tag in &["v1.2", "v1.2.3-rc1", "1.2.3.4", "release", "v1..2"] {
+            assert_eq!(None, Version::parse(tag), "{}", tag);
+        }
+    }
+
+    #[test]
+    fn latest_version_tag() {
+        let (dir, _) = history();
+
+        assert_eq!(
+            Some("v1.1.0".to_string()),
+            crate::latest_version_tag(&dir, "HEAD").unwrap()
+        );
+        assert_eq!(
+            Some("v0.9.0".to_string()),
+            crate::latest_version_tag(&dir, "v1.0.0~1").unwrap()
+        );
+    }
+
+    #[test]
+    fn latest_version_tag_skips_non_commits() {
+        let (dir, _) = history();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+
+        let blob = repository.blob(b"not a commit").unwrap();
+        let blob = repository.find_object(blob, None).unwrap();
+        repository.tag_lightweight("v2.0.0", &blob, false).unwrap();
+        let tree = repository.head().unwrap().peel_to_tree().unwrap();
+        repository
+            .tag_lightweight("v3.0.0", tree.as_object(), false)
+            .unwrap();
+
+        assert_eq!(
+            Some("v1.1.0".to_string()),
+            crate::latest_version_tag(&dir, "HEAD").unwrap()
+        );
+    }
+
+    #[test]
+    fn changelog() {
+        let (dir, hashes) = history();
+
+        let expected = format!(
+            "## v1.1.0\n\n### Breaking Changes\n\n- **cli:** add flag ({})\n- drop the old api \
+             ({})\n\n### Features\n\n- add export ({})\n\n### Fixes\n\n- **parser:** handle empty \
+             input ({})\n\n### Other\n\n- bump dependencies ({})\n- Update readme ({})\n",
+            hashes[5], hashes[3], hashes[1], hashes[0], hashes[4], hashes[2]
+        );
+        assert_eq!(
+            expected,
+            crate::changelog(&dir, None, "v1.1.0", &ChangelogOptions::default()).unwrap()
+        );
+        assert_eq!(
+            expected,
+            crate::changelog(&dir, Some("v1.0.0"), "v1.1.0", &ChangelogOptions::default()).unwrap()
+        );
+
+        let options = ChangelogOptions {
+            commit_url: Some("https://example.com/commit/{hash}".to_string()),
+        };
+        let changelog = crate::changelog(&dir, Some("v0.9.0"), "v1.0.0", &options).unwrap();
+        let head = GitHelper::open(&dir)
+            .unwrap()
+            .repository()
+            .revparse_single("v1.0.0")
+            .unwrap()
+            .id()
+            .to_string();
+        assert_eq!(
+            format!(
+                "## v1.0.0\n\n### Features\n\n- first feature ([{}](https://example.com/commit/{}))\n",
+                &head[..7],
+                head
+            ),
+            changelog
+        );
+    }
+}
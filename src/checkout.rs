@@ -0,0 +1,730 @@
+//! Checkout of branches, commits and paths with control over how libgit2
+//! treats local modifications and conflicts.
+
+use std::{
+    cell::RefCell,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use git2::{
+    build::CheckoutBuilder,
+    Branch,
+    CheckoutNotificationType,
+    DiffOptions,
+    ObjectType,
+    Status,
+    Tree,
+};
+
+use crate::{
+    repo::{
+        fs_path,
+        path_from_bytes,
+    },
+    Error,
+    GitHelper,
+};
+
+/// How conflicting files are written to the working directory during a
+/// checkout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictStyle {
+    /// Write the file with conflict markers like git merge does.
+    Markers,
+
+    /// Write the file with conflict markers including the common ancestor
+    /// like git merge does with `merge.conflictStyle=diff3`.
+    Diff3,
+
+    /// Take our side of the conflict.
+    Ours,
+
+    /// Take their side of the conflict.
+    Theirs,
+}
+
+/// Options controlling a checkout.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckoutOptions {
+    /// Overwrite modified files in the working directory. Without this the
+    /// checkout fails if it would overwrite local modifications.
+    pub force: bool,
+
+    /// Remove untracked files from the working directory.
+    pub remove_untracked: bool,
+
+    /// Do not touch the working directory, HEAD or the index. Only the paths
+    /// whose content in the working directory differs from the checkout
+    /// target are reported. A dry run does not detect whether the checkout
+    /// would fail because of local modifications.
+    pub dry_run: bool,
+
+    /// How to write conflicting files. None fails the checkout on conflicts.
+    pub conflicts: Option<ConflictStyle>,
+
+    /// Only check out the given paths. All paths are checked out if empty.
+    pub paths: Vec<PathBuf>,
+}
+
+impl CheckoutOptions {
+    /// Create a checkout builder for the options. Every path that is or would
+    /// be changed by the checkout is pushed to `changed`.
+    pub(crate) fn builder<'cb>(&self, changed: &'cb RefCell<Vec<PathBuf>>) -> CheckoutBuilder<'cb> {
+        let mut builder = CheckoutBuilder::new();
+
+        if self.force {
+            builder.force();
+        } else {
+            builder.safe();
+        }
+
+        builder.remove_untracked(self.remove_untracked);
+
+        match self.conflicts {
+            Some(ConflictStyle::Markers) => {
+                builder.allow_conflicts(true).conflict_style_merge(true);
+            }
+            Some(ConflictStyle::Diff3) => {
+                builder.allow_conflicts(true).conflict_style_diff3(true);
+            }
+            Some(ConflictStyle::Ours) => {
+                builder.allow_conflicts(true).use_ours(true);
+            }
+            Some(ConflictStyle::Theirs) => {
+                builder.allow_conflicts(true).use_theirs(true);
+            }
+            None => {}
+        }
+
+        for path in &self.paths {
+            builder.path(path.as_path());
+        }
+
+        let mut notify_on = CheckoutNotificationType::UPDATED;
+        if self.remove_untracked {
+            notify_on |= CheckoutNotificationType::UNTRACKED;
+        }
+
+        builder.notify_on(notify_on);
+        builder.notify(move |_, path, _, _, _| {
+            if let Some(path) = path {
+                changed.borrow_mut().push(path.to_path_buf());
+            }
+            true
+        });
+
+        builder
+    }
+}
+
+impl GitHelper {
+    /// Check out the local branch with the given name and point HEAD to it.
+    /// Returns the paths that were changed in the working directory or that
+    /// would be changed when `dry_run` is set. Fails with
+    /// Error::OperationInProgress during a merge, rebase or similar unless
+    /// `force` is set.
+    pub fn checkout_branch(
+        &self,
+        name: &str,
+        options: &CheckoutOptions,
+    ) -> Result<Vec<PathBuf>, Error> {
+        if !options.force && !options.dry_run {
+            self.ensure_clean_state()?;
+        }
+
+        let repository = self.repository();
+        let branch = repository
+            .find_branch(name, git2::BranchType::Local)
+            .map_err(|_| Error::BranchNotFound(name.to_string()))?;
+
+        let reference = branch.get();
+        let tree = reference
+            .peel(ObjectType::Tree)
+            .map_err(Error::RepositoryFindTree)?;
+
+        if options.dry_run {
+            return self.dry_run(tree.as_tree(), options);
+        }
+
+        let changed = RefCell::new(Vec::new());
+        repository
+            .checkout_tree(&tree, Some(&mut options.builder(&changed)))
+            .map_err(Error::Checkout)?;
+
+        let refname = String::from_utf8_lossy(reference.name_bytes()).into_owned();
+        repository.set_head(&refname).map_err(Error::HeadUpdate)?;
+
+        Ok(finish(changed))
+    }
+
+    /// Create a local branch at the remote-tracking branch of the given
+    /// remote, set the remote-tracking branch as its upstream and check it
+    /// out, like `git checkout --track <remote>/<branch>`. The remote has to
+    /// be fetched before. Fails with Error::BranchNotFound if the
+    /// remote-tracking branch does not exist. Returns the paths that were
+    /// changed in the working directory.
+    pub fn checkout_remote_branch(
+        &self,
+        remote: &str,
+        branch: &str,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let repository = self.repository();
+        let upstream = format!("{}/{}", remote, branch);
+        let commit = repository
+            .find_branch(&upstream, git2::BranchType::Remote)
+            .and_then(|remote_branch| remote_branch.get().peel_to_commit())
+            .map_err(|_| Error::BranchNotFound(upstream.clone()))?;
+
+        repository
+            .branch(branch, &commit, false)
+            .and_then(|mut local| local.set_upstream(Some(&upstream)))
+            .map_err(Error::BranchCreate)?;
+
+        self.checkout_branch(branch, &CheckoutOptions::default())
+    }
+
+    /// Check out the commit the given revision resolves to and detach HEAD at
+    /// it. Returns the paths that were changed in the working directory or
+    /// that would be changed when `dry_run` is set. Fails with
+    /// Error::OperationInProgress during a merge, rebase or similar unless
+    /// `force` is set.
+    pub fn checkout_commit(
+        &self,
+        revspec: &str,
+        options: &CheckoutOptions,
+    ) -> Result<Vec<PathBuf>, Error> {
+        if !options.force && !options.dry_run {
+            self.ensure_clean_state()?;
+        }
+
+        let repository = self.repository();
+        let commit = repository
+            .revparse_single(revspec)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| Error::RevisionNotFound(revspec.to_string()))?;
+
+        if options.dry_run {
+            let tree = commit.tree().map_err(Error::CommitTree)?;
+            return self.dry_run(Some(&tree), options);
+        }
+
+        let changed = RefCell::new(Vec::new());
+        repository
+            .checkout_tree(commit.as_object(), Some(&mut options.builder(&changed)))
+            .map_err(Error::Checkout)?;
+
+        repository
+            .set_head_detached(commit.id())
+            .map_err(Error::HeadUpdate)?;
+
+        Ok(finish(changed))
+    }
+
+    /// Point HEAD at a new branch without commits like `git checkout
+    /// --orphan`, so the next commit is a root commit on that branch. The
+    /// index is cleared. With `clear_worktree` the files tracked by the
+    /// previous HEAD are removed from the working directory as well,
+    /// otherwise they are left as untracked files.
+    ///
+    /// Nothing is changed if the checkout fails: with
+    /// Error::InvalidBranchName or Error::BranchExists for the name, with
+    /// Error::UncommittedChanges if the working directory or the index
+    /// differ from HEAD, with Error::SnapshotUnsupported for snapshots and
+    /// with Error::OperationInProgress during a merge, rebase or similar.
+    pub fn checkout_orphan(&self, name: &str, clear_worktree: bool) -> Result<(), Error> {
+        if self.is_snapshot() {
+            return Err(Error::SnapshotUnsupported);
+        }
+        if !Branch::name_is_valid(name).unwrap_or(false) {
+            return Err(Error::InvalidBranchName(name.to_string()));
+        }
+        self.ensure_clean_state()?;
+
+        let repository = self.repository();
+        if repository
+            .find_branch(name, git2::BranchType::Local)
+            .is_ok()
+        {
+            return Err(Error::BranchExists(name.to_string()));
+        }
+
+        if !repository.is_bare() {
+            let changed = self
+                .status()?
+                .into_iter()
+                .filter(|entry| entry.status != Status::WT_NEW)
+                .map(|entry| entry.path)
+                .collect::<Vec<_>>();
+            if !changed.is_empty() {
+                return Err(Error::UncommittedChanges(changed));
+            }
+        }
+
+        repository
+            .set_head(&format!("refs/heads/{}", name))
+            .map_err(Error::HeadUpdate)?;
+
+        let mut index = repository.index().map_err(Error::IndexOpen)?;
+        if clear_worktree {
+            let workdir = repository.workdir().ok_or(Error::NoWorkdir)?;
+            for entry in index.iter() {
+                let file = workdir.join(path_from_bytes(&entry.path));
+                if fs_path(&file).symlink_metadata().is_ok() {
+                    fs::remove_file(fs_path(&file)).map_err(Error::WorkdirWrite)?;
+                }
+
+                // Remove directories that became empty like git rm does.
+                for dir in file.ancestors().skip(1) {
+                    if dir == workdir || fs::remove_dir(fs_path(dir)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        index.clear().map_err(Error::IndexClear)?;
+        self.write_index(&mut index)
+    }
+
+    /// Restore files in the working directory from the index like `git
+    /// restore`. Local modifications of the restored paths are always
+    /// overwritten. Returns the paths that were restored or that would be
+    /// restored when `dry_run` is set.
+    pub fn restore(&self, options: &CheckoutOptions) -> Result<Vec<PathBuf>, Error> {
+        if options.dry_run {
+            return self.dry_run(None, options);
+        }
+
+        let options = CheckoutOptions {
+            force: true,
+            ..options.clone()
+        };
+
+        let changed = RefCell::new(Vec::new());
+        self.repository()
+            .checkout_index(None, Some(&mut options.builder(&changed)))
+            .map_err(Error::Checkout)?;
+
+        Ok(finish(changed))
+    }
+
+    /// Write every file of the commit the revspec resolves to into the
+    /// destination directory like an extracted `git archive`. Missing
+    /// directories are created and existing files are overwritten. The
+    /// working directory, the index and HEAD are not touched, so this works
+    /// for bare repositories as well.
+    pub fn export_tree<Q: AsRef<Path>>(&self, revspec: &str, dest: Q) -> Result<(), Error> {
+        let commit = self.find_commit(revspec)?;
+
+        let mut builder = CheckoutBuilder::new();
+        builder
+            .force()
+            .recreate_missing(true)
+            .update_index(false)
+            .target_dir(dest.as_ref());
+
+        self.repository()
+            .checkout_tree(commit.as_object(), Some(&mut builder))
+            .map_err(Error::Checkout)
+    }
+
+    /// Return the paths whose content in the working directory differs from
+    /// the given tree or from the index if no tree is given. libgit2 does not
+    /// report anything for dry runs, so the changes are computed with a diff.
+    fn dry_run(
+        &self,
+        tree: Option<&Tree<'_>>,
+        options: &CheckoutOptions,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let repository = self.repository();
+
+        let mut diff_options = DiffOptions::new();
+        diff_options
+            .include_untracked(options.remove_untracked)
+            .recurse_untracked_dirs(options.remove_untracked);
+        for path in &options.paths {
+            diff_options.pathspec(path.as_path());
+        }
+
+        let diff = match tree {
+            Some(tree) => {
+                repository.diff_tree_to_workdir_with_index(Some(tree), Some(&mut diff_options))
+            }
+            None => repository.diff_index_to_workdir(None, Some(&mut diff_options)),
+        }
+        .map_err(Error::Diff)?;
+
+        let changed = diff
+            .deltas()
+            .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+            .map(|path| path.to_path_buf())
+            .collect();
+
+        Ok(finish(RefCell::new(changed)))
+    }
+}
+
+fn finish(changed: RefCell<Vec<PathBuf>>) -> Vec<PathBuf> {
+    let mut changed = changed.into_inner();
+    changed.sort();
+    changed.dedup();
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::PathBuf,
+    };
+    use tempfile::{
+        tempdir,
+        TempDir,
+    };
+
+    use super::CheckoutOptions;
+    use crate::GitHelper;
+
+    /// Repository with the branches master and feature. The feature branch
+    /// changes first_file and adds second_file.
+    fn repository_with_branches() -> TempDir {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        fs::write(dir.path().join("unchanged"), "unchanged").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("master").unwrap();
+
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        repository.branch("feature", &head, false).unwrap();
+        repository.set_head("refs/heads/feature").unwrap();
+
+        fs::write(dir.path().join("first_file"), "feature data").unwrap();
+        fs::write(dir.path().join("second_file"), "second data").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("feature").unwrap();
+
+        helper
+            .checkout_branch("master", &CheckoutOptions::default())
+            .unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn checkout_branch_dry_run() {
+        let dir = repository_with_branches();
+        let expected = vec![PathBuf::from("first_file"), PathBuf::from("second_file")];
+
+        let options = CheckoutOptions {
+            dry_run: true,
+            ..CheckoutOptions::default()
+        };
+        assert_eq!(
+            expected,
+            crate::checkout_branch(&dir, "feature", &options).unwrap()
+        );
+
+        assert_eq!(
+            "first data",
+            fs::read_to_string(dir.path().join("first_file")).unwrap()
+        );
+        assert!(!dir.path().join("second_file").exists());
+
+        let helper = GitHelper::open(&dir).unwrap();
+        assert_eq!(
+            Ok("refs/heads/master"),
+            helper.repository().head().unwrap().name()
+        );
+
+        assert_eq!(
+            expected,
+            crate::checkout_branch(&dir, "feature", &CheckoutOptions::default()).unwrap()
+        );
+        assert_eq!(
+            "feature data",
+            fs::read_to_string(dir.path().join("first_file")).unwrap()
+        );
+        assert_eq!(
+            Ok("refs/heads/feature"),
+            helper.repository().head().unwrap().name()
+        );
+    }
+
+    #[test]
+    fn checkout_commit_force() {
+        let dir = repository_with_branches();
+        fs::write(dir.path().join("first_file"), "local modification").unwrap();
+
+        assert!(crate::checkout_commit(&dir, "feature", &CheckoutOptions::default()).is_err());
+        assert_eq!(
+            "local modification",
+            fs::read_to_string(dir.path().join("first_file")).unwrap()
+        );
+
+        let options = CheckoutOptions {
+            force: true,
+            ..CheckoutOptions::default()
+        };
+        crate::checkout_commit(&dir, "feature", &options).unwrap();
+        assert_eq!(
+            "feature data",
+            fs::read_to_string(dir.path().join("first_file")).unwrap()
+        );
+
+        let helper = GitHelper::open(&dir).unwrap();
+        assert!(helper.repository().head_detached().unwrap());
+    }
+
+    #[test]
+    fn restore_paths() {
+        let dir = repository_with_branches();
+        fs::write(dir.path().join("first_file"), "local modification").unwrap();
+        fs::write(dir.path().join("unchanged"), "local modification").unwrap();
+
+        let options = CheckoutOptions {
+            paths: vec![PathBuf::from("first_file")],
+            ..CheckoutOptions::default()
+        };
+        assert_eq!(
+            vec![PathBuf::from("first_file")],
+            crate::restore(&dir, &options).unwrap()
+        );
+
+        assert_eq!(
+            "first data",
+            fs::read_to_string(dir.path().join("first_file")).unwrap()
+        );
+        assert_eq!(
+            "local modification",
+            fs::read_to_string(dir.path().join("unchanged")).unwrap()
+        );
+    }
+
+    #[test]
+    fn checkout_remote_branch() {
+        let upstream = repository_with_branches();
+
+        let local = tempdir().unwrap();
+        crate::init(&local).unwrap();
+        GitHelper::open(&local)
+            .unwrap()
+            .repository()
+            .remote("origin", upstream.path().to_str().unwrap())
+            .unwrap();
+        crate::fetch(&local, &crate::FetchOptions::default()).unwrap();
+
+        let changed = crate::checkout_remote_branch(&local, "origin", "feature").unwrap();
+        assert!(!changed.is_empty());
+        assert_eq!(
+            "feature data",
+            fs::read_to_string(local.path().join("first_file")).unwrap()
+        );
+        assert!(local.path().join("second_file").exists());
+
+        let helper = GitHelper::open(&local).unwrap();
+        let head = helper.repository().head().unwrap();
+        assert_eq!(Some("refs/heads/feature"), head.name().ok());
+        assert_eq!(
+            Some("origin".to_string()),
+            helper.config_get("branch.feature.remote").unwrap()
+        );
+        assert_eq!(
+            Some("refs/heads/feature".to_string()),
+            helper.config_get("branch.feature.merge").unwrap()
+        );
+
+        match crate::checkout_remote_branch(&local, "origin", "missing") {
+            Err(crate::Error::BranchNotFound(name)) => assert_eq!("origin/missing", name),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn checkout_missing_branch() {
+        let dir = repository_with_branches();
+
+        match crate::checkout_branch(&dir, "missing", &CheckoutOptions::default()) {
+            Err(crate::Error::BranchNotFound(name)) => assert_eq!("missing", name),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn export_tree() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        fs::write(dir.path().join("README"), "readme").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "lib").unwrap();
+        fs::write(dir.path().join("src/nested/mod.rs"), "nested").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Added files").unwrap();
+        fs::write(dir.path().join("README"), "changed readme").unwrap();
+
+        let export = tempdir().unwrap();
+        let dest = export.path().join("export");
+        crate::export_tree(&dir, "HEAD", &dest).unwrap();
+
+        for (path, content) in &[
+            ("README", "readme"),
+            ("src/lib.rs", "lib"),
+            ("src/nested/mod.rs", "nested"),
+        ] {
+            assert_eq!(*content, fs::read_to_string(dest.join(path)).unwrap());
+        }
+        assert!(!dest.join(".git").exists());
+
+        // the repository itself is unchanged
+        assert_eq!(
+            "changed readme",
+            fs::read_to_string(dir.path().join("README")).unwrap()
+        );
+        let status = crate::status(&dir).unwrap();
+        assert_eq!(1, status.len());
+        assert_eq!(git2::Status::WT_MODIFIED, status[0].status);
+    }
+
+    #[test]
+    fn checkout_orphan() {
+        let dir = repository_with_branches();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let master = repository.revparse_single("master").unwrap().id();
+
+        helper.checkout_orphan("pages", true).unwrap();
+        assert!(!dir.path().join("first_file").exists());
+        assert!(!dir.path().join("unchanged").exists());
+        assert_eq!(
+            "refs/heads/pages",
+            repository
+                .find_reference("HEAD")
+                .unwrap()
+                .symbolic_target()
+                .unwrap()
+                .unwrap()
+        );
+        assert!(helper.status().unwrap().is_empty());
+
+        fs::write(dir.path().join("index.html"), "page").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Added page").unwrap();
+
+        let pages = repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(0, pages.parent_count());
+        let tree = pages.tree().unwrap();
+        assert_eq!(
+            vec!["index.html"],
+            tree.iter()
+                .map(|entry| entry.name().unwrap().to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(master, repository.revparse_single("master").unwrap().id());
+
+        helper
+            .checkout_branch("master", &CheckoutOptions::default())
+            .unwrap();
+        assert_eq!(
+            "first data",
+            fs::read_to_string(dir.path().join("first_file")).unwrap()
+        );
+        assert!(!dir.path().join("index.html").exists());
+        assert!(helper.status().unwrap().is_empty());
+    }
+
+    #[test]
+    fn checkout_orphan_keep_worktree() {
+        let dir = repository_with_branches();
+        let helper = GitHelper::open(&dir).unwrap();
+
+        helper.checkout_orphan("pages", false).unwrap();
+        assert_eq!(
+            "first data",
+            fs::read_to_string(dir.path().join("first_file")).unwrap()
+        );
+
+        let status = helper.status().unwrap();
+        assert_eq!(2, status.len());
+        assert!(status
+            .iter()
+            .all(|entry| entry.status == git2::Status::WT_NEW));
+    }
+
+    #[test]
+    fn checkout_orphan_existing_branch() {
+        let dir = repository_with_branches();
+
+        assert_eq!(
+            Err(crate::Error::BranchExists("feature".to_string())),
+            crate::checkout_orphan(&dir, "feature", true)
+        );
+    }
+
+    #[test]
+    fn checkout_orphan_dirty() {
+        let dir = repository_with_branches();
+        let helper = GitHelper::open(&dir).unwrap();
+        let head = helper.repository().head().unwrap().target().unwrap();
+
+        fs::write(dir.path().join("first_file"), "modified").unwrap();
+        fs::write(dir.path().join("unchanged"), "staged").unwrap();
+        helper.stage(&["unchanged"]).unwrap();
+        fs::write(dir.path().join("untracked"), "untracked").unwrap();
+
+        assert_eq!(
+            Err(crate::Error::UncommittedChanges(vec![
+                PathBuf::from("first_file"),
+                PathBuf::from("unchanged"),
+            ])),
+            helper.checkout_orphan("pages", true)
+        );
+
+        assert_eq!(
+            "modified",
+            fs::read_to_string(dir.path().join("first_file")).unwrap()
+        );
+        assert_eq!(
+            "staged",
+            fs::read_to_string(dir.path().join("unchanged")).unwrap()
+        );
+        assert_eq!(head, helper.repository().head().unwrap().target().unwrap());
+        assert_eq!(3, helper.status().unwrap().len());
+    }
+
+    #[test]
+    fn checkout_orphan_invalid_name() {
+        let dir = repository_with_branches();
+        let helper = GitHelper::open(&dir).unwrap();
+        let head = helper.repository().head().unwrap().target().unwrap();
+
+        assert_eq!(
+            Err(crate::Error::InvalidBranchName("a..b".to_string())),
+            helper.checkout_orphan("a..b", true)
+        );
+
+        assert_eq!(
+            "first data",
+            fs::read_to_string(dir.path().join("first_file")).unwrap()
+        );
+        assert_eq!(head, helper.repository().head().unwrap().target().unwrap());
+        assert!(helper.status().unwrap().is_empty());
+    }
+
+    #[test]
+    fn checkout_orphan_snapshot() {
+        let dir = repository_with_branches();
+        let snapshot = GitHelper::open_snapshot(&dir).unwrap();
+
+        assert_eq!(
+            Err(crate::Error::SnapshotUnsupported),
+            snapshot.checkout_orphan("pages", true)
+        );
+        assert!(dir.path().join("first_file").exists());
+    }
+}
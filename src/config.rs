@@ -0,0 +1,195 @@
+//! Typed access to the git configuration of a repository.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use git2::{
+    Config,
+    ConfigLevel,
+    Error as Git2Error,
+    ErrorCode,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// Configuration key of the directory hooks are run from.
+const HOOKS_PATH: &str = "core.hooksPath";
+
+impl GitHelper {
+    /// Read a string value from the configuration of the repository. Returns
+    /// None if the key is not set.
+    pub fn config_get(&self, key: &str) -> Result<Option<String>, Error> {
+        let config = self.config()?;
+
+        optional(config.get_string(key))
+    }
+
+    /// Set a string value in the local configuration of the repository.
+    pub fn config_set(&self, key: &str, value: &str) -> Result<(), Error> {
+        self.repository()
+            .config()
+            .and_then(|config| config.open_level(ConfigLevel::Local))
+            .and_then(|mut config| config.set_str(key, value))
+            .map_err(Error::Config)
+    }
+
+    /// Read a boolean value from the configuration of the repository. Returns
+    /// None if the key is not set and Error::Config if the value is not a
+    /// boolean.
+    pub fn config_get_bool(&self, key: &str) -> Result<Option<bool>, Error> {
+        let config = self.config()?;
+
+        optional(config.get_bool(key))
+    }
+
+    /// Read an integer value from the configuration of the repository.
+    /// Suffixes like `k`, `m` and `g` are applied like git does. Returns
+    /// None if the key is not set and Error::Config if the value is not an
+    /// integer.
+    pub fn config_get_int(&self, key: &str) -> Result<Option<i64>, Error> {
+        let config = self.config()?;
+
+        optional(config.get_i64(key))
+    }
+
+    /// Set `core.hooksPath` in the local configuration of the repository.
+    /// Relative paths are taken relative to the root of the working
+    /// directory like git does.
+    pub fn set_hooks_path(&self, path: &Path) -> Result<(), Error> {
+        self.config_set(HOOKS_PATH, &path.to_string_lossy())
+    }
+
+    /// Return the absolute path of the directory git runs hooks from. This is
+    /// `core.hooksPath` resolved against the root of the working directory,
+    /// or the git directory for bare repositories, and `hooks` in the git
+    /// directory if it is not set.
+    pub fn hooks_path(&self) -> Result<PathBuf, Error> {
+        let repository = self.repository();
+        let base = repository.workdir().unwrap_or_else(|| repository.path());
+
+        match self.config_get(HOOKS_PATH)? {
+            Some(path) => Ok(base.join(path)),
+            None => Ok(repository.path().join("hooks")),
+        }
+    }
+
+    /// Return a snapshot of the configuration of the repository including the
+    /// global and system configuration.
+    pub(crate) fn config(&self) -> Result<Config, Error> {
+        self.repository()
+            .config()
+            .and_then(|mut config| config.snapshot())
+            .map_err(Error::Config)
+    }
+}
+
+/// Map the error for a key that is not set to None.
+pub(crate) fn optional<T>(value: Result<T, Git2Error>) -> Result<Option<T>, Error> {
+    match value {
+        Ok(value) => Ok(Some(value)),
+        Err(ref err) if err.code() == ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(Error::Config(err)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::Repository;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    use crate::GitHelper;
+
+    #[test]
+    fn config_get_bool() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        assert_eq!(
+            Some(false),
+            crate::config_get_bool(&dir, "core.bare").unwrap()
+        );
+
+        let bare = tempdir().unwrap();
+        Repository::init_bare(&bare).unwrap();
+        assert_eq!(
+            Some(true),
+            crate::config_get_bool(&bare, "core.bare").unwrap()
+        );
+
+        assert_eq!(
+            None,
+            crate::config_get_bool(&dir, "githelper.missing").unwrap()
+        );
+    }
+
+    #[test]
+    fn config_set_and_get() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        assert_eq!(None, crate::config_get(&dir, "user.nickname").unwrap());
+        crate::config_set(&dir, "user.nickname", "tester").unwrap();
+        assert_eq!(
+            Some("tester".to_string()),
+            crate::config_get(&dir, "user.nickname").unwrap()
+        );
+    }
+
+    #[test]
+    fn config_get_int() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        assert_eq!(
+            Some(0),
+            helper
+                .config_get_int("core.repositoryformatversion")
+                .unwrap()
+        );
+        assert_eq!(None, helper.config_get_int("githelper.missing").unwrap());
+
+        let mut config = helper.repository().config().unwrap();
+        config.set_str("githelper.size", "2k").unwrap();
+        config.set_str("githelper.text", "not a number").unwrap();
+
+        assert_eq!(Some(2048), helper.config_get_int("githelper.size").unwrap());
+        match helper.config_get_int("githelper.text") {
+            Err(crate::Error::Config(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match helper.config_get_bool("githelper.text") {
+            Err(crate::Error::Config(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn hooks_path() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        let workdir = helper.workdir().unwrap().to_path_buf();
+
+        assert_eq!(
+            helper.repository().path().join("hooks"),
+            crate::hooks_path(&dir).unwrap()
+        );
+
+        crate::set_hooks_path(&dir, Path::new(".githooks")).unwrap();
+        assert_eq!(
+            Some(".githooks".to_string()),
+            crate::config_get(&dir, "core.hooksPath").unwrap()
+        );
+        let hooks_path = crate::hooks_path(&dir).unwrap();
+        assert!(hooks_path.is_absolute());
+        assert_eq!(workdir.join(".githooks"), hooks_path);
+
+        let absolute = tempdir().unwrap();
+        crate::set_hooks_path(&dir, absolute.path()).unwrap();
+        assert_eq!(absolute.path(), crate::hooks_path(&dir).unwrap());
+    }
+}
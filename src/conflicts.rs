@@ -0,0 +1,338 @@
+//! Inspection and resolution of conflicts left behind by merges and
+//! cherry-picks.
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use git2::{
+    IndexEntry,
+    Oid,
+    Repository,
+    RepositoryState,
+};
+
+use crate::{
+    repo::{
+        fs_path,
+        index_path,
+        path_from_bytes,
+    },
+    Error,
+    GitHelper,
+};
+
+/// One side of a conflict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictSide {
+    /// Oid of the blob of this side.
+    pub oid: Oid,
+    /// Content of the blob of this side.
+    pub content: Vec<u8>,
+}
+
+/// A path that is conflicted in the index. A side is None if the path does
+/// not exist on that side, e.g. because it was deleted there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Path of the conflicted file relative to the root of the repository.
+    pub path: PathBuf,
+    /// Version of the file in the merge base.
+    pub ancestor: Option<ConflictSide>,
+    /// Version of the file in HEAD.
+    pub ours: Option<ConflictSide>,
+    /// Version of the file in the commit that is merged or picked.
+    pub theirs: Option<ConflictSide>,
+}
+
+/// How a conflict is resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Take the version of HEAD.
+    Ours,
+    /// Take the version of the commit that is merged or picked.
+    Theirs,
+    /// Take the given content.
+    Content(Vec<u8>),
+}
+
+impl GitHelper {
+    /// Return the conflicts in the index sorted by path. A repository
+    /// without conflicts returns an empty list.
+    pub fn conflicts(&self) -> Result<Vec<Conflict>, Error> {
+        let index = self.repository().index().map_err(Error::IndexOpen)?;
+
+        let mut conflicts = index
+            .conflicts()
+            .map_err(Error::IndexConflicts)?
+            .map(|conflict| {
+                let conflict = conflict.map_err(Error::IndexConflicts)?;
+                let path = conflict
+                    .our
+                    .as_ref()
+                    .or(conflict.their.as_ref())
+                    .or(conflict.ancestor.as_ref())
+                    .map(|entry| path_from_bytes(&entry.path))
+                    .ok_or(Error::IndexConflictEmpty)?;
+
+                Ok(Conflict {
+                    path,
+                    ancestor: self.conflict_side(conflict.ancestor)?,
+                    ours: self.conflict_side(conflict.our)?,
+                    theirs: self.conflict_side(conflict.their)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(conflicts)
+    }
+
+    /// Return true if the index contains conflicts.
+    pub fn has_conflicts(&self) -> Result<bool, Error> {
+        let index = self.repository().index().map_err(Error::IndexOpen)?;
+
+        Ok(index.has_conflicts())
+    }
+
+    /// Resolve the conflict on the given path. The resolved content is
+    /// written to the working directory and staged and the conflict entries
+    /// are removed from the index. Resolving to a side that does not have the
+    /// path removes the file.
+    pub fn resolve_conflict<F: AsRef<Path>>(
+        &self,
+        path: F,
+        resolution: Resolution,
+    ) -> Result<(), Error> {
+        let workdir = self.repository().workdir().ok_or(Error::NoWorkdir)?;
+        let path = index_path(workdir, path.as_ref());
+        let path = path.as_path();
+
+        let conflict = self
+            .conflicts()?
+            .into_iter()
+            .find(|conflict| conflict.path == path)
+            .ok_or_else(|| Error::IndexEntryNotFound(path.to_path_buf()))?;
+
+        let content = match resolution {
+            Resolution::Ours => conflict.ours.map(|side| side.content),
+            Resolution::Theirs => conflict.theirs.map(|side| side.content),
+            Resolution::Content(content) => Some(content),
+        };
+
+        let mut index = self.repository().index().map_err(Error::IndexOpen)?;
+        index
+            .conflict_remove(path)
+            .map_err(Error::IndexConflictRemove)?;
+
+        match content {
+            Some(content) => {
+                fs::write(fs_path(&workdir.join(path)), content).map_err(Error::WorkdirWrite)?;
+                index.add_path(path).map_err(Error::IndexAddPath)?;
+            }
+            None => {
+                let file = workdir.join(path);
+                let file = fs_path(&file);
+                if file.exists() {
+                    fs::remove_file(file).map_err(Error::WorkdirWrite)?;
+                }
+                index.remove_path(path).map_err(Error::IndexRemovePath)?;
+            }
+        }
+
+        self.write_index(&mut index)?;
+
+        Ok(())
+    }
+
+    /// Return the commits that are merged into HEAD by an ongoing merge. A
+    /// repository that is not merging returns an empty list.
+    pub(crate) fn merge_heads(&self) -> Result<Vec<Oid>, Error> {
+        let repository = self.repository();
+        if self.repository_state() != RepositoryState::Merge {
+            return Ok(Vec::new());
+        }
+
+        // mergehead_foreach needs a mutable repository so use a separate
+        // handle for it.
+        let mut heads = Vec::new();
+        Repository::open(repository.path())
+            .and_then(|mut repository| {
+                repository.mergehead_foreach(|oid| {
+                    heads.push(*oid);
+                    true
+                })
+            })
+            .map_err(Error::MergeHeads)?;
+
+        Ok(heads)
+    }
+
+    fn conflict_side(&self, entry: Option<IndexEntry>) -> Result<Option<ConflictSide>, Error> {
+        entry
+            .map(|entry| {
+                let blob = self
+                    .repository()
+                    .find_blob(entry.id)
+                    .map_err(Error::RepositoryFindBlob)?;
+
+                Ok(ConflictSide {
+                    oid: entry.id,
+                    content: blob.content().to_vec(),
+                })
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::PathBuf,
+    };
+    use tempfile::tempdir;
+
+    use super::Resolution;
+    use crate::{
+        test_util::{
+            conflicted_cherry_pick,
+            conflicted_merge,
+        },
+        GitHelper,
+    };
+
+    #[test]
+    fn conflicts_clean_repository() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        assert!(crate::conflicts(&dir).unwrap().is_empty());
+        assert!(!crate::has_conflicts(&dir).unwrap());
+    }
+
+    #[test]
+    fn conflicts_after_merge() {
+        let dir = conflicted_merge();
+        assert!(crate::has_conflicts(&dir).unwrap());
+
+        let conflicts = crate::conflicts(&dir).unwrap();
+        assert_eq!(1, conflicts.len());
+
+        let conflict = &conflicts[0];
+        assert_eq!(PathBuf::from("conflict"), conflict.path);
+        assert_eq!(
+            b"base\n".to_vec(),
+            conflict.ancestor.as_ref().unwrap().content
+        );
+        assert_eq!(b"ours\n".to_vec(), conflict.ours.as_ref().unwrap().content);
+        assert_eq!(
+            b"theirs\n".to_vec(),
+            conflict.theirs.as_ref().unwrap().content
+        );
+        assert_eq!(
+            git2::Oid::hash_object(git2::ObjectType::Blob, b"theirs\n").unwrap(),
+            conflict.theirs.as_ref().unwrap().oid
+        );
+    }
+
+    #[test]
+    fn resolve_conflict() {
+        let dir = conflicted_merge();
+
+        crate::resolve_conflict(
+            &dir,
+            "conflict",
+            Resolution::Content(b"resolved\n".to_vec()),
+        )
+        .unwrap();
+
+        assert!(!crate::has_conflicts(&dir).unwrap());
+        assert_eq!(
+            "resolved\n",
+            fs::read_to_string(dir.path().join("conflict")).unwrap()
+        );
+
+        let status = crate::status(&dir).unwrap();
+        assert_eq!(1, status.len());
+        assert_eq!(PathBuf::from("conflict"), status[0].path);
+        assert_eq!(git2::Status::INDEX_MODIFIED, status[0].status);
+    }
+
+    #[test]
+    fn resolve_conflict_theirs_and_commit_merge() {
+        let dir = conflicted_merge();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let ours = repository.head().unwrap().peel_to_commit().unwrap().id();
+        let theirs = repository.revparse_single("feature").unwrap().id();
+
+        crate::resolve_conflict(&dir, "conflict", Resolution::Theirs).unwrap();
+        assert_eq!(
+            "theirs\n",
+            fs::read_to_string(dir.path().join("conflict")).unwrap()
+        );
+
+        crate::commit(&dir, "merge feature").unwrap();
+
+        let merge = repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(vec![ours, theirs], merge.parent_ids().collect::<Vec<_>>());
+        assert_eq!(git2::RepositoryState::Clean, repository.state());
+        assert!(crate::status(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn commit_concludes_merge_with_prepared_message() {
+        let dir = conflicted_merge();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let prepared = repository.message().unwrap();
+
+        fs::write(dir.path().join("conflict"), "resolved\n").unwrap();
+        crate::stage(&dir, &["conflict"]).unwrap();
+        crate::commit(&dir, "").unwrap();
+
+        let merge = repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(2, merge.parent_count());
+        assert_eq!(prepared, merge.message().unwrap());
+        assert_eq!(git2::RepositoryState::Clean, repository.state());
+        assert!(!repository.path().join("MERGE_HEAD").exists());
+        assert!(!repository.path().join("MERGE_MSG").exists());
+    }
+
+    #[test]
+    fn commit_concludes_cherry_pick() {
+        let dir = conflicted_cherry_pick();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap().id();
+        assert_eq!(git2::RepositoryState::CherryPick, repository.state());
+
+        crate::resolve_conflict(&dir, "conflict", Resolution::Theirs).unwrap();
+        crate::commit(&dir, "").unwrap();
+
+        let picked = repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(vec![head], picked.parent_ids().collect::<Vec<_>>());
+        assert!(picked.message().unwrap().starts_with("theirs"));
+        assert_eq!(git2::RepositoryState::Clean, repository.state());
+    }
+
+    #[test]
+    fn resolve_conflict_not_conflicted() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+
+        match crate::resolve_conflict(&dir, "first_file", Resolution::Ours) {
+            Err(crate::Error::IndexEntryNotFound(path)) => {
+                assert_eq!(PathBuf::from("first_file"), path)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
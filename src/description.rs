@@ -0,0 +1,79 @@
+//! Description of a repository as shown by gitweb and similar tools.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// Description git writes into new repositories. It is treated like a
+/// missing description.
+const DEFAULT_DESCRIPTION: &str =
+    "Unnamed repository; edit this file 'description' to name the repository.";
+
+impl GitHelper {
+    /// Return the description of the repository from the `description` file
+    /// in the git directory without the trailing newline. Returns None if
+    /// the file does not exist or still contains the default description
+    /// written by `git init`.
+    pub fn get_description(&self) -> Result<Option<String>, Error> {
+        let description = match fs::read_to_string(self.description_path()) {
+            Ok(description) => description,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::Description(err)),
+        };
+
+        let description = description.trim_end_matches('\n');
+        if description == DEFAULT_DESCRIPTION {
+            return Ok(None);
+        }
+
+        Ok(Some(description.to_string()))
+    }
+
+    /// Write the description of the repository into the `description` file
+    /// in the git directory followed by a newline.
+    pub fn set_description(&self, text: &str) -> Result<(), Error> {
+        fs::write(self.description_path(), format!("{}\n", text)).map_err(Error::Description)
+    }
+
+    fn description_path(&self) -> PathBuf {
+        self.repository().path().join("description")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::Repository;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn description() {
+        let dir = tempdir().unwrap();
+        Repository::init_bare(&dir).unwrap();
+        assert_eq!(None, crate::get_description(&dir).unwrap());
+
+        fs::write(
+            dir.path().join("description"),
+            "Unnamed repository; edit this file 'description' to name the repository.\n",
+        )
+        .unwrap();
+        assert_eq!(None, crate::get_description(&dir).unwrap());
+
+        crate::set_description(&dir, "Backup of the website").unwrap();
+        assert_eq!(
+            "Backup of the website\n",
+            fs::read_to_string(dir.path().join("description")).unwrap()
+        );
+        assert_eq!(
+            Some("Backup of the website".to_string()),
+            crate::get_description(&dir).unwrap()
+        );
+    }
+}
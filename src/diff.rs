@@ -0,0 +1,318 @@
+//! Lists of the files that changed between commits, the index and the working
+//! directory with rename and copy detection.
+
+use std::path::PathBuf;
+
+use git2::{
+    Binding,
+    Delta,
+    Diff,
+    DiffFindOptions,
+    DiffOptions,
+};
+
+use crate::{
+    repo::path_from_bytes,
+    Error,
+    GitHelper,
+};
+
+/// Options controlling how changes are detected. The defaults match the
+/// defaults of git.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffConfig {
+    /// Report a deleted and an added file with similar content as a rename.
+    pub detect_renames: bool,
+
+    /// Report an added file with content similar to an existing file as a
+    /// copy. Unmodified files are considered as sources as well, like `git
+    /// diff --find-copies-harder`.
+    pub detect_copies: bool,
+
+    /// Similarity in percent from which files are treated as renamed or
+    /// copied.
+    pub rename_threshold: u16,
+
+    /// Number of unchanged lines shown around changes.
+    pub context_lines: u32,
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        Self {
+            detect_renames: true,
+            detect_copies: false,
+            rename_threshold: 50,
+            context_lines: 3,
+        }
+    }
+}
+
+impl DiffConfig {
+    /// Return the diff options for the config.
+    pub(crate) fn diff_options(&self) -> DiffOptions {
+        let mut options = DiffOptions::new();
+        options
+            .context_lines(self.context_lines)
+            .include_unmodified(self.detect_copies);
+
+        options
+    }
+
+    /// Run rename and copy detection on the diff as configured.
+    pub(crate) fn find_similar(&self, diff: &mut Diff<'_>) -> Result<(), Error> {
+        if !self.detect_renames && !self.detect_copies {
+            return Ok(());
+        }
+
+        let mut options = DiffFindOptions::new();
+        options
+            .renames(self.detect_renames)
+            .copies(self.detect_copies)
+            .copies_from_unmodified(self.detect_copies)
+            .rename_threshold(self.rename_threshold)
+            .copy_threshold(self.rename_threshold);
+
+        diff.find_similar(Some(&mut options)).map_err(Error::Diff)
+    }
+}
+
+/// File that changed in a diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffEntry {
+    /// Kind of the change.
+    pub status: Delta,
+
+    /// Path before the change. None for added files.
+    pub old_path: Option<PathBuf>,
+
+    /// Path after the change. None for deleted files.
+    pub new_path: Option<PathBuf>,
+
+    /// Similarity of the old and the new file in percent for renames and
+    /// copies.
+    pub similarity: Option<u16>,
+}
+
+impl GitHelper {
+    /// List the files that changed between the commits the revspecs resolve
+    /// to.
+    pub fn diff_commits(
+        &self,
+        old: &str,
+        new: &str,
+        config: &DiffConfig,
+    ) -> Result<Vec<DiffEntry>, Error> {
+        let mut diff = self.commits_diff(old, new, config)?;
+
+        diff_entries(&mut diff, config)
+    }
+
+    /// List the files that are staged, i.e. that changed between HEAD and
+    /// the index. Without a HEAD commit every file in the index is added.
+    pub fn diff_staged(&self, config: &DiffConfig) -> Result<Vec<DiffEntry>, Error> {
+        let head_tree = match self.head_commit() {
+            Some(commit) => Some(commit.tree().map_err(Error::CommitTree)?),
+            None => None,
+        };
+
+        let mut diff = self
+            .repository()
+            .diff_tree_to_index(head_tree.as_ref(), None, Some(&mut config.diff_options()))
+            .map_err(Error::Diff)?;
+
+        diff_entries(&mut diff, config)
+    }
+
+    /// Return the diff between the trees of the commits the revspecs resolve
+    /// to. Renames and copies are not detected yet.
+    pub(crate) fn commits_diff(
+        &self,
+        old: &str,
+        new: &str,
+        config: &DiffConfig,
+    ) -> Result<Diff<'_>, Error> {
+        let old_tree = self.find_commit(old)?.tree().map_err(Error::CommitTree)?;
+        let new_tree = self.find_commit(new)?.tree().map_err(Error::CommitTree)?;
+
+        self.repository()
+            .diff_tree_to_tree(
+                Some(&old_tree),
+                Some(&new_tree),
+                Some(&mut config.diff_options()),
+            )
+            .map_err(Error::Diff)
+    }
+}
+
+/// Detect renames and copies in the diff and return its changed files.
+pub(crate) fn diff_entries(
+    diff: &mut Diff<'_>,
+    config: &DiffConfig,
+) -> Result<Vec<DiffEntry>, Error> {
+    config.find_similar(diff)?;
+
+    let entries = diff
+        .deltas()
+        .filter(|delta| delta.status() != Delta::Unmodified)
+        .map(|delta| {
+            let path = |file: git2::DiffFile<'_>| file.path_bytes().map(path_from_bytes);
+
+            let (old_path, new_path) = match delta.status() {
+                Delta::Added | Delta::Untracked => (None, path(delta.new_file())),
+                Delta::Deleted => (path(delta.old_file()), None),
+                _ => (path(delta.old_file()), path(delta.new_file())),
+            };
+
+            // SAFETY: the delta points into the diff which outlives it. git2
+            // does not expose the similarity so it is read from the raw delta
+            let similarity = match delta.status() {
+                Delta::Renamed | Delta::Copied => Some(unsafe { (*delta.raw()).similarity }),
+                _ => None,
+            };
+
+            DiffEntry {
+                status: delta.status(),
+                old_path,
+                new_path,
+                similarity,
+            }
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use git2::Delta;
+    use std::{
+        fs,
+        path::PathBuf,
+    };
+    use tempfile::{
+        tempdir,
+        TempDir,
+    };
+
+    use super::{
+        DiffConfig,
+        DiffEntry,
+    };
+    use crate::GitHelper;
+
+    /// Content of ten distinct lines.
+    fn lines() -> String {
+        (0..10)
+            .map(|index| format!("line number {}\n", index))
+            .collect()
+    }
+
+    /// Create a repository with a committed file `old` and stage renaming it
+    /// to `new` with the given content.
+    fn staged_rename(content: &str) -> TempDir {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("old"), lines()).unwrap();
+        helper.stage(&["old"]).unwrap();
+        helper.commit("Added old").unwrap();
+
+        fs::remove_file(dir.path().join("old")).unwrap();
+        fs::write(dir.path().join("new"), content).unwrap();
+        let mut index = helper.repository().index().unwrap();
+        index.remove_path(std::path::Path::new("old")).unwrap();
+        index.write().unwrap();
+        helper.stage(&["new"]).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn diff_staged_exact_rename() {
+        let dir = staged_rename(&lines());
+
+        assert_eq!(
+            vec![DiffEntry {
+                status: Delta::Renamed,
+                old_path: Some(PathBuf::from("old")),
+                new_path: Some(PathBuf::from("new")),
+                similarity: Some(100),
+            }],
+            crate::diff_staged(&dir, &DiffConfig::default()).unwrap()
+        );
+
+        let config = DiffConfig {
+            detect_renames: false,
+            ..DiffConfig::default()
+        };
+        let entries = crate::diff_staged(&dir, &config).unwrap();
+        assert_eq!(
+            vec![Delta::Added, Delta::Deleted],
+            entries.iter().map(|entry| entry.status).collect::<Vec<_>>()
+        );
+        assert_eq!(None, entries[0].old_path);
+        assert_eq!(Some(PathBuf::from("new")), entries[0].new_path);
+        assert_eq!(Some(PathBuf::from("old")), entries[1].old_path);
+        assert_eq!(None, entries[1].new_path);
+    }
+
+    #[test]
+    fn diff_staged_rename_threshold() {
+        let edited = lines()
+            .replace("line number 2", "changed")
+            .replace("line number 7", "changed");
+        let dir = staged_rename(&edited);
+
+        let entries = crate::diff_staged(&dir, &DiffConfig::default()).unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(Delta::Renamed, entries[0].status);
+        let similarity = entries[0].similarity.unwrap();
+        assert!(similarity > 50 && similarity < 100, "{}", similarity);
+
+        let at_threshold = DiffConfig {
+            rename_threshold: similarity,
+            ..DiffConfig::default()
+        };
+        let entries = crate::diff_staged(&dir, &at_threshold).unwrap();
+        assert_eq!(Delta::Renamed, entries[0].status);
+
+        let above_threshold = DiffConfig {
+            rename_threshold: similarity + 1,
+            ..DiffConfig::default()
+        };
+        let entries = crate::diff_staged(&dir, &above_threshold).unwrap();
+        assert_eq!(
+            vec![Delta::Added, Delta::Deleted],
+            entries.iter().map(|entry| entry.status).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn diff_commits_copy() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("original"), lines()).unwrap();
+        helper.stage(&["original"]).unwrap();
+        helper.commit("Added original").unwrap();
+        fs::write(dir.path().join("copy"), lines()).unwrap();
+        helper.stage(&["copy"]).unwrap();
+        helper.commit("Added copy").unwrap();
+
+        let config = DiffConfig {
+            detect_copies: true,
+            ..DiffConfig::default()
+        };
+        assert_eq!(
+            vec![DiffEntry {
+                status: Delta::Copied,
+                old_path: Some(PathBuf::from("original")),
+                new_path: Some(PathBuf::from("copy")),
+                similarity: Some(100),
+            }],
+            crate::diff_commits(&dir, "HEAD~1", "HEAD", &config).unwrap()
+        );
+
+        let entries = crate::diff_commits(&dir, "HEAD~1", "HEAD", &DiffConfig::default()).unwrap();
+        assert_eq!(Delta::Added, entries[0].status);
+    }
+}
@@ -50,6 +50,323 @@ pub enum Error {
 
     /// Error when pushing to remote.
     RemotePush(Git2Error),
+
+    /// Error when the object database can not be opened.
+    OdbOpen(Git2Error),
+
+    /// Error while iterating over the objects in the object database.
+    OdbForeach(Git2Error),
+
+    /// Error when no index entry exists for the given path.
+    IndexEntryNotFound(std::path::PathBuf),
+
+    /// Error while adding an entry to the index.
+    IndexAdd(Git2Error),
+
+    /// Error while reading a directory in the git directory.
+    ReadDirectory(std::io::Error),
+
+    /// Error while listing the references of the repository.
+    RepositoryReferences(Git2Error),
+
+    /// Error while walking through the history of the repository.
+    RepositoryRevwalk(Git2Error),
+
+    /// Error while finding a commit in the repository.
+    RepositoryFindCommit(Git2Error),
+
+    /// Error while getting the tree of a commit.
+    CommitTree(Git2Error),
+
+    /// Error while walking through a tree.
+    TreeWalk(Git2Error),
+
+    /// Error while reading an object from the object database.
+    OdbRead(Git2Error),
+
+    /// Error when an operation needs a working directory but the repository
+    /// is bare.
+    NoWorkdir,
+
+    /// Error while writing the packed-refs file or removing loose references.
+    PackRefsWrite(std::io::Error),
+
+    /// Error while building a packfile from loose objects.
+    Repack(Git2Error),
+
+    /// Error while removing loose objects after they were packed.
+    RepackFilesystem(std::io::Error),
+
+    /// Error when the written packfile does not contain all loose objects.
+    /// The loose objects are kept in that case.
+    RepackIncomplete,
+
+    /// Error while creating the buffer of a commit that should be signed.
+    CommitCreateBuffer(Git2Error),
+
+    /// Error returned by the signing callback.
+    CommitSign(String),
+
+    /// Error when the commit buffer to be signed is not valid utf-8.
+    CommitBufferNotUtf8,
+
+    /// Error while moving HEAD to a new commit.
+    HeadUpdate(Git2Error),
+
+    /// Error while reading the conflicts from the index.
+    IndexConflicts(Git2Error),
+
+    /// Error when a conflict in the index has neither an ancestor, our nor
+    /// their entry.
+    IndexConflictEmpty,
+
+    /// Error while finding a tag in the repository.
+    RepositoryFindTag(Git2Error),
+
+    /// Error while removing the conflict entries of a path from the index.
+    IndexConflictRemove(Git2Error),
+
+    /// Error when a branch with the given name does not exist.
+    BranchNotFound(String),
+
+    /// Error when a revision can not be resolved.
+    RevisionNotFound(String),
+
+    /// Error while checking out a tree or the index.
+    Checkout(Git2Error),
+
+    /// Error while diffing the working directory.
+    Diff(Git2Error),
+
+    /// Error when an empty root commit is requested but HEAD already points
+    /// to a commit.
+    NotEmptyHistory,
+
+    /// Error while building a tree.
+    TreeBuilder(Git2Error),
+
+    /// Error when a remote with the given name does not exist.
+    RemoteNotFound(String),
+
+    /// Error while listing the references of a remote.
+    RemoteList(Git2Error),
+
+    /// Error when the remote rejected the update of the given references.
+    /// Contains the name of every rejected reference with the reason.
+    PushRejected(Vec<(String, String)>),
+
+    /// Error when HEAD is expected to point to a branch but is detached.
+    HeadNotBranch,
+
+    /// Error while reading the git configuration.
+    Config(Git2Error),
+
+    /// Error when none of the credential sources provided credentials the
+    /// remote with the given url accepted.
+    Authentication(String),
+
+    /// Error when fetching from remote.
+    RemoteFetch(Git2Error),
+
+    /// Error while applying network options.
+    Network(Git2Error),
+
+    /// Error while reading the attributes of a path.
+    Attributes(Git2Error),
+
+    /// Error while resolving symbolic links in a path.
+    ResolvePath(std::io::Error),
+
+    /// Error while applying a diff to the index.
+    IndexApply(Git2Error),
+
+    /// Error when a hunk index is out of range for the diff of a file.
+    HunkNotFound(usize),
+
+    /// Error when a remote has no url configured.
+    RemoteNoUrl(String),
+
+    /// Error while creating a branch.
+    BranchCreate(Git2Error),
+
+    /// Error while deleting a branch.
+    BranchDelete(Git2Error),
+
+    /// Error when no name or email is configured for new commits.
+    SignatureNotConfigured,
+
+    /// Error when a commit has no signature.
+    NotSigned(String),
+
+    /// Error when a date is not in a format git accepts.
+    InvalidDate(String),
+
+    /// Error when a blob could not be found in the repository.
+    RepositoryFindBlob(Git2Error),
+
+    /// Error when a file in the working directory could not be written.
+    WorkdirWrite(std::io::Error),
+
+    /// Error when a path could not be removed from the index.
+    IndexRemovePath(Git2Error),
+
+    /// Error when the heads of an ongoing merge could not be read.
+    MergeHeads(Git2Error),
+
+    /// Error when the state of an ongoing merge could not be cleaned up.
+    CleanupState(Git2Error),
+
+    /// Error when aborting while no merge, cherry-pick or rebase is in
+    /// progress.
+    NoOperationInProgress,
+
+    /// Error when the repository could not be reset.
+    Reset(Git2Error),
+
+    /// Error when a rebase could not be aborted.
+    RebaseAbort(Git2Error),
+
+    /// Error when the mailmap of the repository could not be read or applied.
+    Mailmap(Git2Error),
+
+    /// Error when an operation refuses to run because a merge, rebase or other
+    /// operation is in progress.
+    OperationInProgress(crate::RepoState),
+
+    /// Error when a blob could not be written to the repository.
+    RepositoryWriteBlob(Git2Error),
+
+    /// Error when no bisect is in progress.
+    NoBisectInProgress,
+
+    /// Error when the bisect state file could not be read or written.
+    BisectState(std::io::Error),
+
+    /// Error when a line of the bisect state file is invalid.
+    BisectStateInvalid(String),
+
+    /// Error when bisecting can not continue because every remaining
+    /// candidate was skipped.
+    BisectInconclusive(Vec<git2::Oid>),
+
+    /// Error when a revision is not an ancestor of HEAD.
+    NotAncestor(String),
+
+    /// Error when a patch could not be parsed.
+    PatchParse(Git2Error),
+
+    /// Error when a diff could not be applied to the repository.
+    Apply(Git2Error),
+
+    /// Error when hunks of a diff do not apply to the repository.
+    ApplyConflict(Vec<crate::ApplyFailure>),
+
+    /// Error when a packfile could not be written to the object database.
+    PackWrite(Git2Error),
+
+    /// Error when a string is not a valid object id.
+    InvalidOid(String),
+
+    /// Error when an object does not exist in the object database.
+    ObjectNotFound(String),
+
+    /// Error when the certificate of a remote was rejected by the certificate
+    /// check of the network options or by libgit2. Contains the reason.
+    CertificateRejected(String),
+
+    /// Error when a file was never committed.
+    FileNotFound(std::path::PathBuf),
+
+    /// Error when the stash could not be read or changed.
+    Stash(Git2Error),
+
+    /// Error when a gitignore template with the given name does not exist.
+    UnknownGitignoreTemplate(String),
+
+    /// Error when a repository could not be cloned.
+    Clone(Git2Error),
+
+    /// Error when a remote could not be added or changed.
+    RemoteConfig(Git2Error),
+
+    /// Error when the `origin` of an existing repository does not point to
+    /// the expected url.
+    RemoteMismatch {
+        /// Url `origin` should point to.
+        expected: String,
+
+        /// Url `origin` points to. None if there is no `origin`.
+        found: Option<String>,
+    },
+
+    /// Error when the description file could not be read or written.
+    Description(std::io::Error),
+
+    /// Error when a repository is owned by another user than the current
+    /// one and its path is not listed in `safe.directory`. libgit2 refuses
+    /// to open such repositories like git does.
+    UnsafeOwnership {
+        /// Path the repository was opened with.
+        path: std::path::PathBuf,
+
+        /// User id of the owner of the path. None if it can not be
+        /// determined, e.g. on platforms without user ids, or if the path
+        /// itself belongs to the current user.
+        owner_uid: Option<u32>,
+    },
+
+    /// Error when the remote does not advertise a HEAD, e.g. because it is
+    /// empty.
+    NoRemoteHead(String),
+
+    /// Error when a commit template contains a placeholder without a value.
+    UnknownPlaceholder(String),
+
+    /// Error when the tags of the repository could not be listed.
+    RepositoryTags(Git2Error),
+
+    /// Error when more commits should be squashed than there are before the
+    /// root commit.
+    SquashPastRoot(usize),
+
+    /// Error when the commits to squash contain a merge commit.
+    SquashMerge(String),
+
+    /// Error when a commit to squash is already on the upstream of the branch.
+    SquashPushed(String),
+
+    /// Error when a rebase could not be started, continued or finished.
+    Rebase(Git2Error),
+
+    /// Error when replaying a commit conflicted with the given paths. The
+    /// operation was aborted.
+    Conflicts(Vec<std::path::PathBuf>),
+
+    /// Error when a branch that should be created already exists.
+    BranchExists(String),
+
+    /// Error when the index could not be cleared.
+    IndexClear(Git2Error),
+
+    /// Error when a branch name is not a valid reference name.
+    InvalidBranchName(String),
+
+    /// Error when an operation would discard local changes of the listed paths
+    /// in the working directory or the index.
+    UncommittedChanges(Vec<std::path::PathBuf>),
+
+    /// Error when an operation is not supported on a repository opened with
+    /// GitHelper::open_snapshot.
+    SnapshotUnsupported,
+
+    /// Error when an operation did not finish in time.
+    Timeout {
+        /// Name of the operation that timed out.
+        operation: &'static str,
+
+        /// Time that passed until the operation was given up.
+        elapsed: std::time::Duration,
+    },
 }
 
 impl std::fmt::Display for Error {
@@ -57,23 +374,561 @@ impl std::fmt::Display for Error {
         use Error::*;
 
         match self {
+            Apply(err) => write!(f, "can not apply diff: {}", err),
+            ApplyConflict(failures) => write!(f, "diff does not apply: {:?}", failures),
+            Attributes(err) => write!(f, "can not read attributes: {}", err),
+            Authentication(url) => write!(f, "can not authenticate to remote {}", url),
+            BisectInconclusive(candidates) => {
+                write!(f, "only skipped commits left to test: {:?}", candidates)
+            }
+            BisectState(err) => write!(f, "can not access bisect state: {}", err),
+            BisectStateInvalid(line) => write!(f, "invalid line in bisect state: {}", line),
+            BranchCreate(err) => write!(f, "can not create branch: {}", err),
+            BranchDelete(err) => write!(f, "can not delete branch: {}", err),
+            BranchExists(name) => write!(f, "branch {} already exists", name),
+            BranchNotFound(name) => write!(f, "can not find branch {}", name),
+            CertificateRejected(reason) => write!(f, "remote certificate rejected: {}", reason),
+            Checkout(err) => write!(f, "can not checkout: {}", err),
+            CleanupState(err) => write!(f, "can not clean up repository state: {}", err),
+            Clone(err) => write!(f, "can not clone repository: {}", err),
+            CommitBufferNotUtf8 => write!(f, "commit buffer is not valid utf-8"),
+            CommitCreateBuffer(err) => write!(f, "can not create commit buffer: {}", err),
+            CommitSign(err) => write!(f, "can not sign commit: {}", err),
+            CommitTree(err) => write!(f, "can not get tree of commit: {}", err),
+            Config(err) => write!(f, "can not read git configuration: {}", err),
+            Conflicts(paths) => write!(f, "conflicts in {:?}", paths),
+            Description(err) => write!(f, "can not access description: {}", err),
+            Diff(err) => write!(f, "can not diff working directory: {}", err),
+            FileNotFound(path) => write!(f, "file {} was never committed", path.display()),
             FileStatus(err) => write!(f, "can not determine file status: {}", err),
+            HeadNotBranch => write!(f, "HEAD does not point to a branch"),
+            HeadUpdate(err) => write!(f, "can not update HEAD: {}", err),
+            HunkNotFound(index) => write!(f, "can not find hunk {}", index),
+            IndexAdd(err) => write!(f, "can not add entry to index: {}", err),
             IndexAddPath(err) => write!(f, "can not add path to index: {}", err),
+            IndexApply(err) => write!(f, "can not apply changes to index: {}", err),
+            IndexClear(err) => write!(f, "can not clear index: {}", err),
+            IndexConflictEmpty => write!(f, "conflict in index has no entries"),
+            IndexConflictRemove(err) => {
+                write!(f, "can not remove conflict from index: {}", err)
+            }
+            IndexConflicts(err) => write!(f, "can not read conflicts from index: {}", err),
+            IndexEntryNotFound(path) => {
+                write!(f, "can not find index entry for path {}", path.display())
+            }
             IndexOpen(err) => write!(f, "can not open index: {}", err),
+            IndexRemovePath(err) => write!(f, "can not remove path from index: {}", err),
             IndexWrite(err) => write!(f, "can not write index: {}", err),
             IndexWriteTree(err) => write!(f, "can not write index tree: {}", err),
+            InvalidBranchName(name) => write!(f, "invalid branch name {}", name),
+            InvalidDate(date) => write!(f, "invalid date {}", date),
+            InvalidOid(oid) => write!(f, "invalid object id: {}", oid),
+            Mailmap(err) => write!(f, "can not apply mailmap: {}", err),
+            MergeHeads(err) => write!(f, "can not read merge heads: {}", err),
+            Network(err) => write!(f, "can not apply network options: {}", err),
+            NoBisectInProgress => write!(f, "no bisect in progress"),
+            NoOperationInProgress => write!(f, "no merge, cherry-pick or rebase in progress"),
             NoOriginConfigured => write!(f, "no origin configured"),
+            NoRemoteHead(name) => write!(f, "remote {} has no default branch", name),
+            NotAncestor(revspec) => write!(f, "{} is not an ancestor of HEAD", revspec),
+            NotEmptyHistory => write!(f, "repository already has commits"),
+            NoWorkdir => write!(f, "repository has no working directory"),
+            NotSigned(revspec) => write!(f, "commit {} is not signed", revspec),
+            ObjectNotFound(oid) => write!(f, "object not found: {}", oid),
+            OdbForeach(err) => write!(f, "can not iterate over object database: {}", err),
+            OdbOpen(err) => write!(f, "can not open object database: {}", err),
+            OdbRead(err) => write!(f, "can not read object from object database: {}", err),
+            OperationInProgress(state) => write!(f, "operation in progress: {:?}", state),
+            PackRefsWrite(err) => write!(f, "can not write packed refs: {}", err),
+            PackWrite(err) => write!(f, "can not write packfile: {}", err),
+            PatchParse(err) => write!(f, "can not parse patch: {}", err),
+            PushRejected(refs) => {
+                let refs = refs
+                    .iter()
+                    .map(|(reference, reason)| format!("{} ({})", reference, reason))
+                    .collect::<Vec<_>>();
+                write!(f, "remote rejected references: {}", refs.join(", "))
+            }
+            ReadDirectory(err) => write!(f, "can not read directory: {}", err),
+            Rebase(err) => write!(f, "can not rebase: {}", err),
+            RebaseAbort(err) => write!(f, "can not abort rebase: {}", err),
+            RemoteConfig(err) => write!(f, "can not configure remote: {}", err),
+            RemoteMismatch { expected, found } => write!(
+                f,
+                "origin points to {} instead of {}",
+                found.as_deref().unwrap_or("nothing"),
+                expected
+            ),
+            RemoteNoUrl(name) => write!(f, "remote {} has no url", name),
+            Repack(err) => write!(f, "can not repack loose objects: {}", err),
+            RepackFilesystem(err) => write!(f, "can not remove packed loose objects: {}", err),
+            RepackIncomplete => write!(f, "packfile does not contain all loose objects"),
             RemoteConnect(err) => write!(f, "can not connet to remote: {}", err),
+            RemoteFetch(err) => write!(f, "can not fetch from remote: {}", err),
+            RemoteList(err) => write!(f, "can not list references of remote: {}", err),
+            RemoteNotFound(name) => write!(f, "can not find remote {}", name),
             RemotePush(err) => write!(f, "can not push to remote: {}", err),
             RepositoryCommit(err) => write!(f, "can not commit to repository: {}", err),
+            RepositoryFindBlob(err) => write!(f, "can not find blob in repository: {}", err),
+            RepositoryFindCommit(err) => write!(f, "can not find commit in repository: {}", err),
+            RepositoryFindTag(err) => write!(f, "can not find tag in repository: {}", err),
             RepositoryFindTree(err) => write!(f, "can not find tree in repository: {}", err),
             RepositoryHead(err) => write!(f, "can not find head of repository: {}", err),
             RepositoryInit(err) => write!(f, "can not init repository: {}", err),
             RepositoryOpen(err) => write!(f, "can not open repository: {}", err),
+            RepositoryReferences(err) => {
+                write!(f, "can not list references of repository: {}", err)
+            }
+            RepositoryRevwalk(err) => write!(f, "can not walk repository history: {}", err),
             RepositorySignature(err) => write!(f, "can not get signature from repository: {}", err),
+            RepositoryTags(err) => write!(f, "can not list tags of repository: {}", err),
+            RepositoryWriteBlob(err) => write!(f, "can not write blob to repository: {}", err),
+            Reset(err) => write!(f, "can not reset repository: {}", err),
+            ResolvePath(err) => write!(f, "can not resolve path: {}", err),
+            RevisionNotFound(revspec) => write!(f, "can not find revision {}", revspec),
+            SignatureNotConfigured => write!(f, "no name and email configured for commits"),
+            SnapshotUnsupported => write!(f, "operation is not supported on snapshots"),
+            SquashMerge(oid) => write!(f, "can not squash across merge commit {}", oid),
+            SquashPastRoot(count) => {
+                write!(f, "can not squash {} commits past the root commit", count)
+            }
+            SquashPushed(oid) => write!(
+                f,
+                "commit {} is already on the upstream, squash with force to rewrite it",
+                oid
+            ),
+            Stash(err) => write!(f, "can not access stash: {}", err),
             StripRepositoryPrefix(err) => {
                 write!(f, "can not strip repository path prefix: {}", err)
             }
+            Timeout { operation, elapsed } => {
+                write!(f, "{} timed out after {:?}", operation, elapsed)
+            }
+            TreeBuilder(err) => write!(f, "can not build tree: {}", err),
+            TreeWalk(err) => write!(f, "can not walk tree: {}", err),
+            UncommittedChanges(paths) => write!(f, "uncommitted changes in {:?}", paths),
+            UnknownGitignoreTemplate(name) => write!(f, "unknown gitignore template {}", name),
+            UnknownPlaceholder(name) => write!(f, "unknown placeholder {{{}}} in template", name),
+            UnsafeOwnership { path, owner_uid } => {
+                write!(f, "repository {} is owned by another user", path.display())?;
+                if let Some(uid) = owner_uid {
+                    write!(f, " (uid {})", uid)?;
+                }
+                write!(
+                    f,
+                    ", if it is trusted add it with add_safe_directory or `git config --global \
+                     --add safe.directory {}`",
+                    path.display()
+                )
+            }
+            WorkdirWrite(err) => write!(f, "can not write file in working directory: {}", err),
+        }
+    }
+}
+
+impl Error {
+    /// Format the error with its causes as a multi-line report: the message
+    /// of the error, the name of the variant, the paths the error is about
+    /// if any and every underlying error with the class and code of git2
+    /// errors and the kind of io errors.
+    pub fn chain(&self) -> String {
+        let debug = format!("{:?}", self);
+        let variant = debug
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default();
+
+        let mut report = format!("error: {}\n  variant: {}\n", self, variant);
+        let paths = match self {
+            Error::FileNotFound(path)
+            | Error::IndexEntryNotFound(path)
+            | Error::UnsafeOwnership { path, .. } => std::slice::from_ref(path),
+            Error::Conflicts(paths) | Error::UncommittedChanges(paths) => paths.as_slice(),
+            _ => &[],
+        };
+        for path in paths {
+            report.push_str(&format!("  path: {}\n", path.display()));
+        }
+
+        let mut source = std::error::Error::source(self);
+        while let Some(err) = source {
+            if let Some(err) = err.downcast_ref::<Git2Error>() {
+                report.push_str(&format!(
+                    "  caused by: git2 error (class: {:?}, code: {:?}): {}\n",
+                    err.class(),
+                    err.code(),
+                    err.message()
+                ));
+            } else if let Some(err) = err.downcast_ref::<std::io::Error>() {
+                report.push_str(&format!(
+                    "  caused by: io error (kind: {:?}): {}\n",
+                    err.kind(),
+                    err
+                ));
+            } else {
+                report.push_str(&format!("  caused by: {}\n", err));
+            }
+            source = err.source();
+        }
+
+        report
+    }
+
+    /// Return the kind of io error that matches the error best. Errors
+    /// wrapping git2 or io errors use the kind of the underlying error.
+    fn io_kind(&self) -> std::io::ErrorKind {
+        use git2::ErrorCode;
+        use std::io::ErrorKind;
+        use Error::*;
+
+        let source = std::error::Error::source(self);
+        if let Some(err) = source.and_then(|err| err.downcast_ref::<std::io::Error>()) {
+            return err.kind();
+        }
+        if let Some(err) = source.and_then(|err| err.downcast_ref::<Git2Error>()) {
+            return match err.code() {
+                ErrorCode::NotFound => ErrorKind::NotFound,
+                ErrorCode::Exists => ErrorKind::AlreadyExists,
+                ErrorCode::Auth | ErrorCode::Certificate | ErrorCode::Owner => {
+                    ErrorKind::PermissionDenied
+                }
+                ErrorCode::Ambiguous | ErrorCode::InvalidSpec | ErrorCode::Invalid => {
+                    ErrorKind::InvalidInput
+                }
+                ErrorCode::Timeout => ErrorKind::TimedOut,
+                _ => ErrorKind::Other,
+            };
         }
+
+        match self {
+            BranchNotFound(_)
+            | FileNotFound(_)
+            | IndexEntryNotFound(_)
+            | ObjectNotFound(_)
+            | RemoteNotFound(_)
+            | RevisionNotFound(_) => ErrorKind::NotFound,
+            BranchExists(_) => ErrorKind::AlreadyExists,
+            InvalidBranchName(_) | InvalidDate(_) | InvalidOid(_) => ErrorKind::InvalidInput,
+            BisectStateInvalid(_) | CommitBufferNotUtf8 => ErrorKind::InvalidData,
+            Authentication(_) | CertificateRejected(_) | UnsafeOwnership { .. } => {
+                ErrorKind::PermissionDenied
+            }
+            Timeout { .. } => ErrorKind::TimedOut,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match self {
+            Apply(err)
+            | Attributes(err)
+            | BranchCreate(err)
+            | BranchDelete(err)
+            | Checkout(err)
+            | CleanupState(err)
+            | Clone(err)
+            | CommitCreateBuffer(err)
+            | CommitTree(err)
+            | Config(err)
+            | Diff(err)
+            | FileStatus(err)
+            | HeadUpdate(err)
+            | IndexAdd(err)
+            | IndexAddPath(err)
+            | IndexApply(err)
+            | IndexClear(err)
+            | IndexConflictRemove(err)
+            | IndexConflicts(err)
+            | IndexOpen(err)
+            | IndexRemovePath(err)
+            | IndexWrite(err)
+            | IndexWriteTree(err)
+            | Mailmap(err)
+            | MergeHeads(err)
+            | Network(err)
+            | OdbForeach(err)
+            | OdbOpen(err)
+            | OdbRead(err)
+            | PackWrite(err)
+            | PatchParse(err)
+            | Rebase(err)
+            | RebaseAbort(err)
+            | RemoteConfig(err)
+            | RemoteConnect(err)
+            | RemoteFetch(err)
+            | RemoteList(err)
+            | RemotePush(err)
+            | Repack(err)
+            | RepositoryCommit(err)
+            | RepositoryFindBlob(err)
+            | RepositoryFindCommit(err)
+            | RepositoryFindTag(err)
+            | RepositoryFindTree(err)
+            | RepositoryHead(err)
+            | RepositoryInit(err)
+            | RepositoryOpen(err)
+            | RepositoryReferences(err)
+            | RepositoryRevwalk(err)
+            | RepositorySignature(err)
+            | RepositoryTags(err)
+            | RepositoryWriteBlob(err)
+            | Reset(err)
+            | Stash(err)
+            | TreeBuilder(err)
+            | TreeWalk(err) => Some(err),
+            BisectState(err)
+            | Description(err)
+            | PackRefsWrite(err)
+            | ReadDirectory(err)
+            | RepackFilesystem(err)
+            | ResolvePath(err)
+            | WorkdirWrite(err) => Some(err),
+            StripRepositoryPrefix(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Convert into an io error with the kind that matches the error best, e.g.
+/// ErrorKind::NotFound for missing revisions.
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        std::io::Error::new(err.io_kind(), err)
+    }
+}
+
+/// Errors wrapping git2 errors compare their code, class and message, errors
+/// wrapping io errors their kind and message.
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        use Error::*;
+
+        match (self, other) {
+            (Apply(a), Apply(b))
+            | (Attributes(a), Attributes(b))
+            | (BranchCreate(a), BranchCreate(b))
+            | (BranchDelete(a), BranchDelete(b))
+            | (Checkout(a), Checkout(b))
+            | (CleanupState(a), CleanupState(b))
+            | (Clone(a), Clone(b))
+            | (CommitCreateBuffer(a), CommitCreateBuffer(b))
+            | (CommitTree(a), CommitTree(b))
+            | (Config(a), Config(b))
+            | (Diff(a), Diff(b))
+            | (FileStatus(a), FileStatus(b))
+            | (HeadUpdate(a), HeadUpdate(b))
+            | (IndexAdd(a), IndexAdd(b))
+            | (IndexAddPath(a), IndexAddPath(b))
+            | (IndexApply(a), IndexApply(b))
+            | (IndexClear(a), IndexClear(b))
+            | (IndexConflictRemove(a), IndexConflictRemove(b))
+            | (IndexConflicts(a), IndexConflicts(b))
+            | (IndexOpen(a), IndexOpen(b))
+            | (IndexRemovePath(a), IndexRemovePath(b))
+            | (IndexWrite(a), IndexWrite(b))
+            | (IndexWriteTree(a), IndexWriteTree(b))
+            | (Mailmap(a), Mailmap(b))
+            | (MergeHeads(a), MergeHeads(b))
+            | (Network(a), Network(b))
+            | (OdbForeach(a), OdbForeach(b))
+            | (OdbOpen(a), OdbOpen(b))
+            | (OdbRead(a), OdbRead(b))
+            | (PackWrite(a), PackWrite(b))
+            | (PatchParse(a), PatchParse(b))
+            | (Rebase(a), Rebase(b))
+            | (RebaseAbort(a), RebaseAbort(b))
+            | (RemoteConfig(a), RemoteConfig(b))
+            | (RemoteConnect(a), RemoteConnect(b))
+            | (RemoteFetch(a), RemoteFetch(b))
+            | (RemoteList(a), RemoteList(b))
+            | (RemotePush(a), RemotePush(b))
+            | (Repack(a), Repack(b))
+            | (RepositoryCommit(a), RepositoryCommit(b))
+            | (RepositoryFindBlob(a), RepositoryFindBlob(b))
+            | (RepositoryFindCommit(a), RepositoryFindCommit(b))
+            | (RepositoryFindTag(a), RepositoryFindTag(b))
+            | (RepositoryFindTree(a), RepositoryFindTree(b))
+            | (RepositoryHead(a), RepositoryHead(b))
+            | (RepositoryInit(a), RepositoryInit(b))
+            | (RepositoryOpen(a), RepositoryOpen(b))
+            | (RepositoryReferences(a), RepositoryReferences(b))
+            | (RepositoryRevwalk(a), RepositoryRevwalk(b))
+            | (RepositorySignature(a), RepositorySignature(b))
+            | (RepositoryTags(a), RepositoryTags(b))
+            | (RepositoryWriteBlob(a), RepositoryWriteBlob(b))
+            | (Reset(a), Reset(b))
+            | (Stash(a), Stash(b))
+            | (TreeBuilder(a), TreeBuilder(b))
+            | (TreeWalk(a), TreeWalk(b)) => a == b,
+            (BisectState(a), BisectState(b))
+            | (Description(a), Description(b))
+            | (PackRefsWrite(a), PackRefsWrite(b))
+            | (ReadDirectory(a), ReadDirectory(b))
+            | (RepackFilesystem(a), RepackFilesystem(b))
+            | (ResolvePath(a), ResolvePath(b))
+            | (WorkdirWrite(a), WorkdirWrite(b)) => {
+                a.kind() == b.kind() && a.to_string() == b.to_string()
+            }
+            (ApplyConflict(a), ApplyConflict(b)) => a == b,
+            (Authentication(a), Authentication(b))
+            | (BisectStateInvalid(a), BisectStateInvalid(b))
+            | (BranchExists(a), BranchExists(b))
+            | (BranchNotFound(a), BranchNotFound(b))
+            | (CertificateRejected(a), CertificateRejected(b))
+            | (CommitSign(a), CommitSign(b))
+            | (InvalidBranchName(a), InvalidBranchName(b))
+            | (InvalidDate(a), InvalidDate(b))
+            | (InvalidOid(a), InvalidOid(b))
+            | (NoRemoteHead(a), NoRemoteHead(b))
+            | (NotAncestor(a), NotAncestor(b))
+            | (NotSigned(a), NotSigned(b))
+            | (ObjectNotFound(a), ObjectNotFound(b))
+            | (RemoteNoUrl(a), RemoteNoUrl(b))
+            | (RemoteNotFound(a), RemoteNotFound(b))
+            | (RevisionNotFound(a), RevisionNotFound(b))
+            | (SquashMerge(a), SquashMerge(b))
+            | (SquashPushed(a), SquashPushed(b))
+            | (UnknownGitignoreTemplate(a), UnknownGitignoreTemplate(b))
+            | (UnknownPlaceholder(a), UnknownPlaceholder(b)) => a == b,
+            (BisectInconclusive(a), BisectInconclusive(b)) => a == b,
+            (Conflicts(a), Conflicts(b)) | (UncommittedChanges(a), UncommittedChanges(b)) => a == b,
+            (FileNotFound(a), FileNotFound(b)) | (IndexEntryNotFound(a), IndexEntryNotFound(b)) => {
+                a == b
+            }
+            (HunkNotFound(a), HunkNotFound(b)) | (SquashPastRoot(a), SquashPastRoot(b)) => a == b,
+            (OperationInProgress(a), OperationInProgress(b)) => a == b,
+            (PushRejected(a), PushRejected(b)) => a == b,
+            (StripRepositoryPrefix(a), StripRepositoryPrefix(b)) => a == b,
+            (
+                RemoteMismatch {
+                    expected: a_expected,
+                    found: a_found,
+                },
+                RemoteMismatch {
+                    expected: b_expected,
+                    found: b_found,
+                },
+            ) => a_expected == b_expected && a_found == b_found,
+            (
+                Timeout {
+                    operation: a_operation,
+                    elapsed: a_elapsed,
+                },
+                Timeout {
+                    operation: b_operation,
+                    elapsed: b_elapsed,
+                },
+            ) => a_operation == b_operation && a_elapsed == b_elapsed,
+            (
+                UnsafeOwnership {
+                    path: a_path,
+                    owner_uid: a_owner_uid,
+                },
+                UnsafeOwnership {
+                    path: b_path,
+                    owner_uid: b_owner_uid,
+                },
+            ) => a_path == b_path && a_owner_uid == b_owner_uid,
+            (CommitBufferNotUtf8, CommitBufferNotUtf8)
+            | (HeadNotBranch, HeadNotBranch)
+            | (IndexConflictEmpty, IndexConflictEmpty)
+            | (NoBisectInProgress, NoBisectInProgress)
+            | (NoOperationInProgress, NoOperationInProgress)
+            | (NoOriginConfigured, NoOriginConfigured)
+            | (NoWorkdir, NoWorkdir)
+            | (NotEmptyHistory, NotEmptyHistory)
+            | (RepackIncomplete, RepackIncomplete)
+            | (SignatureNotConfigured, SignatureNotConfigured)
+            | (SnapshotUnsupported, SnapshotUnsupported) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io,
+        path::PathBuf,
+    };
+    use tempfile::tempdir;
+
+    use super::Error;
+
+    #[test]
+    fn chain_repository_open() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("missing");
+
+        let err = crate::status(&path).unwrap_err();
+
+        assert_eq!(
+            format!(
+                "error: can not open repository: failed to resolve path '{path}': No such file or \
+                 directory; class=Os (2); code=NotFound (-3)\n  variant: RepositoryOpen\n  caused \
+                 by: git2 error (class: Os, code: NotFound): failed to resolve path '{path}': No \
+                 such file or directory\n",
+                path = path.display()
+            ),
+            err.chain()
+        );
+        assert_eq!(io::ErrorKind::NotFound, io::Error::from(err).kind());
+    }
+
+    #[test]
+    fn chain_path_context() {
+        let err = Error::IndexEntryNotFound(PathBuf::from("some/file"));
+
+        assert_eq!(
+            "error: can not find index entry for path some/file\n  variant: IndexEntryNotFound\n  \
+             path: some/file\n",
+            err.chain()
+        );
+        assert_eq!(io::ErrorKind::NotFound, io::Error::from(err).kind());
+
+        for err in &[
+            Error::FileNotFound(PathBuf::from("some/file")),
+            Error::UnsafeOwnership {
+                path: PathBuf::from("some/file"),
+                owner_uid: Some(1000),
+            },
+        ] {
+            assert!(
+                err.chain().contains("\n  path: some/file\n"),
+                "{}",
+                err.chain()
+            );
+        }
+
+        for err in &[
+            Error::Conflicts(vec![PathBuf::from("first"), PathBuf::from("second")]),
+            Error::UncommittedChanges(vec![PathBuf::from("first"), PathBuf::from("second")]),
+        ] {
+            assert!(
+                err.chain().ends_with("\n  path: first\n  path: second\n"),
+                "{}",
+                err.chain()
+            );
+        }
+    }
+
+    #[test]
+    fn partial_eq() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        assert_eq!(
+            Err(Error::RevisionNotFound("missing".to_string())),
+            crate::commit_message(&dir, "missing")
+        );
+        assert_ne!(
+            Error::RevisionNotFound("missing".to_string()),
+            Error::BranchNotFound("missing".to_string())
+        );
+        assert_eq!(Error::NoWorkdir, Error::NoWorkdir);
     }
 }
@@ -0,0 +1,468 @@
+//! Fetching of references from a remote.
+
+use std::cell::RefCell;
+
+use git2::{
+    FetchOptions as Git2FetchOptions,
+    FetchPrune,
+};
+
+use crate::{
+    credentials::{
+        default_credential_sources,
+        CredentialResolver,
+        CredentialSource,
+    },
+    network::{
+        Cancel,
+        NetworkOptions,
+    },
+    Error,
+    GitHelper,
+};
+
+/// Refspec that fetches or pushes every reference to the same name, as used
+/// by mirrors.
+pub(crate) const MIRROR_REFSPEC: &str = "+refs/*:refs/*";
+
+/// Options for fetch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchOptions {
+    /// Name of the remote to fetch from.
+    pub remote: String,
+
+    /// Refspecs to fetch. If empty the refspecs configured for the remote
+    /// are used.
+    pub refspecs: Vec<String>,
+
+    /// Remove remote-tracking references whose branch no longer exists on
+    /// the remote.
+    pub prune: bool,
+
+    /// Fetch every reference of the remote including tags and notes to the
+    /// same name like a mirror clone and remove the references the remote
+    /// no longer has. Overrides `refspecs` and `prune`.
+    pub mirror: bool,
+
+    /// Limit the history of the fetched references to this many commits
+    /// from their tips like `git fetch --depth`. In a shallow repository a
+    /// larger depth fetches more of the history. If None the depth of a
+    /// shallow repository is kept and other repositories fetch the complete
+    /// history.
+    pub depth: Option<i32>,
+
+    /// Sources tried in order when the remote requires authentication.
+    pub credentials: Vec<CredentialSource>,
+
+    /// Options for the transport to the remote.
+    pub network: NetworkOptions,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            remote: "origin".to_string(),
+            refspecs: Vec::new(),
+            prune: false,
+            mirror: false,
+            depth: None,
+            credentials: default_credential_sources(),
+            network: NetworkOptions::default(),
+        }
+    }
+}
+
+/// Result of fetch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchReport {
+    /// Number of objects received from the remote.
+    pub objects_received: usize,
+
+    /// Local references that were created or moved by the fetch.
+    pub updated_refs: Vec<String>,
+
+    /// Remote-tracking references that were removed because of `prune`.
+    pub pruned_refs: Vec<String>,
+}
+
+/// Result of fetching from one remote in fetch_all.
+#[derive(Debug, PartialEq)]
+pub struct RemoteFetchResult {
+    /// Name of the remote.
+    pub remote: String,
+
+    /// Report of the fetch or why it failed.
+    pub result: Result<FetchReport, Error>,
+}
+
+impl GitHelper {
+    /// Fetch references and objects from a remote.
+    pub fn fetch(&self, options: &FetchOptions) -> Result<FetchReport, Error> {
+        let options = options.clone();
+
+        self.with_timeout(
+            "fetch",
+            options.network.operation_timeout,
+            move |helper, cancel| helper.fetch_cancellable(&options, cancel),
+        )
+    }
+
+    /// Fetch from every configured remote in the order of the configuration
+    /// with the refspecs configured for it. A failing remote does not stop
+    /// the others, the result of every remote is returned. The `remote` and
+    /// `refspecs` of the options are ignored, the other options like the
+    /// credentials and the network options apply to every remote.
+    pub fn fetch_all(&self, options: &FetchOptions) -> Result<Vec<RemoteFetchResult>, Error> {
+        let remotes = self.repository().remotes().map_err(Error::RemoteConfig)?;
+
+        let results = remotes
+            .iter_bytes()
+            .map(|remote| {
+                let remote = String::from_utf8_lossy(remote).into_owned();
+                let options = FetchOptions {
+                    remote: remote.clone(),
+                    refspecs: Vec::new(),
+                    ..options.clone()
+                };
+
+                RemoteFetchResult {
+                    result: self.fetch(&options),
+                    remote,
+                }
+            })
+            .collect();
+
+        Ok(results)
+    }
+
+    /// Fetch the history of the remote up to the depth, e.g. to get more
+    /// of the history of a shallow clone. The depth counts the commits from
+    /// the tips of the fetched references like `git fetch --depth`.
+    pub fn fetch_deepen(&self, remote: &str, depth: i32) -> Result<FetchReport, Error> {
+        self.fetch(&FetchOptions {
+            remote: remote.to_string(),
+            depth: Some(depth),
+            ..FetchOptions::default()
+        })
+    }
+
+    fn fetch_cancellable(
+        &self,
+        options: &FetchOptions,
+        cancel: &Cancel,
+    ) -> Result<FetchReport, Error> {
+        let repository = self.repository();
+        let mut remote = repository
+            .find_remote(&options.remote)
+            .map_err(|_| Error::RemoteNotFound(options.remote.clone()))?;
+
+        let _settings = options.network.apply_global()?;
+        let resolver = RefCell::new(CredentialResolver::new(repository, &options.credentials)?);
+        let report = RefCell::new(FetchReport::default());
+
+        {
+            let mut callbacks = CredentialResolver::callbacks(&resolver);
+            options.network.install(&mut callbacks);
+            cancel.install(&mut callbacks);
+            callbacks.update_tips(|reference, _, new| {
+                let mut report = report.borrow_mut();
+                if new.is_zero() {
+                    report.pruned_refs.push(reference.to_string());
+                } else {
+                    report.updated_refs.push(reference.to_string());
+                }
+                true
+            });
+
+            let mut fetch_options = Git2FetchOptions::new();
+            fetch_options
+                .remote_callbacks(callbacks)
+                .proxy_options(options.network.proxy_options())
+                .prune(if options.prune || options.mirror {
+                    FetchPrune::On
+                } else {
+                    FetchPrune::Off
+                });
+            if let Some(depth) = options.depth {
+                fetch_options.depth(depth);
+            }
+
+            let refspecs = if options.mirror {
+                vec![MIRROR_REFSPEC.to_string()]
+            } else {
+                options.refspecs.clone()
+            };
+
+            remote
+                .fetch(&refspecs, Some(&mut fetch_options), None)
+                .map_err(|err| resolver.borrow().error(err, Error::RemoteFetch))?;
+        }
+
+        let mut report = report.into_inner();
+        report.objects_received = remote.stats().received_objects();
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::Repository;
+    use std::{
+        fs,
+        net::{
+            TcpListener,
+            TcpStream,
+        },
+        path::Path,
+        process::{
+            Child,
+            Command,
+            Stdio,
+        },
+        thread,
+        time::Duration,
+    };
+    use tempfile::tempdir;
+
+    use super::FetchOptions;
+    use crate::GitHelper;
+
+    #[test]
+    fn fetch_prune() {
+        let upstream = tempdir().unwrap();
+        let helper = GitHelper::init(&upstream).unwrap();
+        fs::write(upstream.path().join("first_file"), "first data").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Added first_file").unwrap();
+
+        let head = helper
+            .repository()
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        helper.repository().branch("feature", &head, false).unwrap();
+
+        let local = tempdir().unwrap();
+        let repository = Repository::init(&local).unwrap();
+        repository
+            .remote("origin", upstream.path().to_str().unwrap())
+            .unwrap();
+
+        let report = crate::fetch(&local, &FetchOptions::default()).unwrap();
+        assert!(report.objects_received > 0);
+        assert!(report
+            .updated_refs
+            .contains(&"refs/remotes/origin/feature".to_string()));
+        assert!(repository
+            .find_reference("refs/remotes/origin/feature")
+            .is_ok());
+
+        helper
+            .repository()
+            .find_branch("feature", git2::BranchType::Local)
+            .unwrap()
+            .delete()
+            .unwrap();
+
+        // without prune the stale remote-tracking branch stays
+        crate::fetch(&local, &FetchOptions::default()).unwrap();
+        assert!(repository
+            .find_reference("refs/remotes/origin/feature")
+            .is_ok());
+
+        let options = FetchOptions {
+            prune: true,
+            ..FetchOptions::default()
+        };
+        let report = crate::fetch(&local, &options).unwrap();
+        assert_eq!(
+            vec!["refs/remotes/origin/feature".to_string()],
+            report.pruned_refs
+        );
+        assert!(repository
+            .find_reference("refs/remotes/origin/feature")
+            .is_err());
+        assert!(repository
+            .find_reference("refs/remotes/origin/master")
+            .is_ok());
+    }
+
+    #[test]
+    fn fetch_deepen_passes_depth() {
+        let upstream = tempdir().unwrap();
+        GitHelper::init(&upstream)
+            .unwrap()
+            .commit_empty("Initial commit")
+            .unwrap();
+
+        let dir = tempdir().unwrap();
+        GitHelper::init(&dir)
+            .unwrap()
+            .repository()
+            .remote("origin", upstream.path().to_str().unwrap())
+            .unwrap();
+
+        // the local transport refuses shallow fetches, so the error shows
+        // that the depth reached libgit2
+        match crate::fetch_deepen(&dir, "origin", 3) {
+            Err(crate::Error::RemoteFetch(err)) => {
+                assert_eq!(git2::ErrorClass::Net, err.class());
+                assert!(err.message().contains("shallow"), "{}", err.message());
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        crate::fetch(&dir, &FetchOptions::default()).unwrap();
+    }
+
+    /// `git daemon` serving the repositories below a directory, killed when
+    /// dropped. libgit2 does not support shallow fetches over the local
+    /// transport so tests of them need a server.
+    struct Daemon(Child);
+
+    impl Drop for Daemon {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    /// Serve the repositories below the directory with `git daemon` and
+    /// return it with its port. Panics if git is not installed.
+    fn daemon(base: &Path) -> (Daemon, u16) {
+        // `git daemon` runs git-daemon as child process which would outlive
+        // the test when only git is killed, so git-daemon is started directly
+        let exec_path = Command::new("git")
+            .arg("--exec-path")
+            .output()
+            .expect("git has to be installed to run this test");
+        let exec_path = String::from_utf8(exec_path.stdout).unwrap();
+
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let child = Command::new(Path::new(exec_path.trim()).join("git-daemon"))
+            .arg("--reuseaddr")
+            .arg("--export-all")
+            .arg("--listen=127.0.0.1")
+            .arg(format!("--port={}", port))
+            .arg(format!("--base-path={}", base.display()))
+            .arg(base)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("git-daemon has to be installed to run this test");
+        let daemon = Daemon(child);
+
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                return (daemon, port);
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        panic!("git daemon did not start");
+    }
+
+    /// Needs git with git-daemon installed. Run with `cargo test -- --ignored
+    /// fetch_deepen`.
+    #[test]
+    #[ignore = "needs git-daemon"]
+    fn fetch_deepen() {
+        let base = tempdir().unwrap();
+        let helper = GitHelper::init(base.path().join("upstream")).unwrap();
+        helper.commit_empty("Commit 0").unwrap();
+        for index in 1..5 {
+            helper.commit(&format!("Commit {}", index)).unwrap();
+        }
+
+        let (_daemon, port) = daemon(base.path());
+
+        let dir = tempdir().unwrap();
+        let url = format!("git://127.0.0.1:{}/upstream", port);
+        let options = crate::CloneOptions {
+            depth: Some(1),
+            ..crate::CloneOptions::default()
+        };
+        let clone = GitHelper::clone_or_open(&url, &dir, &options).unwrap();
+        assert!(clone.repository().is_shallow());
+        let count = || crate::commit_count(&dir, &crate::LogFilter::default()).unwrap();
+        assert_eq!(1, count());
+
+        crate::fetch_deepen(&dir, "origin", 3).unwrap();
+        assert_eq!(3, count());
+        assert!(clone.repository().is_shallow());
+    }
+
+    #[test]
+    fn fetch_all() {
+        let dir = tempdir().unwrap();
+        let repository = Repository::init(&dir).unwrap();
+
+        let mut remotes = Vec::new();
+        for name in &["origin", "upstream"] {
+            let remote = tempdir().unwrap();
+            GitHelper::init(&remote)
+                .unwrap()
+                .commit_empty(&format!("Initial {} commit", name))
+                .unwrap();
+            repository
+                .remote(name, remote.path().to_str().unwrap())
+                .unwrap();
+            remotes.push(remote);
+        }
+
+        let results = crate::fetch_all(&dir, &FetchOptions::default()).unwrap();
+        assert_eq!(
+            vec!["origin", "upstream"],
+            results
+                .iter()
+                .map(|result| result.remote.as_str())
+                .collect::<Vec<_>>()
+        );
+        for result in &results {
+            let report = result.result.as_ref().unwrap();
+            assert!(report.objects_received > 0);
+            assert_eq!(
+                vec![format!("refs/remotes/{}/master", result.remote)],
+                report.updated_refs
+            );
+        }
+
+        // a missing remote fails without stopping the others
+        remotes.remove(0).close().unwrap();
+        GitHelper::open(&remotes[0])
+            .unwrap()
+            .commit("Second upstream commit")
+            .unwrap();
+
+        let results = crate::fetch_all(&dir, &FetchOptions::default()).unwrap();
+        assert!(matches!(
+            results[0].result,
+            Err(crate::Error::RemoteFetch(_))
+        ));
+        assert_eq!(
+            vec!["refs/remotes/upstream/master".to_string()],
+            results[1].result.as_ref().unwrap().updated_refs
+        );
+    }
+
+    #[test]
+    fn fetch_missing_remote() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        let options = FetchOptions {
+            remote: "upstream".to_string(),
+            ..FetchOptions::default()
+        };
+        match crate::fetch(&dir, &options) {
+            Err(crate::Error::RemoteNotFound(name)) => assert_eq!("upstream", name),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Inspection of the commit history of a repository.
+
+use git2::{
+    Commit,
+    Oid,
+};
+
+use crate::{
+    time::Timestamp,
+    Error,
+    GitHelper,
+};
+
+impl GitHelper {
+    /// Return the ids of the parents of the commit the revspec resolves to as
+    /// hex strings in the order they are stored in the commit. A root commit
+    /// has no parents, a merge commit has two or more.
+    pub fn commit_parents(&self, revspec: &str) -> Result<Vec<String>, Error> {
+        let commit = self.find_commit(revspec)?;
+
+        Ok(commit.parent_ids().map(|oid| oid.to_string()).collect())
+    }
+
+    /// Return the time the commit the revspec resolves to was authored at.
+    pub fn authored_at(&self, revspec: &str) -> Result<Timestamp, Error> {
+        let commit = self.find_commit(revspec)?;
+        let time = commit.author().when();
+
+        Ok(time.into())
+    }
+
+    /// Return the time the commit the revspec resolves to was committed at.
+    /// The time is independent of timezones, the offset of the committer's
+    /// timezone is kept separately in the timestamp.
+    pub fn committed_at(&self, revspec: &str) -> Result<Timestamp, Error> {
+        let commit = self.find_commit(revspec)?;
+        let time = commit.committer().when();
+
+        Ok(time.into())
+    }
+
+    /// True if the commit `ancestor` resolves to is reachable from the commit
+    /// `descendant` resolves to, like `git merge-base --is-ancestor`. A
+    /// commit is its own ancestor.
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool, Error> {
+        let ancestor = self.find_commit(ancestor)?.id();
+        let descendant = self.find_commit(descendant)?.id();
+
+        if ancestor == descendant {
+            return Ok(true);
+        }
+
+        self.repository()
+            .graph_descendant_of(descendant, ancestor)
+            .map_err(Error::RepositoryRevwalk)
+    }
+
+    /// Return the complete message of the commit the revspec resolves to
+    /// including the body and trailers.
+    pub fn commit_message(&self, revspec: &str) -> Result<String, Error> {
+        let commit = self.find_commit(revspec)?;
+
+        Ok(String::from_utf8_lossy(commit.message_bytes()).into_owned())
+    }
+
+    /// Return the id of the root tree of the commit the revspec resolves to.
+    /// Commits with the same content have the same tree id regardless of
+    /// their message, parents or signatures.
+    pub fn tree_oid(&self, revspec: &str) -> Result<Oid, Error> {
+        Ok(self.find_commit(revspec)?.tree_id())
+    }
+
+    /// Return the commit the revspec resolves to. Fails with
+    /// Error::RevisionNotFound if the revspec does not resolve to a commit.
+    pub(crate) fn find_commit(&self, revspec: &str) -> Result<Commit<'_>, Error> {
+        self.repository()
+            .revparse_single(revspec)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| Error::RevisionNotFound(revspec.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::{
+        Signature,
+        Time,
+    };
+    use std::time::{
+        Duration,
+        UNIX_EPOCH,
+    };
+    use tempfile::tempdir;
+
+    use crate::{
+        CommitOptions,
+        GitHelper,
+        Timestamp,
+    };
+
+    #[test]
+    fn commit_parents() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.commit_empty("Initial commit").unwrap();
+        helper.commit("Second commit").unwrap();
+
+        let repository = helper.repository();
+        let second = repository.head().unwrap().peel_to_commit().unwrap();
+        let root = second.parent(0).unwrap();
+
+        let signature = repository.signature().unwrap();
+        let merge = repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Merge commit",
+                &second.tree().unwrap(),
+                &[&second, &root],
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![second.id().to_string(), root.id().to_string()],
+            crate::commit_parents(&dir, "HEAD").unwrap()
+        );
+        assert_eq!(
+            vec![root.id().to_string()],
+            crate::commit_parents(&dir, &format!("{}^", merge)).unwrap()
+        );
+        assert!(crate::commit_parents(&dir, &root.id().to_string())
+            .unwrap()
+            .is_empty());
+
+        match crate::commit_parents(&dir, "missing") {
+            Err(crate::Error::RevisionNotFound(revspec)) => assert_eq!("missing", revspec),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn commit_with_fixed_timestamps() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        let options = CommitOptions {
+            author_time: Some(Timestamp::from_seconds(1_500_000_000, 120)),
+            committer_time: Some(Timestamp::from_seconds(1_600_000_000, -300)),
+            ..CommitOptions::default()
+        };
+        helper.commit_with("Reproducible commit", &options).unwrap();
+
+        assert_eq!(
+            options.author_time.unwrap(),
+            crate::authored_at(&dir, "HEAD").unwrap()
+        );
+        assert_eq!(
+            options.committer_time.unwrap(),
+            crate::committed_at(&dir, "HEAD").unwrap()
+        );
+
+        // the same content and timestamps result in the same commit
+        let first = helper.repository().head().unwrap().target().unwrap();
+        let other = tempdir().unwrap();
+        let other_helper = GitHelper::init(&other).unwrap();
+        other_helper
+            .commit_with("Reproducible commit", &options)
+            .unwrap();
+        assert_eq!(
+            first,
+            other_helper.repository().head().unwrap().target().unwrap()
+        );
+    }
+
+    #[test]
+    fn committed_at_system_time() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        let repository = helper.repository();
+
+        let signature = Signature::new(
+            "Tester",
+            "tester@example.com",
+            &Time::new(1_600_000_000, -300),
+        )
+        .unwrap();
+        let tree = repository
+            .find_tree(repository.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Fixed time",
+                &tree,
+                &[],
+            )
+            .unwrap();
+
+        let committed_at = crate::committed_at(&dir, "HEAD").unwrap();
+        assert_eq!(
+            UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            committed_at.time
+        );
+        assert_eq!(-300, committed_at.offset_minutes);
+    }
+
+    #[test]
+    fn is_ancestor() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.commit_empty("First commit").unwrap();
+        helper.commit("Second commit").unwrap();
+        helper.commit("Third commit").unwrap();
+
+        let repository = helper.repository();
+        let signature = repository.signature().unwrap();
+        let tree = helper.head_commit().unwrap().tree().unwrap();
+        repository
+            .commit(
+                Some("refs/heads/unrelated"),
+                &signature,
+                &signature,
+                "Unrelated root commit",
+                &tree,
+                &[],
+            )
+            .unwrap();
+
+        assert!(crate::is_ancestor(&dir, "HEAD~2", "HEAD").unwrap());
+        assert!(crate::is_ancestor(&dir, "HEAD", "HEAD").unwrap());
+        assert!(!crate::is_ancestor(&dir, "HEAD", "HEAD~2").unwrap());
+        assert!(!crate::is_ancestor(&dir, "unrelated", "HEAD").unwrap());
+        assert!(!crate::is_ancestor(&dir, "HEAD~2", "unrelated").unwrap());
+    }
+
+    #[test]
+    fn tree_oid() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        std::fs::write(dir.path().join("first_file"), "first data").unwrap();
+        helper.stage(&["first_file"]).unwrap();
+        helper.commit("First commit").unwrap();
+        helper.commit("Second commit").unwrap();
+
+        let tree = crate::tree_oid(&dir, "HEAD").unwrap();
+        crate::commit_to_bare(&dir, "other", tree, "Unrelated root commit").unwrap();
+
+        assert_eq!(tree, crate::tree_oid(&dir, "HEAD~1").unwrap());
+        assert_eq!(tree, crate::tree_oid(&dir, "other").unwrap());
+        assert_ne!(
+            crate::commit_parents(&dir, "HEAD").unwrap(),
+            crate::commit_parents(&dir, "other").unwrap()
+        );
+
+        match crate::tree_oid(&dir, "missing") {
+            Err(crate::Error::RevisionNotFound(revspec)) => assert_eq!("missing", revspec),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn commit_message() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        let message = "Add parser\n\nThe parser reads the\nconfiguration.\n\nSigned-off-by: \
+                       Tester <tester@example.com>\n";
+        helper.commit(message).unwrap();
+
+        assert_eq!(message, crate::commit_message(&dir, "HEAD").unwrap());
+
+        let entries = crate::log_filtered(&dir, &crate::LogFilter::default()).unwrap();
+        assert_eq!("Add parser", entries[0].summary);
+        assert_eq!(message, entries[0].message);
+        assert!(entries[0]
+            .message
+            .contains("The parser reads the\nconfiguration."));
+        assert!(entries[0].message.contains("Signed-off-by: Tester"));
+    }
+}
@@ -0,0 +1,140 @@
+//! Staging of single hunks of the changes to a file, like `git add --patch`.
+
+use std::{
+    cell::Cell,
+    path::Path,
+};
+
+use git2::{
+    ApplyOptions,
+    DiffOptions,
+    Patch,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+impl GitHelper {
+    /// Stage only the given hunks of the changes between the index and the
+    /// working directory of a file. Hunks are counted from zero in the order
+    /// `git diff` shows them; the other hunks stay unstaged. The path has to
+    /// be relative to the working directory and tracked in the index. Fails
+    /// with Error::HunkNotFound if an index is out of range.
+    pub fn stage_hunks<F: AsRef<Path>>(
+        &self,
+        path: F,
+        hunk_indices: &[usize],
+    ) -> Result<(), Error> {
+        let repository = self.repository();
+        let path = path.as_ref();
+
+        let mut diff_options = DiffOptions::new();
+        diff_options.pathspec(path).disable_pathspec_match(true);
+
+        let mut index = repository.index().map_err(Error::IndexOpen)?;
+        let diff = repository
+            .diff_index_to_workdir(Some(&index), Some(&mut diff_options))
+            .map_err(Error::Diff)?;
+
+        let hunk_count = match Patch::from_diff(&diff, 0).map_err(Error::Diff)? {
+            Some(patch) => patch.num_hunks(),
+            None => 0,
+        };
+        if let Some(missing) = hunk_indices.iter().find(|index| **index >= hunk_count) {
+            return Err(Error::HunkNotFound(*missing));
+        }
+
+        let current = Cell::new(0);
+        let mut apply_options = ApplyOptions::new();
+        apply_options.hunk_callback(|hunk| {
+            if hunk.is_none() {
+                return true;
+            }
+
+            let selected = hunk_indices.contains(&current.get());
+            current.set(current.get() + 1);
+            selected
+        });
+
+        let tree = self.index_tree()?;
+        let applied = repository
+            .apply_to_tree(&tree, &diff, Some(&mut apply_options))
+            .map_err(Error::IndexApply)?;
+
+        let entry = applied
+            .get_path(path, 0)
+            .ok_or_else(|| Error::IndexEntryNotFound(path.to_path_buf()))?;
+
+        index.add(&entry).map_err(Error::IndexAdd)?;
+        self.write_index(&mut index)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::tempdir;
+
+    use crate::GitHelper;
+
+    fn numbered_lines(changed: &[usize]) -> String {
+        (1..=20)
+            .map(|line| {
+                if changed.contains(&line) {
+                    format!("changed line {}\n", line)
+                } else {
+                    format!("line {}\n", line)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stage_first_hunk() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("file"), numbered_lines(&[])).unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added file").unwrap();
+
+        fs::write(dir.path().join("file"), numbered_lines(&[2, 18])).unwrap();
+        crate::stage_hunks(&dir, "file", &[0]).unwrap();
+
+        let status = crate::status(&dir).unwrap();
+        assert_eq!(1, status.len());
+        assert_eq!(
+            git2::Status::INDEX_MODIFIED | git2::Status::WT_MODIFIED,
+            status[0].status
+        );
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let index = helper.repository().index().unwrap();
+        let entry = index.get_path(std::path::Path::new("file"), 0).unwrap();
+        let blob = helper.repository().find_blob(entry.id).unwrap();
+        assert_eq!(numbered_lines(&[2]).as_bytes(), blob.content());
+
+        // staging the remaining hunk leaves nothing unstaged
+        crate::stage_hunks(&dir, "file", &[0]).unwrap();
+        let status = crate::status(&dir).unwrap();
+        assert_eq!(git2::Status::INDEX_MODIFIED, status[0].status);
+    }
+
+    #[test]
+    fn stage_missing_hunk() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("file"), numbered_lines(&[])).unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added file").unwrap();
+
+        fs::write(dir.path().join("file"), numbered_lines(&[2, 18])).unwrap();
+        match crate::stage_hunks(&dir, "file", &[0, 2]) {
+            Err(crate::Error::HunkNotFound(index)) => assert_eq!(2, index),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! Resolution of the author and committer of new commits.
+
+use std::env;
+
+use git2::{
+    Config,
+    Signature,
+};
+
+use crate::{
+    config::optional,
+    repo::CommitOptions,
+    time::{
+        parse_git_date,
+        Timestamp,
+    },
+    Error,
+    GitHelper,
+};
+
+/// Name, email and time of the author or committer of a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sig {
+    /// Name of the person.
+    pub name: String,
+
+    /// Email of the person.
+    pub email: String,
+
+    /// Time the commit was authored or committed at.
+    pub when: Timestamp,
+}
+
+impl Sig {
+    /// Convert into a git2 signature. Fails with Error::RepositorySignature
+    /// if the name or email contain characters git does not allow, e.g.
+    /// angle brackets.
+    pub fn to_signature(&self) -> Result<Signature<'static>, Error> {
+        Signature::new(&self.name, &self.email, &self.when.into())
+            .map_err(Error::RepositorySignature)
+    }
+}
+
+impl From<&Signature<'_>> for Sig {
+    fn from(signature: &Signature<'_>) -> Self {
+        Self {
+            name: String::from_utf8_lossy(signature.name_bytes()).into_owned(),
+            email: String::from_utf8_lossy(signature.email_bytes()).into_owned(),
+            when: signature.when().into(),
+        }
+    }
+}
+
+/// Role of a person in a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Role {
+    Author,
+    Committer,
+}
+
+impl Role {
+    fn env_prefix(self) -> &'static str {
+        match self {
+            Role::Author => "GIT_AUTHOR",
+            Role::Committer => "GIT_COMMITTER",
+        }
+    }
+
+    fn config_section(self) -> &'static str {
+        match self {
+            Role::Author => "author",
+            Role::Committer => "committer",
+        }
+    }
+}
+
+impl GitHelper {
+    /// Return the author new commits will be created with, like `git var
+    /// GIT_AUTHOR_IDENT`. The name is taken from `GIT_AUTHOR_NAME`,
+    /// `author.name` or `user.name` and the email from `GIT_AUTHOR_EMAIL`,
+    /// `author.email`, `user.email` or `EMAIL`, whichever is set first. The
+    /// time is taken from `GIT_AUTHOR_DATE` if set, see
+    /// [`parse_git_date`](crate::time::parse_git_date) for the accepted
+    /// formats. Fails with Error::SignatureNotConfigured if no name or no
+    /// email is set and with Error::InvalidDate if the date can not be
+    /// parsed.
+    pub fn effective_signature(&self) -> Result<Sig, Error> {
+        let config = self.config()?;
+
+        resolve_signature(&config, Role::Author, &|name| env::var(name).ok())
+    }
+
+    /// Return the author and committer of a new commit. Signatures and times
+    /// given in the options take precedence over the `GIT_AUTHOR_*` and
+    /// `GIT_COMMITTER_*` environment variables which take precedence over the
+    /// configuration.
+    pub(crate) fn commit_signatures(
+        &self,
+        options: &CommitOptions,
+    ) -> Result<(Signature<'static>, Signature<'static>), Error> {
+        self.commit_signatures_with_env(options, &|name| env::var(name).ok())
+    }
+
+    /// Like commit_signatures but with the environment read through `env`.
+    pub(crate) fn commit_signatures_with_env(
+        &self,
+        options: &CommitOptions,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<(Signature<'static>, Signature<'static>), Error> {
+        let config = self.config()?;
+
+        let mut author = match &options.author {
+            Some(author) => author.clone(),
+            None => resolve_signature(&config, Role::Author, env)?,
+        };
+        let mut committer = match &options.committer {
+            Some(committer) => committer.clone(),
+            None => resolve_signature(&config, Role::Committer, env)?,
+        };
+
+        if let Some(author_time) = options.author_time {
+            author.when = author_time;
+        }
+        if let Some(committer_time) = options.committer_time {
+            committer.when = committer_time;
+        }
+
+        Ok((author.to_signature()?, committer.to_signature()?))
+    }
+}
+
+/// Resolve the signature for the role from the environment and the
+/// configuration like git does. The environment is read through `env` so
+/// tests do not have to change the environment of the process.
+pub(crate) fn resolve_signature(
+    config: &Config,
+    role: Role,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<Sig, Error> {
+    let config_value = |key: &str| -> Result<Option<String>, Error> {
+        let role_key = format!("{}.{}", role.config_section(), key);
+        match optional(config.get_string(&role_key))? {
+            Some(value) => Ok(Some(value)),
+            None => optional(config.get_string(&format!("user.{}", key))),
+        }
+    };
+
+    let name = match env(&format!("{}_NAME", role.env_prefix())) {
+        Some(name) => name,
+        None => config_value("name")?.ok_or(Error::SignatureNotConfigured)?,
+    };
+
+    let email = match env(&format!("{}_EMAIL", role.env_prefix())) {
+        Some(email) => email,
+        None => match config_value("email")? {
+            Some(email) => email,
+            None => env("EMAIL").ok_or(Error::SignatureNotConfigured)?,
+        },
+    };
+
+    let now = Signature::now(&name, &email).map_err(Error::RepositorySignature)?;
+    let mut signature = Sig::from(&now);
+
+    if let Some(date) = env(&format!("{}_DATE", role.env_prefix())) {
+        signature.when = parse_git_date(&date).ok_or(Error::InvalidDate(date))?;
+    }
+
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod test {
+    use git2::Config;
+    use std::{
+        collections::HashMap,
+        fs,
+    };
+    use tempfile::tempdir;
+
+    use super::{
+        resolve_signature,
+        Role,
+        Sig,
+    };
+    use crate::{
+        CommitOptions,
+        GitHelper,
+        Timestamp,
+    };
+
+    fn resolve(config: &str, env: &[(&str, &str)], role: Role) -> Result<Sig, crate::Error> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config");
+        fs::write(&path, config).unwrap();
+        let config = Config::open(&path).unwrap();
+
+        let env = env
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        resolve_signature(&config, role, &|name| env.get(name).cloned())
+    }
+
+    #[test]
+    fn signature_from_config() {
+        let config = "[user]\n\tname = Config User\n\temail = \
+                      config@example.com\n[committer]\n\tname = Config Committer\n";
+
+        let author = resolve(config, &[], Role::Author).unwrap();
+        assert_eq!("Config User", author.name);
+        assert_eq!("config@example.com", author.email);
+
+        let committer = resolve(config, &[], Role::Committer).unwrap();
+        assert_eq!("Config Committer", committer.name);
+        assert_eq!("config@example.com", committer.email);
+    }
+
+    #[test]
+    fn signature_from_env() {
+        let config = "[user]\n\tname = Config User\n\temail = config@example.com\n";
+        let env = [
+            ("GIT_AUTHOR_NAME", "Env Author"),
+            ("GIT_AUTHOR_EMAIL", "author@example.com"),
+            ("GIT_COMMITTER_NAME", "Env Committer"),
+        ];
+
+        let author = resolve(config, &env, Role::Author).unwrap();
+        assert_eq!("Env Author", author.name);
+        assert_eq!("author@example.com", author.email);
+
+        let committer = resolve(config, &env, Role::Committer).unwrap();
+        assert_eq!("Env Committer", committer.name);
+        assert_eq!("config@example.com", committer.email);
+
+        let author = resolve(
+            "",
+            &[
+                ("GIT_AUTHOR_NAME", "Env Author"),
+                ("EMAIL", "fallback@example.com"),
+            ],
+            Role::Author,
+        )
+        .unwrap();
+        assert_eq!("fallback@example.com", author.email);
+    }
+
+    #[test]
+    fn signature_missing() {
+        match resolve("", &[], Role::Author) {
+            Err(crate::Error::SignatureNotConfigured) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match resolve("[user]\n\tname = Config User\n", &[], Role::Author) {
+            Err(crate::Error::SignatureNotConfigured) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn commit_with_signature_override() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.config_set("user.name", "Repository User").unwrap();
+        helper
+            .config_set("user.email", "repository@example.com")
+            .unwrap();
+
+        let signature = crate::effective_signature(&dir).unwrap();
+        if std::env::var("GIT_AUTHOR_NAME").is_err() {
+            assert_eq!("Repository User", signature.name);
+        }
+
+        let author = Sig {
+            name: "Override Author".to_string(),
+            email: "override@example.com".to_string(),
+            when: Timestamp::from_seconds(1_500_000_000, 60),
+        };
+        let options = CommitOptions {
+            author: Some(author.clone()),
+            ..CommitOptions::default()
+        };
+        helper.commit_with("Commit with author", &options).unwrap();
+
+        let head = helper.head_commit().unwrap();
+        assert_eq!(author, Sig::from(&head.author()));
+    }
+
+    #[test]
+    fn commit_signatures_from_env() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.config_set("user.name", "Repository User").unwrap();
+        helper
+            .config_set("user.email", "repository@example.com")
+            .unwrap();
+
+        let env = [
+            ("GIT_AUTHOR_NAME", "Env Author"),
+            ("GIT_AUTHOR_EMAIL", "author@example.com"),
+            ("GIT_AUTHOR_DATE", "Thu, 07 Apr 2005 22:13:13 +0200"),
+            ("GIT_COMMITTER_DATE", "@1112904793 -0500"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<HashMap<_, _>>();
+        let env = |name: &str| env.get(name).cloned();
+
+        // explicit options take precedence over the environment
+        let options = CommitOptions {
+            committer_time: Some(Timestamp::from_seconds(1_600_000_000, 0)),
+            ..CommitOptions::default()
+        };
+        let (author, committer) = helper.commit_signatures_with_env(&options, &env).unwrap();
+
+        let tree = helper.index_tree().unwrap();
+        let oid = helper
+            .repository()
+            .commit(Some("HEAD"), &author, &committer, "Commit", &tree, &[])
+            .unwrap();
+        let commit = helper.repository().find_commit(oid).unwrap();
+
+        assert_eq!(
+            Sig {
+                name: "Env Author".to_string(),
+                email: "author@example.com".to_string(),
+                when: Timestamp::from_seconds(1_112_904_793, 120),
+            },
+            Sig::from(&commit.author())
+        );
+        assert_eq!(
+            Sig {
+                name: "Repository User".to_string(),
+                email: "repository@example.com".to_string(),
+                when: Timestamp::from_seconds(1_600_000_000, 0),
+            },
+            Sig::from(&commit.committer())
+        );
+
+        let invalid = |name: &str| match name {
+            "GIT_AUTHOR_DATE" => Some("yesterday".to_string()),
+            _ => None,
+        };
+        match helper.commit_signatures_with_env(&CommitOptions::default(), &invalid) {
+            Err(crate::Error::InvalidDate(date)) => assert_eq!("yesterday", date),
+            Err(err) => panic!("unexpected error: {:?}", err),
+            Ok(_) => panic!("invalid date was accepted"),
+        }
+    }
+}
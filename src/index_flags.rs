@@ -0,0 +1,113 @@
+//! Management of the skip-worktree and assume-unchanged flags of index
+//! entries.
+
+use std::{
+    collections::HashSet,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use git2::{
+    Index,
+    IndexEntry,
+    IndexEntryExtendedFlag,
+    IndexEntryFlag,
+};
+
+use crate::{
+    repo::path_from_bytes,
+    Error,
+    GitHelper,
+};
+
+/// Index entry that has the skip-worktree or the assume-unchanged flag set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedEntry {
+    /// Path of the entry relative to the repository root.
+    pub path: PathBuf,
+
+    /// True if the skip-worktree flag is set for the entry.
+    pub skip_worktree: bool,
+
+    /// True if the assume-unchanged flag is set for the entry.
+    pub assume_unchanged: bool,
+}
+
+impl GitHelper {
+    /// Set or clear the skip-worktree flag of the index entry for the given
+    /// path. Entries with the flag set are not reported by status and not
+    /// staged by stage_all even when the file was modified in the working
+    /// tree.
+    pub fn set_skip_worktree<F: AsRef<Path>>(&self, path: F, on: bool) -> Result<(), Error> {
+        self.update_index_entry(path, |entry| {
+            if on {
+                entry.flags_extended |= IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            } else {
+                entry.flags_extended &= !IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            }
+        })
+    }
+
+    /// Set or clear the assume-unchanged flag of the index entry for the
+    /// given path.
+    pub fn set_assume_unchanged<F: AsRef<Path>>(&self, path: F, on: bool) -> Result<(), Error> {
+        self.update_index_entry(path, |entry| {
+            if on {
+                entry.flags |= IndexEntryFlag::VALID.bits();
+            } else {
+                entry.flags &= !IndexEntryFlag::VALID.bits();
+            }
+        })
+    }
+
+    /// List all index entries that have the skip-worktree or the
+    /// assume-unchanged flag set.
+    pub fn list_skipped(&self) -> Result<Vec<SkippedEntry>, Error> {
+        let index = self.repository().index().map_err(Error::IndexOpen)?;
+
+        let entries = index
+            .iter()
+            .map(|entry| SkippedEntry {
+                path: path_from_bytes(&entry.path),
+                skip_worktree: IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended)
+                    .is_skip_worktree(),
+                assume_unchanged: IndexEntryFlag::from_bits_truncate(entry.flags).is_valid(),
+            })
+            .filter(|entry| entry.skip_worktree || entry.assume_unchanged)
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn update_index_entry<F, U>(&self, path: F, update: U) -> Result<(), Error>
+    where
+        F: AsRef<Path>,
+        U: FnOnce(&mut IndexEntry),
+    {
+        let mut index = self.repository().index().map_err(Error::IndexOpen)?;
+
+        let mut entry = index
+            .get_path(path.as_ref(), 0)
+            .ok_or_else(|| Error::IndexEntryNotFound(path.as_ref().to_path_buf()))?;
+
+        update(&mut entry);
+
+        index.add(&entry).map_err(Error::IndexAdd)?;
+        self.write_index(&mut index)?;
+
+        Ok(())
+    }
+}
+
+/// Return the paths of all index entries flagged as skip-worktree.
+pub(crate) fn skip_worktree_paths(index: &Index) -> HashSet<PathBuf> {
+    index
+        .iter()
+        .filter(|entry| {
+            IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree()
+        })
+        .map(|entry| path_from_bytes(&entry.path))
+        .collect()
+}
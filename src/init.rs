@@ -0,0 +1,214 @@
+//! Creation of repositories with a default branch, a `.gitignore` and an
+//! initial commit.
+
+use std::{
+    fs,
+    path::Path,
+};
+
+use git2::{
+    FileMode,
+    Repository,
+    RepositoryInitOptions,
+};
+
+use crate::{
+    CommitOptions,
+    Error,
+    GitHelper,
+};
+
+/// Content of the `.gitignore` created by GitHelper::init_with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Gitignore {
+    /// Use the given content.
+    Contents(String),
+
+    /// Use one of the built-in templates: `rust`, `node` or `python`.
+    /// Unknown names fail with Error::UnknownGitignoreTemplate.
+    Template(String),
+}
+
+impl Gitignore {
+    /// Return the content of the `.gitignore`.
+    fn contents(&self) -> Result<&str, Error> {
+        match self {
+            Gitignore::Contents(contents) => Ok(contents),
+            Gitignore::Template(name) => match name.as_str() {
+                "rust" => Ok("/target\n"),
+                "node" => Ok("node_modules/\nnpm-debug.log*\n"),
+                "python" => Ok("__pycache__/\n*.py[cod]\n.venv/\n"),
+                _ => Err(Error::UnknownGitignoreTemplate(name.clone())),
+            },
+        }
+    }
+}
+
+/// Options for GitHelper::init_with. The default creates a nonbare
+/// repository like GitHelper::init.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InitOptions {
+    /// Create a bare repository without working directory.
+    pub bare: bool,
+
+    /// Name of the branch HEAD points to, e.g. `main`. If None the branch is
+    /// named like git does, from `init.defaultBranch` or `master`.
+    pub default_branch: Option<String>,
+
+    /// Write a `.gitignore` to the working directory. Bare repositories
+    /// only get the `.gitignore` in the initial commit, so it requires
+    /// `initial_commit` for them.
+    pub gitignore: Option<Gitignore>,
+
+    /// Create an initial commit with this message. The commit contains the
+    /// `.gitignore` if one is written and is empty otherwise, so branches
+    /// can be created right away. The signature is resolved like for
+    /// GitHelper::commit.
+    pub initial_commit: Option<String>,
+}
+
+impl GitHelper {
+    /// Create a new git repository in the given path as configured by the
+    /// options and open it.
+    pub fn init_with<P: AsRef<Path>>(repo_path: P, options: &InitOptions) -> Result<Self, Error> {
+        let gitignore = options
+            .gitignore
+            .as_ref()
+            .map(Gitignore::contents)
+            .transpose()?;
+        if options.bare && gitignore.is_some() && options.initial_commit.is_none() {
+            return Err(Error::NoWorkdir);
+        }
+
+        let mut init_options = RepositoryInitOptions::new();
+        init_options.bare(options.bare);
+        if let Some(branch) = &options.default_branch {
+            init_options.initial_head(branch);
+        }
+
+        let repository =
+            Repository::init_opts(&repo_path, &init_options).map_err(Error::RepositoryInit)?;
+        let helper = Self::from_repository(repository);
+
+        if let (Some(gitignore), Some(workdir)) = (gitignore, helper.repository().workdir()) {
+            fs::write(workdir.join(".gitignore"), gitignore).map_err(Error::WorkdirWrite)?;
+        }
+
+        if let Some(message) = &options.initial_commit {
+            helper.initial_commit(message, gitignore)?;
+        }
+
+        Ok(helper)
+    }
+
+    /// Create the root commit with the `.gitignore` if given and update the
+    /// index to it.
+    fn initial_commit(&self, message: &str, gitignore: Option<&str>) -> Result<(), Error> {
+        let repository = self.repository();
+
+        let mut builder = repository.treebuilder(None).map_err(Error::TreeBuilder)?;
+        if let Some(gitignore) = gitignore {
+            let blob = repository
+                .blob(gitignore.as_bytes())
+                .map_err(Error::RepositoryWriteBlob)?;
+            builder
+                .insert(".gitignore", blob, FileMode::Blob.into())
+                .map_err(Error::TreeBuilder)?;
+        }
+        let tree = builder
+            .write()
+            .and_then(|oid| repository.find_tree(oid))
+            .map_err(Error::TreeBuilder)?;
+
+        let (author, committer) = self.commit_signatures(&CommitOptions::default())?;
+        repository
+            .commit(Some("HEAD"), &author, &committer, message, &tree, &[])
+            .map_err(Error::RepositoryCommit)?;
+
+        if !repository.is_bare() {
+            let mut index = repository.index().map_err(Error::IndexOpen)?;
+            index.read_tree(&tree).map_err(Error::IndexAdd)?;
+            self.write_index(&mut index)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::tempdir;
+
+    use super::{
+        Gitignore,
+        InitOptions,
+    };
+    use crate::{
+        GitHelper,
+        LogFilter,
+    };
+
+    #[test]
+    fn init_with_initial_commit() {
+        let dir = tempdir().unwrap();
+        let options = InitOptions {
+            default_branch: Some("main".to_string()),
+            gitignore: Some(Gitignore::Template("rust".to_string())),
+            initial_commit: Some("Initial commit".to_string()),
+            ..InitOptions::default()
+        };
+        crate::init_with(&dir, &options).unwrap();
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let head = helper.repository().head().unwrap();
+        assert_eq!("refs/heads/main", head.name().unwrap());
+        assert_eq!(1, helper.commit_count(&LogFilter::default()).unwrap());
+        assert_eq!(
+            "/target\n",
+            fs::read_to_string(dir.path().join(".gitignore")).unwrap()
+        );
+        assert!(crate::status(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn init_with_empty_initial_commit() {
+        let dir = tempdir().unwrap();
+        let options = InitOptions {
+            bare: true,
+            initial_commit: Some("Initial commit".to_string()),
+            ..InitOptions::default()
+        };
+        let helper = GitHelper::init_with(&dir, &options).unwrap();
+
+        assert!(helper.repository().is_bare());
+        let commit = helper
+            .repository()
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(0, commit.tree().unwrap().len());
+
+        // branches can be created right away
+        helper
+            .repository()
+            .branch("feature", &commit, false)
+            .unwrap();
+    }
+
+    #[test]
+    fn init_with_unknown_template() {
+        let dir = tempdir().unwrap();
+        let options = InitOptions {
+            gitignore: Some(Gitignore::Template("cobol".to_string())),
+            ..InitOptions::default()
+        };
+
+        assert_eq!(
+            Err(crate::Error::UnknownGitignoreTemplate("cobol".to_string())),
+            crate::init_with(&dir, &options)
+        );
+        assert!(!dir.path().join(".git").exists());
+    }
+}
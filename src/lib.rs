@@ -1,128 +1,1107 @@
 //! Helper crate around git2 with functions for common tasks related to git
 //! repositories.
+//!
+//! Every free function opens the repository in the given path before doing
+//! its work. When calling several functions in sequence use [`GitHelper`]
+//! instead which opens the repository once and offers the same functions as
+//! methods. Code that can not keep a [`GitHelper`] around, e.g. because it
+//! polls many repositories, can let the free functions cache opened
+//! repositories with [`set_repo_cache_capacity`].
 
 #![deny(missing_docs)]
 #![warn(rust_2018_idioms)]
 
+pub mod abort;
+pub mod apply;
+pub mod bisect;
+pub mod branches;
+pub mod bulk;
+pub mod cache;
+pub mod changelog;
+pub mod checkout;
+pub mod config;
+pub mod conflicts;
+pub mod credentials;
+pub mod description;
+pub mod diff;
 pub mod error;
+pub mod fetch;
+pub mod history;
+pub mod hunks;
+pub mod identity;
+pub mod index_flags;
+pub mod init;
+pub mod line_endings;
+pub mod log;
+pub mod maintenance;
+pub mod network;
+pub mod ownership;
+pub mod pool;
+pub mod push;
+pub mod refs;
+pub mod remotes;
+pub mod render;
+pub mod repo;
+pub mod report;
+pub mod rewrite;
+pub mod sign;
+pub mod signatures;
+pub mod snapshot;
+pub mod stash;
+pub mod state;
+pub mod stats;
+pub mod status_delta;
+pub mod template;
+pub mod time;
+pub mod url_rewrite;
+pub mod verify;
 
-pub use crate::error::Error;
-
-use std::path::Path;
+#[cfg(test)]
+mod test_util;
 
-use git2::{
-    self,
-    Repository,
+pub use crate::{
+    apply::{
+        ApplyFailure,
+        DiffSpec,
+    },
+    bisect::{
+        BisectStep,
+        Verdict,
+    },
+    branches::{
+        BranchCleanupOptions,
+        BranchInfo,
+    },
+    cache::RepoCache,
+    changelog::{
+        ChangelogOptions,
+        ConventionalCommit,
+    },
+    checkout::{
+        CheckoutOptions,
+        ConflictStyle,
+    },
+    conflicts::{
+        Conflict,
+        ConflictSide,
+        Resolution,
+    },
+    credentials::CredentialSource,
+    diff::{
+        DiffConfig,
+        DiffEntry,
+    },
+    error::Error,
+    fetch::{
+        FetchOptions,
+        FetchReport,
+        RemoteFetchResult,
+    },
+    identity::Sig,
+    index_flags::SkippedEntry,
+    init::{
+        Gitignore,
+        InitOptions,
+    },
+    log::{
+        AuthorMatch,
+        LogEntry,
+        LogFilter,
+        LogOrder,
+        MessageMatch,
+    },
+    maintenance::MaintenanceReport,
+    network::{
+        CertificateCheck,
+        CertificateInfo,
+        NetworkOptions,
+    },
+    pool::{
+        PooledHelper,
+        RepoPool,
+    },
+    push::{
+        PushOptions,
+        PushPreview,
+        PushReport,
+        PushStatus,
+        RefPushPreview,
+        RefPushResult,
+    },
+    remotes::{
+        CloneOptions,
+        RemoteAction,
+    },
+    render::RenderOptions,
+    repo::{
+        CommitOptions,
+        CommitPreview,
+        FileState,
+        GitHelper,
+        IndexEntryInfo,
+        OpenOptions,
+        StatusEntry,
+        StatusOptions,
+    },
+    report::{
+        CommitReport,
+        RemoteReport,
+        RepoReport,
+    },
+    rewrite::SquashOptions,
+    signatures::{
+        ObjectSignature,
+        SignatureEntry,
+        SignatureKind,
+        SignedObject,
+    },
+    state::RepoState,
+    stats::{
+        BlobInfo,
+        RepoStats,
+    },
+    status_delta::{
+        StatusDelta,
+        StatusSnapshot,
+    },
+    time::{
+        parse_git_date,
+        Date,
+        Timestamp,
+    },
+    verify::{
+        VerifyOptions,
+        VerifyReport,
+    },
 };
-use walkdir::{
-    DirEntry,
-    WalkDir,
+
+use std::{
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 
+/// Trust the repository at the path regardless of its owner by adding it to
+/// `safe.directory` in the global configuration. Only call this when the
+/// user opted in.
+pub fn add_safe_directory<P: AsRef<Path>>(path: P) -> Result<(), Error> {
+    GitHelper::add_safe_directory(path)
+}
+
+/// Apply the diff to the index, the working directory or both. Nothing is
+/// changed if a single hunk does not apply. With `check_only` only checks
+/// whether the diff applies cleanly without changing anything.
+pub fn apply_diff<P: AsRef<Path>>(
+    repo_path: P,
+    source: &DiffSpec,
+    location: git2::ApplyLocation,
+    check_only: bool,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.apply_diff(source, location, check_only)
+}
+
+/// Return the time the commit the revspec resolves to was authored at.
+pub fn authored_at<P: AsRef<Path>>(repo_path: P, revspec: &str) -> Result<Timestamp, Error> {
+    cache::open(repo_path)?.authored_at(revspec)
+}
+
+/// Stop bisecting, check out what HEAD pointed to before the bisect started
+/// and remove the bisect state.
+pub fn bisect_reset<P: AsRef<Path>>(repo_path: P) -> Result<(), Error> {
+    cache::open(repo_path)?.bisect_reset()
+}
+
+/// Start bisecting between a bad and a good revision. The first candidate is
+/// checked out with a detached HEAD and returned, or the culprit if there is
+/// nothing left to test.
+pub fn bisect_start<P: AsRef<Path>>(
+    repo_path: P,
+    bad: &str,
+    good: &str,
+) -> Result<BisectStep, Error> {
+    cache::open(repo_path)?.bisect_start(bad, good)
+}
+
+/// Record the verdict for the checked out candidate and check out the next
+/// candidate. Returns the next candidate or the culprit once it is found.
+pub fn bisect_step<P: AsRef<Path>>(repo_path: P, verdict: Verdict) -> Result<BisectStep, Error> {
+    cache::open(repo_path)?.bisect_step(verdict)
+}
+
+/// True if the commit the revision resolves to is the tip of the local branch
+/// or one of its ancestors.
+pub fn branch_contains<P: AsRef<Path>>(
+    repo_path: P,
+    branch: &str,
+    revision: &str,
+) -> Result<bool, Error> {
+    cache::open(repo_path)?.branch_contains(branch, revision)
+}
+
+/// List the local branches sorted by name together with their upstream, how
+/// far they are ahead of and behind their upstream and their tip commit.
+pub fn branch_details<P: AsRef<Path>>(repo_path: P) -> Result<Vec<BranchInfo>, Error> {
+    cache::open(repo_path)?.branch_details()
+}
+
+/// List the names of the branches that contain the commit the revision
+/// resolves to.
+pub fn branches_containing<P: AsRef<Path>>(
+    repo_path: P,
+    revision: &str,
+    include_remote: bool,
+) -> Result<Vec<String>, Error> {
+    cache::open(repo_path)?.branches_containing(revision, include_remote)
+}
+
+/// Commit the given files with the given content on top of HEAD in a single
+/// commit written as one packfile instead of loose objects. The index is
+/// updated to the new commit, the working directory is not touched. Returns
+/// the id of the new commit.
+pub fn bulk_write<P, I>(repo_path: P, entries: I, message: &str) -> Result<git2::Oid, Error>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = (PathBuf, Vec<u8>)>,
+{
+    cache::open(repo_path)?.bulk_write(entries, message)
+}
+
+/// Generate a Markdown changelog of the commits between the tags grouped by
+/// their conventional commit type. Without `from_tag` the changelog starts at
+/// the previous version tag.
+pub fn changelog<P: AsRef<Path>>(
+    repo_path: P,
+    from_tag: Option<&str>,
+    to_tag: &str,
+    options: &ChangelogOptions,
+) -> Result<String, Error> {
+    cache::open(repo_path)?.changelog(from_tag, to_tag, options)
+}
+
+/// Check out the local branch with the given name and point HEAD to it.
+/// Returns the paths that were changed in the working directory or that would
+/// be changed when `dry_run` is set. Fails with Error::OperationInProgress
+/// during a merge, rebase or similar unless `force` is set.
+pub fn checkout_branch<P: AsRef<Path>>(
+    repo_path: P,
+    name: &str,
+    options: &CheckoutOptions,
+) -> Result<Vec<PathBuf>, Error> {
+    cache::open(repo_path)?.checkout_branch(name, options)
+}
+
+/// Check out the commit the given revision resolves to and detach HEAD at it.
+/// Returns the paths that were changed in the working directory or that would
+/// be changed when `dry_run` is set. Fails with Error::OperationInProgress
+/// during a merge, rebase or similar unless `force` is set.
+pub fn checkout_commit<P: AsRef<Path>>(
+    repo_path: P,
+    revspec: &str,
+    options: &CheckoutOptions,
+) -> Result<Vec<PathBuf>, Error> {
+    cache::open(repo_path)?.checkout_commit(revspec, options)
+}
+
+/// Point HEAD at a new branch without commits like `git checkout --orphan`, so
+/// the next commit is a root commit on that branch. The index is cleared. With
+/// `clear_worktree` the files tracked by the previous HEAD are removed from the
+/// working directory as well, otherwise they are left as untracked files.
+/// Nothing is changed if the name is invalid, the branch already exists or the
+/// working directory or the index differ from HEAD.
+pub fn checkout_orphan<P: AsRef<Path>>(
+    repo_path: P,
+    name: &str,
+    clear_worktree: bool,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.checkout_orphan(name, clear_worktree)
+}
+
+/// Create a local branch at the remote-tracking branch of the given remote,
+/// set the remote-tracking branch as its upstream and check it out.
+pub fn checkout_remote_branch<P: AsRef<Path>>(
+    repo_path: P,
+    remote: &str,
+    branch: &str,
+) -> Result<Vec<PathBuf>, Error> {
+    cache::open(repo_path)?.checkout_remote_branch(remote, branch)
+}
+
+/// Abort the cherry-pick in progress. HEAD, the index and the working
+/// directory are reset to the commit that was checked out before the
+/// cherry-pick and the cherry-pick state is removed.
+pub fn cherry_pick_abort<P: AsRef<Path>>(repo_path: P) -> Result<(), Error> {
+    cache::open(repo_path)?.cherry_pick_abort()
+}
+
+/// Clone the repository at the url into a bare mirror at the path like `git
+/// clone --mirror`.
+pub fn clone_mirror<P: AsRef<Path>>(
+    url: &str,
+    repo_path: P,
+    options: &CloneOptions,
+) -> Result<(), Error> {
+    let _ = GitHelper::clone_mirror(url, repo_path, options)?;
+
+    Ok(())
+}
+
+/// Clone the repository at the url into the path. If the path already is a
+/// repository whose `origin` points to the url nothing is done.
+pub fn clone_or_open<P: AsRef<Path>>(
+    url: &str,
+    repo_path: P,
+    options: &CloneOptions,
+) -> Result<(), Error> {
+    let _ = GitHelper::clone_or_open(url, repo_path, options)?;
+
+    Ok(())
+}
+
 /// Commit current stage with given commit message.
 pub fn commit<P: AsRef<Path>>(repo_path: P, message: &str) -> Result<(), Error> {
-    let repository = Repository::open(&repo_path).map_err(Error::RepositoryOpen)?;
-    let mut index = repository.index().map_err(Error::IndexOpen)?;
-    let oid = index.write_tree().map_err(Error::IndexWriteTree)?;
-
-    let mut parent_commit = vec![];
-    if let Ok(head) = repository.head() {
-        if let Ok(head_commit) = head.peel_to_commit() {
-            parent_commit.push(head_commit);
-        };
-    };
+    cache::open(repo_path)?.commit(message)
+}
+
+/// Count the commits reachable from HEAD per day they were authored on in the
+/// timezone of the author.
+pub fn commit_activity<P: AsRef<Path>>(repo_path: P) -> Result<HashMap<Date, usize>, Error> {
+    cache::open(repo_path)?.commit_activity()
+}
 
-    let tree = repository
-        .find_tree(oid)
-        .map_err(Error::RepositoryFindTree)?;
+/// Count the commits that match the filter.
+pub fn commit_count<P: AsRef<Path>>(repo_path: P, filter: &LogFilter) -> Result<usize, Error> {
+    cache::open(repo_path)?.commit_count(filter)
+}
 
-    let signature = repository.signature().map_err(Error::RepositorySignature)?;
+/// Return what committing the current stage with given commit message and
+/// options would create without writing the commit or moving HEAD.
+pub fn commit_dry_run<P: AsRef<Path>>(
+    repo_path: P,
+    message: &str,
+    options: &CommitOptions,
+) -> Result<CommitPreview, Error> {
+    cache::open(repo_path)?.commit_dry_run(message, options)
+}
 
-    repository
-        .commit(
-            Some("HEAD"),
-            &signature,
-            &signature,
-            message,
-            &tree,
-            &parent_commit.iter().collect::<Vec<_>>(),
-        )
-        .map_err(Error::RepositoryCommit)?;
+/// Create a root commit with an empty tree and the given commit message.
+/// Fails with Error::NotEmptyHistory if HEAD already points to a commit.
+pub fn commit_empty<P: AsRef<Path>>(repo_path: P, message: &str) -> Result<(), Error> {
+    cache::open(repo_path)?.commit_empty(message)
+}
 
-    Ok(())
+/// Return the complete message of the commit the revspec resolves to
+/// including the body and trailers.
+pub fn commit_message<P: AsRef<Path>>(repo_path: P, revspec: &str) -> Result<String, Error> {
+    cache::open(repo_path)?.commit_message(revspec)
+}
+
+/// Return the ids of the parents of the commit the revspec resolves to as hex
+/// strings. A root commit has no parents.
+pub fn commit_parents<P: AsRef<Path>>(repo_path: P, revspec: &str) -> Result<Vec<String>, Error> {
+    cache::open(repo_path)?.commit_parents(revspec)
+}
+
+/// Commit current stage with given commit message and sign the commit with an
+/// SSH key. The `sign` callback receives the raw commit buffer and has to
+/// return the armored signature which is then stored in the `gpgsig` header
+/// of the commit.
+pub fn commit_ssh_signed<P, S, E>(repo_path: P, message: &str, sign: S) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    S: FnOnce(&[u8]) -> Result<String, E>,
+    E: std::fmt::Display,
+{
+    cache::open(repo_path)?.commit_ssh_signed(message, sign)
+}
+
+/// Commit current stage with a message rendered from the template. See
+/// GitHelper::commit_templated for the supported placeholders. Fails with
+/// Error::UnknownPlaceholder for placeholders without a value.
+pub fn commit_templated<P: AsRef<Path>>(
+    repo_path: P,
+    template: &str,
+    vars: &HashMap<&str, &str>,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.commit_templated(template, vars)
+}
+
+/// Create a commit with the given tree on the given branch without touching
+/// the index or HEAD. Works in bare repositories.
+pub fn commit_to_bare<P: AsRef<Path>>(
+    repo_path: P,
+    branch: &str,
+    tree_oid: git2::Oid,
+    message: &str,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.commit_to_bare(branch, tree_oid, message)
+}
+
+/// Commit current stage with given commit message and options.
+pub fn commit_with<P: AsRef<Path>>(
+    repo_path: P,
+    message: &str,
+    options: &CommitOptions,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.commit_with(message, options)
+}
+
+/// Commit current stage with given commit message and the signature as author
+/// and committer. The configuration and the environment are not read so a
+/// signature can be created once and reused for many commits.
+pub fn commit_with_signature<P: AsRef<Path>>(
+    repo_path: P,
+    message: &str,
+    signature: &git2::Signature<'_>,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.commit_with_signature(message, signature)
+}
+
+/// List the commits reachable from HEAD whose author email is the given email
+/// ignoring case, newest first.
+pub fn commits_by_author<P: AsRef<Path>>(
+    repo_path: P,
+    email: &str,
+) -> Result<Vec<LogEntry>, Error> {
+    cache::open(repo_path)?.commits_by_author(email)
+}
+
+/// Return the time the commit the revspec resolves to was committed at.
+pub fn committed_at<P: AsRef<Path>>(repo_path: P, revspec: &str) -> Result<Timestamp, Error> {
+    cache::open(repo_path)?.committed_at(revspec)
+}
+
+/// Read a string value from the configuration of the repository. Returns None
+/// if the key is not set.
+pub fn config_get<P: AsRef<Path>>(repo_path: P, key: &str) -> Result<Option<String>, Error> {
+    cache::open(repo_path)?.config_get(key)
+}
+
+/// Read a boolean value from the configuration of the repository. Returns
+/// None if the key is not set and Error::Config if the value is not a boolean.
+pub fn config_get_bool<P: AsRef<Path>>(repo_path: P, key: &str) -> Result<Option<bool>, Error> {
+    cache::open(repo_path)?.config_get_bool(key)
+}
+
+/// Read an integer value from the configuration of the repository. Returns
+/// None if the key is not set and Error::Config if the value is not an
+/// integer.
+pub fn config_get_int<P: AsRef<Path>>(repo_path: P, key: &str) -> Result<Option<i64>, Error> {
+    cache::open(repo_path)?.config_get_int(key)
+}
+
+/// Set a string value in the local configuration of the repository.
+pub fn config_set<P: AsRef<Path>>(repo_path: P, key: &str, value: &str) -> Result<(), Error> {
+    cache::open(repo_path)?.config_set(key, value)
+}
+
+/// Return the conflicts in the index sorted by path. A repository without
+/// conflicts returns an empty list.
+pub fn conflicts<P: AsRef<Path>>(repo_path: P) -> Result<Vec<Conflict>, Error> {
+    cache::open(repo_path)?.conflicts()
+}
+
+/// Delete the local branches that are merged except for the protected ones.
+/// Returns the names of the deleted branches.
+pub fn delete_merged_branches<P: AsRef<Path>>(
+    repo_path: P,
+    options: &BranchCleanupOptions,
+) -> Result<Vec<String>, Error> {
+    cache::open(repo_path)?.delete_merged_branches(options)
+}
+
+/// List the files that changed between the commits the revspecs resolve to.
+pub fn diff_commits<P: AsRef<Path>>(
+    repo_path: P,
+    old: &str,
+    new: &str,
+    config: &DiffConfig,
+) -> Result<Vec<DiffEntry>, Error> {
+    cache::open(repo_path)?.diff_commits(old, new, config)
+}
+
+/// List the files that are staged, i.e. that changed between HEAD and the
+/// index. Without a HEAD commit every file in the index is added.
+pub fn diff_staged<P: AsRef<Path>>(
+    repo_path: P,
+    config: &DiffConfig,
+) -> Result<Vec<DiffEntry>, Error> {
+    cache::open(repo_path)?.diff_staged(config)
+}
+
+/// Render the diff between the commits the revspecs resolve to as text.
+pub fn diff_text<P: AsRef<Path>>(
+    repo_path: P,
+    old: &str,
+    new: &str,
+    config: &DiffConfig,
+    options: &RenderOptions,
+) -> Result<String, Error> {
+    cache::open(repo_path)?.diff_text(old, new, config, options)
+}
+
+/// Return the author new commits will be created with.
+pub fn effective_signature<P: AsRef<Path>>(repo_path: P) -> Result<Sig, Error> {
+    cache::open(repo_path)?.effective_signature()
+}
+
+/// Add the named remote with the url or change its url if it points somewhere
+/// else. Returns what was done.
+pub fn ensure_remote<P: AsRef<Path>>(
+    repo_path: P,
+    name: &str,
+    url: &str,
+) -> Result<RemoteAction, Error> {
+    cache::open(repo_path)?.ensure_remote(name, url)
+}
+
+/// Write every file of the commit the revspec resolves to into the
+/// destination directory without touching the working directory, the index
+/// or HEAD.
+pub fn export_tree<P: AsRef<Path>, Q: AsRef<Path>>(
+    repo_path: P,
+    revspec: &str,
+    dest: Q,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.export_tree(revspec, dest)
+}
+
+/// Fetch references and objects from a remote.
+pub fn fetch<P: AsRef<Path>>(repo_path: P, options: &FetchOptions) -> Result<FetchReport, Error> {
+    cache::open(repo_path)?.fetch(options)
+}
+
+/// Fetch from every configured remote and return the result of every
+/// remote. A failing remote does not stop the others.
+pub fn fetch_all<P: AsRef<Path>>(
+    repo_path: P,
+    options: &FetchOptions,
+) -> Result<Vec<RemoteFetchResult>, Error> {
+    cache::open(repo_path)?.fetch_all(options)
+}
+
+/// Fetch the history of the remote up to the depth counted from the tips of
+/// the fetched references, e.g. to get more of the history of a shallow
+/// clone.
+pub fn fetch_deepen<P: AsRef<Path>>(
+    repo_path: P,
+    remote: &str,
+    depth: i32,
+) -> Result<FetchReport, Error> {
+    cache::open(repo_path)?.fetch_deepen(remote, depth)
+}
+
+/// List the commits reachable from HEAD that changed the file, newest first.
+pub fn file_history<P: AsRef<Path>, F: AsRef<Path>>(
+    repo_path: P,
+    file: F,
+    limit: Option<usize>,
+) -> Result<Vec<LogEntry>, Error> {
+    cache::open(repo_path)?.file_history(file, limit)
+}
+
+/// Return the state of a single file given relative to the repository root.
+pub fn file_status<P: AsRef<Path>, F: AsRef<Path>>(
+    repo_path: P,
+    file: F,
+) -> Result<FileState, Error> {
+    cache::open(repo_path)?.file_status(file)
+}
+
+/// Return the description of the repository from the `description` file in
+/// the git directory. Returns None if there is no description.
+pub fn get_description<P: AsRef<Path>>(repo_path: P) -> Result<Option<String>, Error> {
+    cache::open(repo_path)?.get_description()
+}
+
+/// Return true if the index contains conflicts.
+pub fn has_conflicts<P: AsRef<Path>>(repo_path: P) -> Result<bool, Error> {
+    cache::open(repo_path)?.has_conflicts()
+}
+
+/// Return the absolute path of the directory git runs hooks from.
+pub fn hooks_path<P: AsRef<Path>>(repo_path: P) -> Result<PathBuf, Error> {
+    cache::open(repo_path)?.hooks_path()
+}
+
+/// Return the entries of the index sorted by path.
+pub fn index_entries<P: AsRef<Path>>(repo_path: P) -> Result<Vec<IndexEntryInfo>, Error> {
+    cache::open(repo_path)?.index_entries()
+}
+
+/// Return the file mode of the staged entry with the path, e.g. `0o100755`
+/// for executable files. Fails with Error::IndexEntryNotFound if the path is
+/// not in the index.
+pub fn index_file_mode<P: AsRef<Path>, F: AsRef<Path>>(
+    repo_path: P,
+    path: F,
+) -> Result<u32, Error> {
+    cache::open(repo_path)?.index_file_mode(path)
 }
 
 /// Create a new nonbare git repository in the given path.
 pub fn init<P: AsRef<Path>>(repo_path: P) -> Result<(), Error> {
-    let _ = Repository::init(&repo_path).map_err(Error::RepositoryInit)?;
+    let _ = GitHelper::init(repo_path)?;
+
+    Ok(())
+}
+
+/// Create a new git repository in the given path as configured by the options.
+pub fn init_with<P: AsRef<Path>>(repo_path: P, options: &InitOptions) -> Result<(), Error> {
+    let _ = GitHelper::init_with(repo_path, options)?;
 
     Ok(())
 }
 
+/// True if the commit `ancestor` resolves to is reachable from the commit
+/// `descendant` resolves to.
+pub fn is_ancestor<P: AsRef<Path>>(
+    repo_path: P,
+    ancestor: &str,
+    descendant: &str,
+) -> Result<bool, Error> {
+    cache::open(repo_path)?.is_ancestor(ancestor, descendant)
+}
+
+/// Return the most recent commit reachable from HEAD that changed the file
+/// compared to its first parent. Fails with Error::FileNotFound if no commit
+/// changed the file.
+pub fn last_commit_for_file<P: AsRef<Path>, F: AsRef<Path>>(
+    repo_path: P,
+    file: F,
+) -> Result<LogEntry, Error> {
+    cache::open(repo_path)?.last_commit_for_file(file)
+}
+
+/// Return the tag with the highest semantic version whose commit is reachable
+/// from the revision. Returns None if there is no such tag.
+pub fn latest_version_tag<P: AsRef<Path>>(
+    repo_path: P,
+    revision: &str,
+) -> Result<Option<String>, Error> {
+    cache::open(repo_path)?.latest_version_tag(revision)
+}
+
+/// List all references sorted by their full name together with the oid they
+/// point to. Symbolic references are resolved to the oid of their final
+/// target, annotated tags point to the tag object.
+pub fn list_references<P: AsRef<Path>>(repo_path: P) -> Result<Vec<(String, git2::Oid)>, Error> {
+    cache::open(repo_path)?.list_references()
+}
+
+/// List the signatures of the commits in the range and of the annotated tags
+/// pointing to one of these commits. The signatures are not verified.
+pub fn list_signatures<P: AsRef<Path>>(
+    repo_path: P,
+    range: &str,
+) -> Result<Vec<SignatureEntry>, Error> {
+    cache::open(repo_path)?.list_signatures(range)
+}
+
+/// List all index entries that have the skip-worktree or the
+/// assume-unchanged flag set.
+pub fn list_skipped<P: AsRef<Path>>(repo_path: P) -> Result<Vec<SkippedEntry>, Error> {
+    cache::open(repo_path)?.list_skipped()
+}
+
+/// List the stash entries with their index and message, newest first like `git
+/// stash list`.
+pub fn list_stashes<P: AsRef<Path>>(repo_path: P) -> Result<Vec<(usize, String)>, Error> {
+    cache::open(repo_path)?.list_stashes()
+}
+
+/// List the commits that match the filter, newest first.
+pub fn log_filtered<P: AsRef<Path>>(
+    repo_path: P,
+    filter: &LogFilter,
+) -> Result<Vec<LogEntry>, Error> {
+    cache::open(repo_path)?.log_filtered(filter)
+}
+
+/// List the commits reachable from HEAD by following only the first parent of
+/// merge commits, newest first, like `git log --first-parent`.
+pub fn log_first_parent<P: AsRef<Path>>(
+    repo_path: P,
+    limit: Option<usize>,
+) -> Result<Vec<LogEntry>, Error> {
+    cache::open(repo_path)?.log_first_parent(limit)
+}
+
+/// Do housekeeping in the git directory. Loose references are always moved
+/// into the packed-refs file. With the `repack` feature enabled all loose
+/// objects are additionally written into a new packfile and removed
+/// afterwards.
+pub fn maintenance<P: AsRef<Path>>(repo_path: P) -> Result<MaintenanceReport, Error> {
+    cache::open(repo_path)?.maintenance()
+}
+
+/// Abort the merge in progress. HEAD, the index and the working directory are
+/// reset to the commit that was checked out before the merge and the merge
+/// state is removed.
+pub fn merge_abort<P: AsRef<Path>>(repo_path: P) -> Result<(), Error> {
+    cache::open(repo_path)?.merge_abort()
+}
+
+/// List the local branches that are merged into the given branch or into HEAD
+/// if no branch is given.
+pub fn merged_branches<P: AsRef<Path>>(
+    repo_path: P,
+    into: Option<&str>,
+) -> Result<Vec<String>, Error> {
+    cache::open(repo_path)?.merged_branches(into)
+}
+
+/// Return the sorted paths of the tracked files that were changed in the
+/// working directory but not staged.
+pub fn modified_files<P: AsRef<Path>>(repo_path: P) -> Result<Vec<PathBuf>, Error> {
+    cache::open(repo_path)?.modified_files()
+}
+
+/// Count the objects stored in the object database of the repository. This
+/// includes loose objects as well as objects stored in packfiles.
+pub fn object_count<P: AsRef<Path>>(repo_path: P) -> Result<usize, Error> {
+    cache::open(repo_path)?.object_count()
+}
+
+/// Open the repository in the given path or create a new nonbare repository
+/// there if the path is not a repository.
+pub fn open_or_init<P: AsRef<Path>>(repo_path: P) -> Result<(), Error> {
+    let _ = GitHelper::open_or_init(repo_path)?;
+
+    Ok(())
+}
+
+/// Push references to a remote and report the outcome for every reference.
+/// Rejected references do not fail the push unless `fail_on_reject` is set.
+pub fn push<P: AsRef<Path>>(repo_path: P, options: &PushOptions) -> Result<PushReport, Error> {
+    cache::open(repo_path)?.push(options)
+}
+
+/// Return what pushing would do without pushing anything.
+pub fn push_dry_run<P: AsRef<Path>>(
+    repo_path: P,
+    options: &PushOptions,
+) -> Result<PushPreview, Error> {
+    cache::open(repo_path)?.push_dry_run(options)
+}
+
+/// Push every local reference to the same name on the remote and delete the
+/// references of the remote that do not exist locally.
+pub fn push_mirror<P: AsRef<Path>>(
+    repo_path: P,
+    options: &PushOptions,
+) -> Result<PushReport, Error> {
+    cache::open(repo_path)?.push_mirror(options)
+}
+
+/// Push the refspecs to the remote unless the client can already tell that one
+/// of the references would be rejected. Fails with Error::PushRejected if any
+/// reference is rejected. This is a client-side pre-check only: references the
+/// remote rejects do not prevent the others from being updated.
+pub fn push_prechecked<P: AsRef<Path>>(
+    repo_path: P,
+    remote: &str,
+    refspecs: &[&str],
+) -> Result<PushReport, Error> {
+    cache::open(repo_path)?.push_prechecked(remote, refspecs)
+}
+
 /// Push commits to upstream. By default this will try to push to origin. If
 /// origin does not exist this function will fail with
 /// Error::NoOriginConfigured.
 pub fn push_to_origin<P: AsRef<Path>>(repo_path: P) -> Result<(), Error> {
-    let repository = Repository::open(&repo_path).map_err(Error::RepositoryOpen)?;
-    let mut remote = repository
-        .find_remote("origin")
-        .map_err(|_| Error::NoOriginConfigured)?;
+    cache::open(repo_path)?.push_to_origin()
+}
 
-    remote
-        .connect(git2::Direction::Push)
-        .map_err(Error::RemoteConnect)?;
+/// Read the raw object with the given id from the object database. Returns the
+/// type of the object and its content without the header.
+pub fn read_object<P: AsRef<Path>>(
+    repo_path: P,
+    oid: &str,
+) -> Result<(git2::ObjectType, Vec<u8>), Error> {
+    cache::open(repo_path)?.read_object(oid)
+}
 
-    remote
-        .push(&["refs/heads/master:refs/heads/master"], None)
-        .map_err(Error::RemotePush)?;
+/// Replay the commits of the current branch that are not reachable from
+/// `upstream` on top of `onto`. Conflicts abort the rebase and fail with
+/// Error::Conflicts.
+pub fn rebase<P: AsRef<Path>>(repo_path: P, upstream: &str, onto: &str) -> Result<(), Error> {
+    cache::open(repo_path)?.rebase(upstream, onto)
+}
 
-    Ok(())
+/// Abort the rebase in progress. The rebased branch is checked out again at
+/// the commit it pointed to before the rebase and the rebase state is removed.
+pub fn rebase_abort<P: AsRef<Path>>(repo_path: P) -> Result<(), Error> {
+    cache::open(repo_path)?.rebase_abort()
+}
+
+/// Connect to the named remote and return the branch its HEAD points to, e.g.
+/// `main`. Fails with Error::NoRemoteHead if the remote does not advertise a
+/// HEAD.
+pub fn remote_default_branch<P: AsRef<Path>>(repo_path: P, remote: &str) -> Result<String, Error> {
+    cache::open(repo_path)?.remote_default_branch(remote)
+}
+
+/// Return the fetch url of the named remote.
+pub fn remote_url<P: AsRef<Path>>(repo_path: P, name: &str) -> Result<String, Error> {
+    cache::open(repo_path)?.remote_url(name)
+}
+
+/// Return the operation that is in progress in the repository.
+pub fn repo_state<P: AsRef<Path>>(repo_path: P) -> Result<RepoState, Error> {
+    Ok(cache::open(repo_path)?.repo_state())
+}
+
+/// Gather size statistics of the repository. Object and pack counts are read
+/// from the filesystem under the git directory. The largest blob is searched
+/// in all commits reachable from HEAD. The repository is not modified.
+pub fn repo_stats<P: AsRef<Path>>(repo_path: P) -> Result<RepoStats, Error> {
+    cache::open(repo_path)?.repo_stats()
+}
+
+/// Gather a summary of the repository: current branch, HEAD, dirty state,
+/// remotes, ahead/behind counts, the last commits, tags and stash count.
+pub fn report<P: AsRef<Path>>(repo_path: P) -> Result<RepoReport, Error> {
+    cache::open(repo_path)?.report()
+}
+
+/// Return the state of the repository, e.g. whether a merge, rebase or
+/// cherry-pick is in progress.
+pub fn repository_state<P: AsRef<Path>>(repo_path: P) -> Result<git2::RepositoryState, Error> {
+    Ok(cache::open(repo_path)?.repository_state())
+}
+
+/// Resolve the conflict on the given path. The resolved content is written to
+/// the working directory and staged and the conflict entries are removed from
+/// the index. Resolving to a side that does not have the path removes the
+/// file.
+pub fn resolve_conflict<P: AsRef<Path>, F: AsRef<Path>>(
+    repo_path: P,
+    path: F,
+    resolution: Resolution,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.resolve_conflict(path, resolution)
+}
+
+/// Return the url git would connect to for the given url after applying the
+/// `insteadOf` rules of the repository, global and system configuration. For
+/// pushes `pushInsteadOf` rules take precedence.
+pub fn resolve_url<P: AsRef<Path>>(
+    repo_path: P,
+    url: &str,
+    direction: git2::Direction,
+) -> Result<String, Error> {
+    cache::open(repo_path)?.resolve_url(url, direction)
+}
+
+/// Same as resolve_url but only applies the rules of the global and system
+/// configuration. Useful before a repository exists, e.g. for clones.
+pub fn resolve_url_global(url: &str, direction: git2::Direction) -> Result<String, Error> {
+    let config = git2::Config::open_default()
+        .and_then(|mut config| config.snapshot())
+        .map_err(Error::Config)?;
+
+    url_rewrite::rewrite_url(&config, url, direction)
+}
+
+/// Restore files in the working directory from the index like `git restore`.
+/// Local modifications of the restored paths are always overwritten. Returns
+/// the paths that were restored or that would be restored when `dry_run` is
+/// set.
+pub fn restore<P: AsRef<Path>>(
+    repo_path: P,
+    options: &CheckoutOptions,
+) -> Result<Vec<PathBuf>, Error> {
+    cache::open(repo_path)?.restore(options)
+}
+
+/// Set or clear the assume-unchanged flag of the index entry for the given
+/// path.
+pub fn set_assume_unchanged<P: AsRef<Path>, F: AsRef<Path>>(
+    repo_path: P,
+    path: F,
+    on: bool,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.set_assume_unchanged(path, on)
+}
+
+/// Write the description of the repository into the `description` file in
+/// the git directory.
+pub fn set_description<P: AsRef<Path>>(repo_path: P, text: &str) -> Result<(), Error> {
+    cache::open(repo_path)?.set_description(text)
+}
+
+/// Set `core.hooksPath` in the local configuration of the repository.
+pub fn set_hooks_path<P: AsRef<Path>>(repo_path: P, path: &Path) -> Result<(), Error> {
+    cache::open(repo_path)?.set_hooks_path(path)
+}
+
+/// Set how many opened repositories the free functions keep in the cache of
+/// the current thread. The cache is disabled by default and a capacity of 0
+/// disables it again. See [`RepoCache`] for when cached repositories are
+/// opened again.
+pub fn set_repo_cache_capacity(capacity: usize) {
+    cache::set_capacity(capacity)
+}
+
+/// Set or clear the skip-worktree flag of the index entry for the given path.
+/// Entries with the flag set are not reported by status and not staged by
+/// stage_all even when the file was modified in the working tree.
+pub fn set_skip_worktree<P: AsRef<Path>, F: AsRef<Path>>(
+    repo_path: P,
+    path: F,
+    on: bool,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.set_skip_worktree(path, on)
+}
+
+/// Replace the commits between the revision and HEAD by a single commit on top
+/// of the revision with the tree of HEAD and the given message. The current
+/// branch is moved to the new commit. Returns the id of the new commit.
+pub fn squash<P: AsRef<Path>>(repo_path: P, onto: &str, message: &str) -> Result<git2::Oid, Error> {
+    cache::open(repo_path)?.squash(onto, message)
+}
+
+/// Replace the last `count` commits by a single commit with the tree of HEAD.
+/// Without message the messages of the squashed commits are joined. Refuses
+/// to squash merges, the root commit and commits on the upstream.
+pub fn squash_last<P: AsRef<Path>>(
+    repo_path: P,
+    count: usize,
+    message: Option<&str>,
+) -> Result<git2::Oid, Error> {
+    cache::open(repo_path)?.squash_last(count, message)
+}
+
+/// Replace the last `count` commits by a single commit with the tree of HEAD
+/// with the given options.
+pub fn squash_last_with<P: AsRef<Path>>(
+    repo_path: P,
+    count: usize,
+    options: &SquashOptions,
+) -> Result<git2::Oid, Error> {
+    cache::open(repo_path)?.squash_last_with(count, options)
 }
 
 /// Stage given paths in the repository. Paths have to be relative to the
 /// repo_path.
 pub fn stage<P: AsRef<Path>, F: AsRef<Path>>(repo_path: P, paths: &[F]) -> Result<(), Error> {
-    let repository = Repository::open(&repo_path).map_err(Error::RepositoryOpen)?;
-    let mut index = repository.index().map_err(Error::IndexOpen)?;
+    cache::open(repo_path)?.stage(paths)
+}
 
-    paths
-        .iter()
-        .filter(|path| {
-            let fullpath = repo_path.as_ref().join(path);
-            fullpath.exists() && fullpath.is_file()
-        })
-        .filter(|entry| entry.as_ref() == std::path::PathBuf::from(".git"))
-        .map(|path| index.add_path(path.as_ref()).map_err(Error::IndexAddPath))
-        .collect::<Result<_, Error>>()?;
+/// Stage all paths in the repository. Paths that are flagged as skip-worktree
+/// in the index will not be staged. Returns the staged paths sorted.
+pub fn stage_all<P: AsRef<Path>>(repo_path: P) -> Result<Vec<PathBuf>, Error> {
+    cache::open(repo_path)?.stage_all()
+}
 
-    index.write().map_err(Error::IndexWrite)?;
+/// Stage the given content at the given path without reading from or writing
+/// to the working directory. The path has to be relative to the root of the
+/// repository. With `normalize_line_endings` CRLF line endings are converted
+/// like `stage` does for files in the working directory.
+pub fn stage_blob<P: AsRef<Path>, F: AsRef<Path>>(
+    repo_path: P,
+    path: F,
+    content: &[u8],
+    normalize_line_endings: bool,
+) -> Result<(), Error> {
+    cache::open(repo_path)?.stage_blob(path, content, normalize_line_endings)
+}
 
-    Ok(())
+/// Stage only the given hunks of the changes between the index and the
+/// working directory of a file. Hunks are counted from zero in the order `git
+/// diff` shows them.
+pub fn stage_hunks<P: AsRef<Path>, F: AsRef<Path>>(
+    repo_path: P,
+    file: F,
+    hunk_indices: &[usize],
+) -> Result<(), Error> {
+    cache::open(repo_path)?.stage_hunks(file, hunk_indices)
+}
+
+/// Return the sorted paths of the files whose staged content differs from
+/// HEAD.
+pub fn staged_files<P: AsRef<Path>>(repo_path: P) -> Result<Vec<PathBuf>, Error> {
+    cache::open(repo_path)?.staged_files()
 }
 
-/// Stage all paths in the repository.
-pub fn stage_all<P: AsRef<Path>>(repo_path: P) -> Result<(), Error> {
-    let paths = WalkDir::new(&repo_path)
-        .into_iter()
-        .filter_map(|v| v.ok())
-        .filter(|entry| entry.path() != repo_path.as_ref())
-        .map(|entry| {
-            entry
-                .into_path()
-                .strip_prefix(&repo_path)
-                .map(|path| path.to_path_buf())
-                .map_err(Error::StripRepositoryPrefix)
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+/// Apply the stash entry with the given index and keep it in the stash.
+pub fn stash_apply<P: AsRef<Path>>(repo_path: P, index: usize) -> Result<(), Error> {
+    cache::open(repo_path)?.stash_apply(index)
+}
 
-    stage(repo_path, &paths)?;
+/// Remove the stash entry with the given index from the stash without applying
+/// it.
+pub fn stash_drop<P: AsRef<Path>>(repo_path: P, index: usize) -> Result<(), Error> {
+    cache::open(repo_path)?.stash_drop(index)
+}
 
-    Ok(())
+/// Return status of the repository. Untracked files are included, ignored
+/// files and files flagged as skip-worktree or assume-unchanged are not. A
+/// clean repository returns an empty list.
+pub fn status<P: AsRef<Path>>(repo_path: P) -> Result<Vec<StatusEntry>, Error> {
+    cache::open(repo_path)?.status()
+}
+
+/// Return the status of the repository as snapshot that can be compared with
+/// later snapshots.
+pub fn status_snapshot<P: AsRef<Path>>(repo_path: P) -> Result<StatusSnapshot, Error> {
+    cache::open(repo_path)?.status_snapshot()
+}
+
+/// Return status of the repository with the given options.
+pub fn status_with<P: AsRef<Path>>(
+    repo_path: P,
+    options: &StatusOptions,
+) -> Result<Vec<StatusEntry>, Error> {
+    cache::open(repo_path)?.status_with(options)
+}
+
+/// Return status of the repository with renamed files reported once with
+/// their old path.
+pub fn status_with_renames<P: AsRef<Path>>(repo_path: P) -> Result<Vec<StatusEntry>, Error> {
+    cache::open(repo_path)?.status_with_renames()
+}
+
+/// Return the id of the root tree of the commit the revspec resolves to.
+/// Commits with the same content have the same tree id regardless of their
+/// message, parents or signatures.
+pub fn tree_oid<P: AsRef<Path>>(repo_path: P, revspec: &str) -> Result<git2::Oid, Error> {
+    cache::open(repo_path)?.tree_oid(revspec)
+}
+
+/// Return the sorted paths of the untracked files in the working directory.
+pub fn untracked_files<P: AsRef<Path>>(repo_path: P) -> Result<Vec<PathBuf>, Error> {
+    cache::open(repo_path)?.untracked_files()
+}
+
+/// Check that every object reachable from the references of the repository
+/// can be read from the object database and that its content hashes to its
+/// id. Missing and corrupt objects are collected in the returned report.
+pub fn verify<P: AsRef<Path>>(
+    repo_path: P,
+    options: &VerifyOptions,
+) -> Result<VerifyReport, Error> {
+    cache::open(repo_path)?.verify(options)
+}
+
+/// Pass the signature and the signed content of the commit the revspec
+/// resolves to to the `verify` callback and return its result. Fails with
+/// Error::NotSigned if the commit has no signature.
+pub fn verify_signature<P, V>(repo_path: P, revspec: &str, verify: V) -> Result<bool, Error>
+where
+    P: AsRef<Path>,
+    V: Fn(&[u8], &[u8]) -> bool,
+{
+    cache::open(repo_path)?.verify_signature(revspec, verify)
 }
 
-/// Return status of the repository.
-pub fn status<P: AsRef<Path>>(_repo_path: P) -> Result<(), Error> {
-    unimplemented!()
+/// Write the content as a blob to the object database without touching the
+/// index or the working directory. Returns the id of the blob which is the
+/// sha1 of the content prefixed with the `blob <size>\0` header.
+pub fn write_blob<P: AsRef<Path>>(repo_path: P, content: &[u8]) -> Result<git2::Oid, Error> {
+    cache::open(repo_path)?.write_blob(content)
 }
 
 #[cfg(test)]
@@ -267,10 +1246,148 @@ mod test {
         super::commit(&dir, "added all files").unwrap();
     }
 
+    #[test]
+    fn object_count_empty_repo() {
+        let dir = tempdir().unwrap();
+        super::init(&dir).unwrap();
+
+        assert_eq!(0, super::object_count(&dir).unwrap());
+    }
+
+    #[test]
+    fn object_count_after_commits() {
+        let dir = tempdir().unwrap();
+        super::init(&dir).unwrap();
+
+        let files = vec!["first_file", "second_file", "third_file"];
+        for file in &files {
+            let path = dir.path().join(file);
+            fs::write(&path, file).unwrap();
+        }
+
+        super::stage(&dir, &files).unwrap();
+        super::commit(&dir, "Added files").unwrap();
+
+        // three blobs, one tree and one commit
+        assert!(super::object_count(&dir).unwrap() >= 5);
+    }
+
+    #[test]
+    fn repo_stats_after_commit() {
+        let dir = tempdir().unwrap();
+        super::init(&dir).unwrap();
+
+        let before = super::repo_stats(&dir).unwrap();
+        assert_eq!(0, before.loose_objects);
+        assert_eq!(0, before.packfiles);
+        assert!(!before.bare);
+        assert!(!before.shallow);
+        assert_eq!(None, before.largest_blob);
+
+        fs::write(dir.path().join("small_file"), "small").unwrap();
+        fs::create_dir_all(dir.path().join("subdir")).unwrap();
+        fs::write(dir.path().join("subdir/large_file"), "a much larger file").unwrap();
+
+        super::stage_all(&dir).unwrap();
+        super::commit(&dir, "Added files").unwrap();
+
+        let after = super::repo_stats(&dir).unwrap();
+        assert!(after.loose_objects > before.loose_objects);
+        assert_eq!(1, after.references);
+
+        let largest = after.largest_blob.unwrap();
+        assert_eq!(PathBuf::from("subdir/large_file"), largest.path);
+        assert_eq!("a much larger file".len(), largest.size);
+    }
+
     #[test]
     #[should_panic]
     fn status_not_a_repository() {
         let dir = tempdir().unwrap();
         super::status(&dir).unwrap();
     }
+
+    #[test]
+    fn status_untracked_file() {
+        let dir = tempdir().unwrap();
+        super::init(&dir).unwrap();
+        assert!(super::status(&dir).unwrap().is_empty());
+
+        let first_file = dir.path().join("first_file");
+        fs::write(&first_file, "first data").unwrap();
+
+        let status = super::status(&dir).unwrap();
+        assert_eq!(1, status.len());
+        assert_eq!(PathBuf::from("first_file"), status[0].path);
+        assert_eq!(git2::Status::WT_NEW, status[0].status);
+    }
+
+    #[test]
+    fn skip_worktree_modified_file() {
+        let dir = tempdir().unwrap();
+        super::init(&dir).unwrap();
+
+        let config_file = dir.path().join("config");
+        fs::write(&config_file, "original").unwrap();
+
+        super::stage_all(&dir).unwrap();
+        super::commit(&dir, "Added config").unwrap();
+
+        super::set_skip_worktree(&dir, "config", true).unwrap();
+        fs::write(&config_file, "local modification").unwrap();
+
+        assert!(super::status(&dir).unwrap().is_empty());
+
+        assert!(super::stage_all(&dir).unwrap().is_empty());
+        let original = git2::Oid::hash_object(git2::ObjectType::Blob, b"original").unwrap();
+        let entries = super::index_entries(&dir).unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(original, entries[0].oid);
+        assert!(super::diff_staged(&dir, &Default::default())
+            .unwrap()
+            .is_empty());
+
+        let expected = vec![super::SkippedEntry {
+            path: PathBuf::from("config"),
+            skip_worktree: true,
+            assume_unchanged: false,
+        }];
+        assert_eq!(expected, super::list_skipped(&dir).unwrap());
+
+        super::set_skip_worktree(&dir, "config", false).unwrap();
+        assert!(super::list_skipped(&dir).unwrap().is_empty());
+        assert_eq!(1, super::status(&dir).unwrap().len());
+    }
+
+    #[test]
+    fn assume_unchanged_modified_file() {
+        let dir = tempdir().unwrap();
+        super::init(&dir).unwrap();
+
+        let config_file = dir.path().join("config");
+        fs::write(&config_file, "original").unwrap();
+
+        super::stage_all(&dir).unwrap();
+        super::commit(&dir, "Added config").unwrap();
+
+        super::set_assume_unchanged(&dir, "config", true).unwrap();
+        fs::write(&config_file, "local modification").unwrap();
+
+        assert!(super::status(&dir).unwrap().is_empty());
+
+        let expected = vec![super::SkippedEntry {
+            path: PathBuf::from("config"),
+            skip_worktree: false,
+            assume_unchanged: true,
+        }];
+        assert_eq!(expected, super::list_skipped(&dir).unwrap());
+    }
+
+    #[test]
+    fn skip_worktree_not_in_index() {
+        let dir = tempdir().unwrap();
+        super::init(&dir).unwrap();
+
+        assert!(super::set_skip_worktree(&dir, "missing", true).is_err());
+    }
 }
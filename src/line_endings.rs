@@ -0,0 +1,182 @@
+//! Conversion of line endings when content is stored in the repository.
+//!
+//! Content staged from the working directory with `stage` and `stage_all`
+//! goes through the filters of libgit2 which already apply `core.autocrlf`
+//! and the `text` and `eol` attributes. The functions here apply the same
+//! rules to content that never touched the working directory.
+
+use std::{
+    borrow::Cow,
+    path::Path,
+};
+
+use git2::{
+    AttrCheckFlags,
+    AttrValue,
+};
+
+use crate::{
+    config::optional,
+    Error,
+    GitHelper,
+};
+
+/// Number of bytes git looks at to decide whether content is binary.
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+impl GitHelper {
+    /// Convert CRLF line endings of the content to LF like git does when the
+    /// content is stored under the given path. Whether the content is
+    /// converted depends on the `text` and `eol` attributes of the path and
+    /// on `core.autocrlf`.
+    pub fn normalize_line_endings<'a>(
+        &self,
+        path: &Path,
+        content: &'a [u8],
+    ) -> Result<Cow<'a, [u8]>, Error> {
+        let repository = self.repository();
+
+        let text = repository
+            .get_attr_bytes(path, "text", AttrCheckFlags::INDEX_THEN_FILE)
+            .map_err(Error::Attributes)?;
+        let eol = repository
+            .get_attr_bytes(path, "eol", AttrCheckFlags::INDEX_THEN_FILE)
+            .map_err(Error::Attributes)?;
+
+        let convert = match AttrValue::from_bytes(text) {
+            AttrValue::True => true,
+            AttrValue::False => false,
+            AttrValue::String("auto") => !is_binary(content),
+            AttrValue::Unspecified if eol.is_some() => true,
+            AttrValue::Unspecified => {
+                let autocrlf =
+                    optional(self.config()?.get_string("core.autocrlf"))?.unwrap_or_default();
+
+                autocrlf_enabled(&autocrlf) && !is_binary(content)
+            }
+            _ => false,
+        };
+
+        if !convert || !content.windows(2).any(|pair| pair == b"\r\n") {
+            return Ok(Cow::Borrowed(content));
+        }
+
+        Ok(Cow::Owned(crlf_to_lf(content)))
+    }
+}
+
+/// True for the values of `core.autocrlf` that convert line endings when
+/// content is stored in the repository.
+fn autocrlf_enabled(value: &str) -> bool {
+    let value = value.to_ascii_lowercase();
+
+    value == "input" || value == "true" || value == "yes" || value == "on" || value == "1"
+}
+
+/// Guess whether the content is binary like git does. Content with NUL bytes
+/// or carriage returns that are not followed by a line feed is binary.
+fn is_binary(content: &[u8]) -> bool {
+    let content = &content[..content.len().min(BINARY_CHECK_LENGTH)];
+
+    content.contains(&0)
+        || content
+            .iter()
+            .enumerate()
+            .any(|(index, byte)| *byte == b'\r' && content.get(index + 1) != Some(&b'\n'))
+}
+
+fn crlf_to_lf(content: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(content.len());
+
+    for (index, byte) in content.iter().enumerate() {
+        if *byte == b'\r' && content.get(index + 1) == Some(&b'\n') {
+            continue;
+        }
+        converted.push(*byte);
+    }
+
+    converted
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::Path,
+    };
+    use tempfile::tempdir;
+
+    use crate::GitHelper;
+
+    fn committed_content(helper: &GitHelper, path: &str) -> Vec<u8> {
+        let repository = helper.repository();
+        let tree = repository.head().unwrap().peel_to_tree().unwrap();
+
+        tree.get_path(Path::new(path))
+            .unwrap()
+            .to_object(repository)
+            .unwrap()
+            .peel_to_blob()
+            .unwrap()
+            .content()
+            .to_vec()
+    }
+
+    #[test]
+    fn stage_honors_autocrlf() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        crate::config_set(&dir, "core.autocrlf", "true").unwrap();
+
+        fs::write(dir.path().join("from_disk"), "first\r\nsecond\r\n").unwrap();
+        crate::stage(&dir, &["from_disk"]).unwrap();
+
+        crate::stage_blob(&dir, "normalized", b"first\r\nsecond\r\n", true).unwrap();
+        crate::stage_blob(&dir, "verbatim", b"first\r\nsecond\r\n", false).unwrap();
+        crate::commit(&dir, "Added files with CRLF").unwrap();
+
+        let helper = GitHelper::open(&dir).unwrap();
+        assert_eq!(
+            b"first\nsecond\n".to_vec(),
+            committed_content(&helper, "from_disk")
+        );
+        assert_eq!(
+            b"first\nsecond\n".to_vec(),
+            committed_content(&helper, "normalized")
+        );
+        assert_eq!(
+            b"first\r\nsecond\r\n".to_vec(),
+            committed_content(&helper, "verbatim")
+        );
+    }
+
+    #[test]
+    fn normalize_line_endings_attributes() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(
+            dir.path().join(".gitattributes"),
+            "*.txt text\n*.bin -text\n*.auto text=auto\n",
+        )
+        .unwrap();
+
+        let crlf = b"first\r\nsecond\r\n";
+        let normalize = |path: &str, content: &[u8]| {
+            helper
+                .normalize_line_endings(Path::new(path), content)
+                .unwrap()
+                .into_owned()
+        };
+
+        assert_eq!(b"first\nsecond\n".to_vec(), normalize("file.txt", crlf));
+        assert_eq!(crlf.to_vec(), normalize("file.bin", crlf));
+        assert_eq!(b"first\nsecond\n".to_vec(), normalize("file.auto", crlf));
+        assert_eq!(
+            b"\0binary\r\n".to_vec(),
+            normalize("file.auto", b"\0binary\r\n")
+        );
+
+        // without attributes and autocrlf the content is stored as it is
+        assert_eq!(crlf.to_vec(), normalize("file.other", crlf));
+    }
+}
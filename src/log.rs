@@ -0,0 +1,770 @@
+//! Filtered listing of the commit history.
+
+use std::{
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use git2::{
+    Commit,
+    DiffOptions,
+    Oid,
+    Signature,
+    Sort,
+};
+
+use crate::{
+    time::{
+        Date,
+        Timestamp,
+    },
+    Error,
+    GitHelper,
+};
+
+/// How the email of the author is matched by a LogFilter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthorMatch {
+    /// The email contains the string.
+    Contains(String),
+
+    /// The email is the string.
+    Exact(String),
+
+    /// The email is the string ignoring case, as emails are usually treated.
+    ExactIgnoreCase(String),
+}
+
+/// How the message of a commit is matched by a LogFilter.
+#[derive(Debug, Clone)]
+pub enum MessageMatch {
+    /// The message contains the string.
+    Contains(String),
+
+    /// The regular expression matches somewhere in the message.
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+/// Regular expressions compare their pattern.
+impl PartialEq for MessageMatch {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MessageMatch::Contains(a), MessageMatch::Contains(b)) => a == b,
+            #[cfg(feature = "regex")]
+            (MessageMatch::Regex(a), MessageMatch::Regex(b)) => a.as_str() == b.as_str(),
+            #[cfg(feature = "regex")]
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MessageMatch {}
+
+/// Order in which log_filtered lists commits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogOrder {
+    /// Newest commit time first.
+    #[default]
+    Time,
+
+    /// Children before their parents, ties are broken by commit time. This
+    /// keeps the commits of merged branches together even if their commit
+    /// times interleave or are skewed.
+    Topological,
+}
+
+/// Filter for log_filtered. All filters have to match for a commit to be
+/// listed. The default filter lists every commit reachable from HEAD.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    /// Revision to start the walk at. If None the walk starts at HEAD.
+    pub revision: Option<String>,
+
+    /// Only list commits whose author email matches.
+    pub author: Option<AuthorMatch>,
+
+    /// Only list commits committed at or after this time.
+    pub after: Option<Timestamp>,
+
+    /// Only list commits committed at or before this time.
+    pub before: Option<Timestamp>,
+
+    /// Only list commits whose message matches.
+    pub message: Option<MessageMatch>,
+
+    /// Only list commits that changed one of these paths compared to their
+    /// first parent. Directories match all paths below them.
+    pub paths: Vec<PathBuf>,
+
+    /// Only follow the first parent of merge commits, like `git log
+    /// --first-parent`. On such a walk the commit times are expected to
+    /// decrease, so the walk stops at the first commit older than `after`
+    /// unless `reverse` is set.
+    pub first_parent: bool,
+
+    /// Order of the listed commits.
+    pub order: LogOrder,
+
+    /// List the commits in reverse order, e.g. oldest first for
+    /// LogOrder::Time.
+    pub reverse: bool,
+
+    /// Stop after this many matching commits.
+    pub limit: Option<usize>,
+
+    /// Rewrite the author names and emails with the `.mailmap` of the
+    /// repository, like `git log --use-mailmap`. The author filter then
+    /// matches the rewritten email.
+    pub use_mailmap: bool,
+}
+
+/// Commit listed by log_filtered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Id of the commit.
+    pub oid: Oid,
+
+    /// Full commit message including the body and trailers.
+    pub message: String,
+
+    /// First paragraph of the commit message with line breaks replaced by
+    /// spaces, like `git log --format=%s`.
+    pub summary: String,
+
+    /// Name of the author.
+    pub author_name: String,
+
+    /// Email of the author.
+    pub author_email: String,
+
+    /// Time the commit was authored at.
+    pub authored_at: Timestamp,
+
+    /// Time the commit was committed at.
+    pub committed_at: Timestamp,
+}
+
+impl LogEntry {
+    pub(crate) fn from_commit(commit: &Commit<'_>, author: &Signature<'_>) -> Self {
+        Self {
+            oid: commit.id(),
+            message: String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+            summary: commit
+                .summary_bytes()
+                .map(|summary| String::from_utf8_lossy(summary).into_owned())
+                .unwrap_or_default(),
+            author_name: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+            author_email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+            authored_at: author.when().into(),
+            committed_at: commit.committer().when().into(),
+        }
+    }
+}
+
+impl GitHelper {
+    /// List the commits reachable from the start revision that match the
+    /// filter in the order of the filter.
+    pub fn log_filtered(&self, filter: &LogFilter) -> Result<Vec<LogEntry>, Error> {
+        let repository = self.repository();
+        let start = match &filter.revision {
+            Some(revision) => self.find_commit(revision)?,
+            None => match self.head_commit() {
+                Some(commit) => commit,
+                None => return Ok(Vec::new()),
+            },
+        };
+
+        let mut sorting = match filter.order {
+            LogOrder::Time => Sort::TIME,
+            LogOrder::Topological => Sort::TOPOLOGICAL | Sort::TIME,
+        };
+        if filter.reverse {
+            sorting |= Sort::REVERSE;
+        }
+
+        let mut revwalk = repository.revwalk().map_err(Error::RepositoryRevwalk)?;
+        revwalk
+            .set_sorting(sorting)
+            .and_then(|_| revwalk.push(start.id()))
+            .map_err(Error::RepositoryRevwalk)?;
+        if filter.first_parent {
+            revwalk
+                .simplify_first_parent()
+                .map_err(Error::RepositoryRevwalk)?;
+        }
+
+        let mailmap = if filter.use_mailmap {
+            Some(repository.mailmap().map_err(Error::Mailmap)?)
+        } else {
+            None
+        };
+
+        let mut entries = Vec::new();
+        for oid in revwalk {
+            if filter.limit.is_some_and(|limit| entries.len() >= limit) {
+                break;
+            }
+
+            let oid = oid.map_err(Error::RepositoryRevwalk)?;
+            let commit = repository
+                .find_commit(oid)
+                .map_err(Error::RepositoryFindCommit)?;
+            let committed_at = commit.committer().when().seconds();
+            let author = match &mailmap {
+                Some(mailmap) => commit
+                    .author_with_mailmap(mailmap)
+                    .map_err(Error::Mailmap)?,
+                None => commit.author(),
+            };
+
+            if let Some(after) = filter.after {
+                if committed_at < after.seconds() {
+                    if filter.first_parent && !filter.reverse {
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            if filter
+                .before
+                .is_some_and(|before| committed_at > before.seconds())
+                || !author_matches(filter, &author)
+                || !message_matches(filter, &commit)
+                || !self.changes_paths(filter, &commit)?
+            {
+                continue;
+            }
+
+            entries.push(LogEntry::from_commit(&commit, &author));
+        }
+
+        Ok(entries)
+    }
+
+    /// List the commits reachable from HEAD that changed the file compared to
+    /// their first parent, newest first, like `git log -- <file>`. With a
+    /// limit at most that many commits are listed.
+    pub fn file_history<F: AsRef<Path>>(
+        &self,
+        file: F,
+        limit: Option<usize>,
+    ) -> Result<Vec<LogEntry>, Error> {
+        self.log_filtered(&LogFilter {
+            paths: vec![file.as_ref().to_path_buf()],
+            limit,
+            ..LogFilter::default()
+        })
+    }
+
+    /// List the commits reachable from HEAD by following only the first
+    /// parent of merge commits, newest first, like `git log --first-parent`.
+    /// Merges are listed, the commits of the merged branches are not. With a
+    /// limit at most that many commits are listed.
+    pub fn log_first_parent(&self, limit: Option<usize>) -> Result<Vec<LogEntry>, Error> {
+        self.log_filtered(&LogFilter {
+            first_parent: true,
+            limit,
+            ..LogFilter::default()
+        })
+    }
+
+    /// Return the most recent commit reachable from HEAD that changed the
+    /// file compared to its first parent, e.g. to show who edited it last.
+    /// Fails with Error::FileNotFound if no commit changed the file.
+    pub fn last_commit_for_file<F: AsRef<Path>>(&self, file: F) -> Result<LogEntry, Error> {
+        self.file_history(&file, Some(1))?
+            .pop()
+            .ok_or_else(|| Error::FileNotFound(file.as_ref().to_path_buf()))
+    }
+
+    /// List the commits reachable from HEAD whose author email is the given
+    /// email ignoring case, newest first.
+    pub fn commits_by_author(&self, email: &str) -> Result<Vec<LogEntry>, Error> {
+        self.log_filtered(&LogFilter {
+            author: Some(AuthorMatch::ExactIgnoreCase(email.to_string())),
+            ..LogFilter::default()
+        })
+    }
+
+    /// Count the commits that match the filter.
+    pub fn commit_count(&self, filter: &LogFilter) -> Result<usize, Error> {
+        Ok(self.log_filtered(filter)?.len())
+    }
+
+    /// Count the commits reachable from HEAD per day they were authored on,
+    /// e.g. for contribution graphs. The day is taken in the timezone of the
+    /// author. Days without commits are not in the map.
+    pub fn commit_activity(&self) -> Result<HashMap<Date, usize>, Error> {
+        let mut activity = HashMap::new();
+        for entry in self.log_filtered(&LogFilter::default())? {
+            *activity.entry(entry.authored_at.date()).or_insert(0) += 1;
+        }
+
+        Ok(activity)
+    }
+
+    /// True if the commit changed one of the paths of the filter compared to
+    /// its first parent or if the filter has no paths.
+    fn changes_paths(&self, filter: &LogFilter, commit: &Commit<'_>) -> Result<bool, Error> {
+        if filter.paths.is_empty() {
+            return Ok(true);
+        }
+
+        let mut diff_options = DiffOptions::new();
+        for path in &filter.paths {
+            diff_options.pathspec(path);
+        }
+
+        let tree = commit.tree().map_err(Error::CommitTree)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().map_err(Error::CommitTree)?),
+            Err(_) => None,
+        };
+
+        let diff = self
+            .repository()
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_options))
+            .map_err(Error::Diff)?;
+
+        Ok(diff.deltas().len() > 0)
+    }
+}
+
+fn author_matches(filter: &LogFilter, author: &Signature<'_>) -> bool {
+    let email = String::from_utf8_lossy(author.email_bytes());
+
+    match &filter.author {
+        Some(AuthorMatch::Contains(part)) => email.contains(part.as_str()),
+        Some(AuthorMatch::Exact(expected)) => email == expected.as_str(),
+        Some(AuthorMatch::ExactIgnoreCase(expected)) => email.eq_ignore_ascii_case(expected),
+        None => true,
+    }
+}
+
+fn message_matches(filter: &LogFilter, commit: &Commit<'_>) -> bool {
+    let message = String::from_utf8_lossy(commit.message_bytes());
+
+    match &filter.message {
+        Some(MessageMatch::Contains(part)) => message.contains(part.as_str()),
+        #[cfg(feature = "regex")]
+        Some(MessageMatch::Regex(regex)) => regex.is_match(&message),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::{
+        Oid,
+        Signature,
+        Time,
+    };
+    use std::{
+        collections::HashMap,
+        path::PathBuf,
+    };
+    use tempfile::tempdir;
+
+    use super::{
+        AuthorMatch,
+        LogFilter,
+        LogOrder,
+        MessageMatch,
+    };
+    use crate::{
+        CommitOptions,
+        Date,
+        GitHelper,
+        Sig,
+        Timestamp,
+    };
+
+    /// Commit a file on top of HEAD with the message as content and a fixed
+    /// author and time.
+    fn commit_file(
+        helper: &GitHelper,
+        path: &str,
+        email: &str,
+        seconds: i64,
+        message: &str,
+    ) -> Oid {
+        let repository = helper.repository();
+        helper.stage_blob(path, message.as_bytes(), false).unwrap();
+        let tree = helper.index_tree().unwrap();
+
+        let signature = Signature::new("Tester", email, &Time::new(seconds, 0)).unwrap();
+        let parent = helper.head_commit();
+
+        repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parent.iter().collect::<Vec<_>>(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn log_filtered() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        let first = commit_file(&helper, "src/lib.rs", "alice@example.com", 1000, "Add lib");
+        let second = commit_file(&helper, "README", "alice@example.com", 2000, "Add readme");
+        let third = commit_file(&helper, "src/main.rs", "bob@example.com", 3000, "Add main");
+        let fourth = commit_file(&helper, "src/lib.rs", "alice@example.com", 4000, "Fix lib");
+
+        let all = crate::log_filtered(&dir, &LogFilter::default()).unwrap();
+        assert_eq!(
+            vec![fourth, third, second, first],
+            all.iter().map(|entry| entry.oid).collect::<Vec<_>>()
+        );
+        assert_eq!("alice@example.com", all[0].author_email);
+        assert_eq!(4000, all[0].committed_at.seconds());
+
+        let filter = LogFilter {
+            author: Some(AuthorMatch::Contains("alice".to_string())),
+            after: Some(Timestamp::from_seconds(1500, 0)),
+            paths: vec![PathBuf::from("src")],
+            ..LogFilter::default()
+        };
+        let entries = crate::log_filtered(&dir, &filter).unwrap();
+        assert_eq!(
+            vec![fourth],
+            entries.iter().map(|entry| entry.oid).collect::<Vec<_>>()
+        );
+
+        let filter = LogFilter {
+            author: Some(AuthorMatch::Exact("alice@example.com".to_string())),
+            before: Some(Timestamp::from_seconds(3000, 0)),
+            message: Some(MessageMatch::Contains("Add".to_string())),
+            first_parent: true,
+            ..LogFilter::default()
+        };
+        let entries = crate::log_filtered(&dir, &filter).unwrap();
+        assert_eq!(
+            vec![second, first],
+            entries.iter().map(|entry| entry.oid).collect::<Vec<_>>()
+        );
+
+        let filter = LogFilter {
+            revision: Some("HEAD~1".to_string()),
+            after: Some(Timestamp::from_seconds(2000, 0)),
+            first_parent: true,
+            ..LogFilter::default()
+        };
+        let entries = crate::log_filtered(&dir, &filter).unwrap();
+        assert_eq!(
+            vec![third, second],
+            entries.iter().map(|entry| entry.oid).collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn log_filtered_message_regex() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        let first = commit_file(&helper, "src/lib.rs", "alice@example.com", 1000, "Add lib");
+        commit_file(
+            &helper,
+            "README",
+            "alice@example.com",
+            2000,
+            "Update readme",
+        );
+        let third = commit_file(
+            &helper,
+            "src/lib.rs",
+            "bob@example.com",
+            3000,
+            "Fix lib (#12)",
+        );
+
+        let filter = LogFilter {
+            message: Some(MessageMatch::Regex(
+                regex::Regex::new(r"^(Add|Fix) .*lib").unwrap(),
+            )),
+            ..LogFilter::default()
+        };
+        let entries = crate::log_filtered(&dir, &filter).unwrap();
+        assert_eq!(
+            vec![third, first],
+            entries.iter().map(|entry| entry.oid).collect::<Vec<_>>()
+        );
+
+        let filter = LogFilter {
+            author: Some(AuthorMatch::Contains("bob".to_string())),
+            message: Some(MessageMatch::Regex(regex::Regex::new(r"#\d+").unwrap())),
+            ..LogFilter::default()
+        };
+        let entries = crate::log_filtered(&dir, &filter).unwrap();
+        assert_eq!(
+            vec![third],
+            entries.iter().map(|entry| entry.oid).collect::<Vec<_>>()
+        );
+    }
+
+    /// Create a commit with an empty tree, the given parents and a fixed
+    /// time without updating any reference.
+    fn commit_at(helper: &GitHelper, message: &str, seconds: i64, parents: &[Oid]) -> Oid {
+        let repository = helper.repository();
+        let signature =
+            Signature::new("Tester", "tester@example.com", &Time::new(seconds, 0)).unwrap();
+        let tree = repository
+            .find_tree(repository.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let parents = parents
+            .iter()
+            .map(|oid| repository.find_commit(*oid).unwrap())
+            .collect::<Vec<_>>();
+
+        repository
+            .commit(
+                None,
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn log_order_and_first_parent() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        // the clock of the machine that created main_commit was wrong
+        let base = commit_at(&helper, "Base", 1000, &[]);
+        let main_commit = commit_at(&helper, "Main", 500, &[base]);
+        let feature = commit_at(&helper, "Feature", 3000, &[base]);
+        let merge = commit_at(&helper, "Merge", 5000, &[main_commit, feature]);
+
+        let oids = |filter: &LogFilter| {
+            crate::log_filtered(&dir, filter)
+                .unwrap()
+                .iter()
+                .map(|entry| entry.oid)
+                .collect::<Vec<_>>()
+        };
+
+        let filter = LogFilter {
+            revision: Some(merge.to_string()),
+            ..LogFilter::default()
+        };
+        assert_eq!(vec![merge, feature, base, main_commit], oids(&filter));
+        assert_eq!(4, crate::commit_count(&dir, &filter).unwrap());
+
+        let filter = LogFilter {
+            order: LogOrder::Topological,
+            ..filter
+        };
+        assert_eq!(vec![merge, feature, main_commit, base], oids(&filter));
+
+        let filter = LogFilter {
+            reverse: true,
+            ..filter
+        };
+        assert_eq!(vec![base, main_commit, feature, merge], oids(&filter));
+
+        // the first-parent walk skips the commits of the merged branch
+        let filter = LogFilter {
+            revision: Some(merge.to_string()),
+            first_parent: true,
+            order: LogOrder::Topological,
+            ..LogFilter::default()
+        };
+        assert_eq!(vec![merge, main_commit, base], oids(&filter));
+        assert_eq!(3, crate::commit_count(&dir, &filter).unwrap());
+    }
+
+    #[test]
+    fn log_first_parent() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        let base = commit_at(&helper, "Base", 1000, &[]);
+        let side_first = commit_at(&helper, "Side first", 2000, &[base]);
+        let side_second = commit_at(&helper, "Side second", 3000, &[side_first]);
+        let mainline = commit_at(&helper, "Mainline", 4000, &[base]);
+        let merge = commit_at(&helper, "Merge side", 5000, &[mainline, side_second]);
+        helper
+            .repository()
+            .reference("refs/heads/master", merge, true, "merge")
+            .unwrap();
+
+        let oids = |limit| {
+            crate::log_first_parent(&dir, limit)
+                .unwrap()
+                .iter()
+                .map(|entry| entry.oid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![merge, mainline, base], oids(None));
+        assert_eq!(vec![merge, mainline], oids(Some(2)));
+
+        let all = crate::log_filtered(&dir, &LogFilter::default()).unwrap();
+        assert_eq!(5, all.len());
+    }
+
+    #[test]
+    fn commit_activity() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        assert!(crate::commit_activity(&dir).unwrap().is_empty());
+
+        // 2005-04-07 08:00, 2005-04-07 23:59:59 and 2005-04-08 00:00 UTC
+        let first = commit_at(&helper, "First", 1_112_860_800, &[]);
+        let second = commit_at(&helper, "Second", 1_112_918_399, &[first]);
+        let third = commit_at(&helper, "Third", 1_112_918_400, &[second]);
+        helper
+            .repository()
+            .reference("refs/heads/master", third, true, "commit")
+            .unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert(
+            Date {
+                year: 2005,
+                month: 4,
+                day: 7,
+            },
+            2,
+        );
+        expected.insert(
+            Date {
+                year: 2005,
+                month: 4,
+                day: 8,
+            },
+            1,
+        );
+        assert_eq!(expected, crate::commit_activity(&dir).unwrap());
+    }
+
+    #[test]
+    fn file_history() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        let first = commit_file(&helper, "file", "tester@example.com", 1000, "First");
+        commit_file(&helper, "other", "tester@example.com", 2000, "Second");
+        let third = commit_file(&helper, "file", "tester@example.com", 3000, "Third");
+
+        let history = crate::file_history(&dir, "file", None).unwrap();
+        assert_eq!(
+            vec![third, first],
+            history.iter().map(|entry| entry.oid).collect::<Vec<_>>()
+        );
+
+        let history = crate::file_history(&dir, "file", Some(1)).unwrap();
+        assert_eq!(
+            vec![third],
+            history.iter().map(|entry| entry.oid).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn last_commit_for_file() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        commit_file(&helper, "file", "first@example.com", 1000, "First");
+        let second = commit_file(&helper, "file", "second@example.com", 2000, "Second");
+        commit_file(&helper, "other", "third@example.com", 3000, "Third");
+
+        let entry = crate::last_commit_for_file(&dir, "file").unwrap();
+        assert_eq!(second, entry.oid);
+        assert_eq!("second@example.com", entry.author_email);
+
+        assert_eq!(
+            Err(crate::Error::FileNotFound(PathBuf::from("missing"))),
+            crate::last_commit_for_file(&dir, "missing")
+        );
+    }
+
+    #[test]
+    fn commits_by_author() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        let commit_as = |email: &str, message: &str| {
+            let options = CommitOptions {
+                author: Some(Sig {
+                    name: "Tester".to_string(),
+                    email: email.to_string(),
+                    when: Timestamp::from_seconds(1000, 0),
+                }),
+                ..CommitOptions::default()
+            };
+            helper.commit_with(message, &options).unwrap();
+            helper.head_commit().unwrap().id()
+        };
+
+        let first = commit_as("alice@example.com", "First");
+        commit_as("bob@example.com", "Second");
+        let third = commit_as("Alice@Example.com", "Third");
+
+        let entries = crate::commits_by_author(&dir, "ALICE@example.com").unwrap();
+        assert_eq!(
+            vec![third, first],
+            entries.iter().map(|entry| entry.oid).collect::<Vec<_>>()
+        );
+        assert!(crate::commits_by_author(&dir, "carol@example.com")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn log_filtered_use_mailmap() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        std::fs::write(
+            dir.path().join(".mailmap"),
+            "Alice <alice@example.com> <alice@old.example.com>\n",
+        )
+        .unwrap();
+
+        let first = commit_file(&helper, "a", "alice@old.example.com", 1000, "First");
+        let second = commit_file(&helper, "b", "bob@example.com", 2000, "Second");
+
+        let raw = crate::log_filtered(&dir, &LogFilter::default()).unwrap();
+        assert_eq!("alice@old.example.com", raw[1].author_email);
+
+        let filter = LogFilter {
+            use_mailmap: true,
+            ..LogFilter::default()
+        };
+        let mapped = crate::log_filtered(&dir, &filter).unwrap();
+        assert_eq!(first, mapped[1].oid);
+        assert_eq!("Alice", mapped[1].author_name);
+        assert_eq!("alice@example.com", mapped[1].author_email);
+        assert_eq!(second, mapped[0].oid);
+        assert_eq!("bob@example.com", mapped[0].author_email);
+
+        let filter = LogFilter {
+            author: Some(AuthorMatch::Exact("alice@example.com".to_string())),
+            use_mailmap: true,
+            ..LogFilter::default()
+        };
+        assert_eq!(
+            vec![first],
+            crate::log_filtered(&dir, &filter)
+                .unwrap()
+                .iter()
+                .map(|entry| entry.oid)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! Housekeeping of the git directory similar to `git gc`.
+
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+};
+
+use git2::{
+    ObjectType,
+    ReferenceType,
+};
+use walkdir::WalkDir;
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// Header written to the packed-refs file. The same traits are written by
+/// git itself.
+const PACKED_REFS_HEADER: &str = "# pack-refs with: peeled fully-peeled sorted \n";
+
+/// Prefixes of references that belong to a single worktree and are never
+/// packed, like git does.
+const PER_WORKTREE_REFS: [&str; 3] = ["refs/bisect/", "refs/rewritten/", "refs/worktree/"];
+
+/// Summary of the work done by maintenance.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    /// Number of loose objects that were moved into a packfile.
+    pub objects_packed: usize,
+
+    /// Number of loose references that were moved into the packed-refs file.
+    pub refs_packed: usize,
+
+    /// Number of bytes the objects and references shrank by. Zero if they
+    /// grew, which can happen for tiny repositories where the index of a new
+    /// packfile is larger than the loose objects it replaces.
+    pub bytes_reclaimed: u64,
+}
+
+impl GitHelper {
+    /// Do housekeeping in the git directory. Loose references are always
+    /// moved into the packed-refs file. With the `repack` feature enabled
+    /// all loose objects are additionally written into a new packfile and
+    /// removed afterwards.
+    pub fn maintenance(&self) -> Result<MaintenanceReport, Error> {
+        let size_before = self.maintained_size();
+
+        let refs_packed = self.pack_refs()?;
+
+        #[cfg(feature = "repack")]
+        let objects_packed = self.repack_loose_objects()?;
+        #[cfg(not(feature = "repack"))]
+        let objects_packed = 0;
+
+        let size_after = self.maintained_size();
+
+        Ok(MaintenanceReport {
+            objects_packed,
+            refs_packed,
+            bytes_reclaimed: size_before.saturating_sub(size_after),
+        })
+    }
+
+    /// Write all direct references into the packed-refs file and remove
+    /// their loose files. Symbolic references and the references that belong
+    /// to a single worktree, like those of a bisect, are left untouched.
+    /// Returns the number of loose reference files that were removed.
+    pub fn pack_refs(&self) -> Result<usize, Error> {
+        let repository = self.repository();
+        // packed-refs and the shared loose references live in the common git
+        // directory, not in the git directory of a linked worktree
+        let common_dir = repository.commondir();
+
+        let mut refs = Vec::new();
+        for reference in repository
+            .references()
+            .map_err(Error::RepositoryReferences)?
+        {
+            let reference = reference.map_err(Error::RepositoryReferences)?;
+            if reference.kind() != Some(ReferenceType::Direct) {
+                continue;
+            }
+
+            let name = reference.name_bytes().to_vec();
+            if PER_WORKTREE_REFS
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_bytes()))
+            {
+                continue;
+            }
+
+            let target = match reference.target() {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let peeled = match repository.find_object(target, None) {
+                Ok(object) if object.kind() == Some(ObjectType::Tag) => object
+                    .peel(ObjectType::Any)
+                    .ok()
+                    .map(|peeled| peeled.id())
+                    .filter(|peeled| *peeled != target),
+                _ => None,
+            };
+
+            refs.push((name, target, peeled));
+        }
+        refs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut content = PACKED_REFS_HEADER.as_bytes().to_vec();
+        for (name, target, peeled) in &refs {
+            content.extend_from_slice(format!("{} ", target).as_bytes());
+            content.extend_from_slice(name);
+            content.push(b'\n');
+            if let Some(peeled) = peeled {
+                content.extend_from_slice(format!("^{}\n", peeled).as_bytes());
+            }
+        }
+
+        let lock_path = common_dir.join("packed-refs.lock");
+        let mut lock = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+            .map_err(Error::PackRefsWrite)?;
+
+        let written = lock
+            .write_all(&content)
+            .and_then(|_| lock.sync_all())
+            .and_then(|_| fs::rename(&lock_path, common_dir.join("packed-refs")));
+
+        if let Err(err) = written {
+            let _ = fs::remove_file(&lock_path);
+            return Err(Error::PackRefsWrite(err));
+        }
+
+        let mut removed = 0;
+        for (name, target, _) in &refs {
+            // loose files of names that are not valid UTF-8 are kept, they
+            // take precedence over the packed entry with the same target
+            let name = match std::str::from_utf8(name) {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            let loose_path = common_dir.join(name);
+            if !loose_path.is_file() {
+                continue;
+            }
+
+            // only remove the loose file if it was not updated in the meantime
+            let loose_target = fs::read_to_string(&loose_path).map_err(Error::PackRefsWrite)?;
+            if loose_target.trim() != target.to_string() {
+                continue;
+            }
+
+            fs::remove_file(&loose_path).map_err(Error::PackRefsWrite)?;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Write all loose objects into a new packfile and remove the loose files
+    /// once the packfile and its index exist. Returns the number of objects
+    /// that were packed.
+    #[cfg(feature = "repack")]
+    pub fn repack_loose_objects(&self) -> Result<usize, Error> {
+        let repository = self.repository();
+        let objects_path = repository.path().join("objects");
+
+        let loose = crate::stats::loose_objects(&objects_path)?;
+        if loose.is_empty() {
+            return Ok(0);
+        }
+
+        let mut builder = repository.packbuilder().map_err(Error::Repack)?;
+        for (oid, _) in &loose {
+            builder.insert_object(*oid, None).map_err(Error::Repack)?;
+        }
+
+        let pack_path = objects_path.join("pack");
+        fs::create_dir_all(&pack_path).map_err(Error::RepackFilesystem)?;
+        builder.write(&pack_path, 0).map_err(Error::Repack)?;
+
+        if builder.written() != loose.len() {
+            return Err(Error::RepackIncomplete);
+        }
+
+        let name = builder
+            .name()
+            .map_err(Error::Repack)?
+            .map(|name| format!("pack-{}", name))
+            .ok_or(Error::RepackIncomplete)?;
+
+        let pack_written = pack_path.join(format!("{}.pack", name)).is_file()
+            && pack_path.join(format!("{}.idx", name)).is_file();
+        if !pack_written {
+            return Err(Error::RepackIncomplete);
+        }
+
+        for (_, path) in &loose {
+            fs::remove_file(path).map_err(Error::RepackFilesystem)?;
+            if let Some(fanout) = path.parent() {
+                // fails if there are still other objects in the directory
+                let _ = fs::remove_dir(fanout);
+            }
+        }
+
+        repository
+            .odb()
+            .and_then(|odb| odb.refresh())
+            .map_err(Error::Repack)?;
+
+        Ok(loose.len())
+    }
+
+    fn maintained_size(&self) -> u64 {
+        let git_dir = self.repository().path();
+
+        let tree_size = |path: &Path| -> u64 {
+            WalkDir::new(path)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.metadata().ok())
+                .filter(|metadata| metadata.is_file())
+                .map(|metadata| metadata.len())
+                .sum()
+        };
+
+        tree_size(&git_dir.join("objects"))
+            + tree_size(&git_dir.join("refs"))
+            + tree_size(&git_dir.join("packed-refs"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::tempdir;
+
+    use crate::GitHelper;
+
+    fn repository_with_history() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        for (file, message) in &[("first_file", "first"), ("second_file", "second")] {
+            fs::write(dir.path().join(file), file).unwrap();
+            helper.stage(&[file]).unwrap();
+            helper.commit(message).unwrap();
+        }
+
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        repository.branch("feature", &head, false).unwrap();
+        let signature = repository.signature().unwrap();
+        repository
+            .tag("v1.0.0", head.as_object(), &signature, "release", false)
+            .unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn maintenance_packs_refs() {
+        let dir = repository_with_history();
+
+        let report = crate::maintenance(&dir).unwrap();
+        assert_eq!(3, report.refs_packed);
+
+        let git_dir = dir.path().join(".git");
+        assert!(!git_dir.join("refs/heads/master").exists());
+        assert!(!git_dir.join("refs/tags/v1.0.0").exists());
+
+        let packed_refs = fs::read_to_string(git_dir.join("packed-refs")).unwrap();
+        assert!(packed_refs.contains(" refs/heads/feature\n"));
+        assert!(packed_refs.contains(" refs/heads/master\n"));
+        assert!(packed_refs.contains(" refs/tags/v1.0.0\n^"));
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        assert!(repository.find_reference("refs/heads/feature").is_ok());
+        assert_eq!(
+            repository.head().unwrap().target(),
+            repository
+                .revparse_single("v1.0.0^{commit}")
+                .ok()
+                .map(|object| object.id())
+        );
+
+        // nothing left to pack on the second run
+        assert_eq!(0, crate::maintenance(&dir).unwrap().refs_packed);
+    }
+
+    #[test]
+    fn pack_refs_keeps_non_utf8_names() {
+        let dir = repository_with_history();
+        let git_dir = dir.path().join(".git");
+        let head = GitHelper::open(&dir)
+            .unwrap()
+            .repository()
+            .refname_to_id("HEAD")
+            .unwrap();
+
+        let mut packed = format!("{} refs/heads/caf", head).into_bytes();
+        packed.extend_from_slice(b"\xe9\n");
+        fs::write(git_dir.join("packed-refs"), packed).unwrap();
+
+        GitHelper::open(&dir).unwrap().pack_refs().unwrap();
+
+        let packed = fs::read(git_dir.join("packed-refs")).unwrap();
+        assert!(packed
+            .windows(b"refs/heads/caf\xe9\n".len())
+            .any(|line| line == b"refs/heads/caf\xe9\n"));
+        assert!(String::from_utf8_lossy(&packed).contains(" refs/heads/master\n"));
+    }
+
+    #[test]
+    fn pack_refs_in_linked_worktree() {
+        let dir = repository_with_history();
+        let worktrees = tempdir().unwrap();
+        let path = worktrees.path().join("linked");
+        GitHelper::open(&dir)
+            .unwrap()
+            .repository()
+            .worktree("linked", &path, None)
+            .unwrap();
+
+        let linked = GitHelper::open(&path).unwrap();
+        let worktree_dir = linked.repository().path().to_path_buf();
+        let head = linked.repository().head().unwrap().target().unwrap();
+        linked
+            .repository()
+            .reference("refs/bisect/bad", head, false, "bisect")
+            .unwrap();
+
+        assert!(linked.pack_refs().unwrap() > 0);
+
+        let git_dir = dir.path().join(".git");
+        assert!(!worktree_dir.join("packed-refs").exists());
+        let packed_refs = fs::read_to_string(git_dir.join("packed-refs")).unwrap();
+        assert!(packed_refs.contains(" refs/heads/master\n"));
+        assert!(packed_refs.contains(" refs/heads/linked\n"));
+        assert!(!packed_refs.contains("refs/bisect/"));
+        assert!(!git_dir.join("refs/heads/master").exists());
+        assert!(worktree_dir.join("refs/bisect/bad").exists());
+    }
+
+    #[cfg(feature = "repack")]
+    #[test]
+    fn maintenance_repacks_loose_objects() {
+        let dir = repository_with_history();
+        let before = crate::repo_stats(&dir).unwrap();
+        let objects_before = crate::object_count(&dir).unwrap();
+        assert!(before.loose_objects > 0);
+
+        let report = crate::maintenance(&dir).unwrap();
+        assert_eq!(before.loose_objects, report.objects_packed);
+
+        let after = crate::repo_stats(&dir).unwrap();
+        assert_eq!(0, after.loose_objects);
+        assert_eq!(1, after.packfiles);
+        assert_eq!(objects_before, crate::object_count(&dir).unwrap());
+        assert!(crate::status(&dir).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,502 @@
+//! Transport options shared by all operations that talk to a remote.
+
+use std::{
+    convert::TryFrom,
+    ffi::CString,
+    fmt,
+    os::raw::c_int,
+    path::PathBuf,
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        mpsc,
+        Arc,
+        Mutex,
+        MutexGuard,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use git2::{
+    cert::Cert,
+    CertificateCheckStatus,
+    Error as Git2Error,
+    ErrorClass,
+    ErrorCode,
+    ProxyOptions,
+    RemoteCallbacks,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// libgit2 only offers process wide settings for some of the options. They
+/// are changed under this lock, so concurrent operations do not interleave
+/// their changes.
+static GLOBAL_SETTINGS: Mutex<()> = Mutex::new(());
+
+/// Options for the transport used to talk to a remote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkOptions {
+    /// Url of the proxy to use. If None the proxy is detected like git does
+    /// from the `http.proxy` configuration and the `http_proxy` and
+    /// `https_proxy` environment variables.
+    pub proxy: Option<String>,
+
+    /// File with the certificates of the certificate authorities to trust
+    /// instead of the ones of the system. This is a process wide setting of
+    /// libgit2 and stays in effect for later operations.
+    pub ca_bundle: Option<PathBuf>,
+
+    /// User agent sent to http remotes. This is a process wide setting of
+    /// libgit2 and stays in effect for later operations.
+    pub user_agent: Option<String>,
+
+    /// Timeout for connecting to the remote and for every read from and
+    /// write to it. This is a process wide setting of libgit2 and stays in
+    /// effect for later operations.
+    pub timeout: Option<Duration>,
+
+    /// Maximum duration of the whole operation including connecting to the
+    /// remote and transferring data. None waits forever. When the deadline
+    /// passes the operation fails with Error::Timeout and the transfer is
+    /// aborted the next time libgit2 reports progress.
+    pub operation_timeout: Option<Duration>,
+
+    /// Accept every certificate the remote presents, including expired and
+    /// self-signed ones. This disables the protection against man in the
+    /// middle attacks and should only be used for trusted internal servers.
+    pub danger_accept_invalid_certs: bool,
+
+    /// Decide whether to accept the certificate the remote presents instead
+    /// of validating it against the trusted certificate authorities, e.g. to
+    /// accept the pinned certificate of a self-signed internal server.
+    /// Rejected certificates fail the operation with
+    /// Error::CertificateRejected. Ignored if `danger_accept_invalid_certs`
+    /// is set.
+    pub certificate_check: Option<CertificateCheck>,
+}
+
+/// Certificate a remote presented when connecting to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateInfo {
+    /// Name of the host the certificate was presented for.
+    pub host: String,
+
+    /// DER encoded X.509 certificate of https remotes.
+    pub x509: Option<Vec<u8>>,
+
+    /// SHA-256 hash of the host key of ssh remotes.
+    pub hostkey_sha256: Option<Vec<u8>>,
+}
+
+/// Callback deciding whether a certificate is accepted. Returns true to
+/// accept the certificate. Callbacks compare equal if they are clones of the
+/// same callback.
+#[derive(Clone)]
+pub struct CertificateCheck(Arc<dyn Fn(&CertificateInfo) -> bool + Send + Sync>);
+
+impl CertificateCheck {
+    /// Create a certificate check from the callback.
+    pub fn new<F>(check: F) -> Self
+    where
+        F: Fn(&CertificateInfo) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(check))
+    }
+}
+
+impl fmt::Debug for CertificateCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CertificateCheck(..)")
+    }
+}
+
+impl PartialEq for CertificateCheck {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CertificateCheck {}
+
+impl CertificateInfo {
+    fn new(cert: &Cert<'_>, host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            x509: cert.as_x509().map(|x509| x509.data().to_vec()),
+            hostkey_sha256: cert
+                .as_hostkey()
+                .and_then(|hostkey| hostkey.hash_sha256())
+                .map(|hash| hash.to_vec()),
+        }
+    }
+}
+
+impl NetworkOptions {
+    /// Return the proxy options for an operation.
+    pub(crate) fn proxy_options(&self) -> ProxyOptions<'static> {
+        let mut options = ProxyOptions::new();
+
+        match &self.proxy {
+            Some(url) => options.url(url),
+            None => options.auto(),
+        };
+
+        options
+    }
+
+    /// Install the certificate check callback if the options need one.
+    pub(crate) fn install(&self, callbacks: &mut RemoteCallbacks<'_>) {
+        if self.certificate_status().is_none() {
+            return;
+        }
+
+        let options = self.clone();
+        callbacks.certificate_check(move |cert, host| {
+            options.check_certificate(&CertificateInfo::new(cert, host))
+        });
+    }
+
+    /// Decision of the certificate check callback. None if no callback is
+    /// needed and libgit2 should check certificates on its own.
+    pub(crate) fn certificate_status(&self) -> Option<CertificateCheckStatus> {
+        if self.danger_accept_invalid_certs || self.certificate_check.is_some() {
+            Some(CertificateCheckStatus::CertificateOk)
+        } else if self.ca_bundle.is_some() {
+            // libgit2 validates against the configured bundle
+            Some(CertificateCheckStatus::CertificatePassthrough)
+        } else {
+            None
+        }
+    }
+
+    /// Check the certificate as configured. Certificates rejected by the
+    /// certificate check fail with a certificate error that is reported as
+    /// Error::CertificateRejected.
+    pub(crate) fn check_certificate(
+        &self,
+        certificate: &CertificateInfo,
+    ) -> Result<CertificateCheckStatus, Git2Error> {
+        match &self.certificate_check {
+            Some(check) if !self.danger_accept_invalid_certs => {
+                if (check.0)(certificate) {
+                    Ok(CertificateCheckStatus::CertificateOk)
+                } else {
+                    Err(Git2Error::new(
+                        ErrorCode::Certificate,
+                        ErrorClass::Net,
+                        format!("certificate of {} rejected", certificate.host),
+                    ))
+                }
+            }
+            _ => Ok(self
+                .certificate_status()
+                .unwrap_or(CertificateCheckStatus::CertificatePassthrough)),
+        }
+    }
+
+    /// Apply the options libgit2 only supports as process wide settings.
+    /// The returned guard keeps other operations from changing the settings
+    /// until it is dropped, so it has to be held for the whole operation.
+    pub(crate) fn apply_global(&self) -> Result<Option<MutexGuard<'static, ()>>, Error> {
+        if self.ca_bundle.is_none() && self.user_agent.is_none() && self.timeout.is_none() {
+            return Ok(None);
+        }
+
+        let lock = GLOBAL_SETTINGS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(ca_bundle) = &self.ca_bundle {
+            // SAFETY: calls into libgit2 that change process wide settings
+            // are serialized through GLOBAL_SETTINGS
+            unsafe { git2::opts::set_ssl_cert_file(ca_bundle) }.map_err(Error::Network)?;
+        }
+
+        if let Some(user_agent) = &self.user_agent {
+            let user_agent = CString::new(user_agent.as_str())
+                .map_err(|_| Error::Network(Git2Error::from_str("user agent contains NUL")))?;
+
+            // SAFETY: the string outlives the call, libgit2 copies it
+            let result = unsafe {
+                libgit2_sys::git_libgit2_opts(
+                    libgit2_sys::GIT_OPT_SET_USER_AGENT as c_int,
+                    user_agent.as_ptr(),
+                )
+            };
+            if result < 0 {
+                return Err(Error::Network(Git2Error::last_error(result)));
+            }
+        }
+
+        if let Some(timeout) = self.timeout {
+            let milliseconds = c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX);
+
+            // SAFETY: see above
+            unsafe {
+                git2::opts::set_server_connect_timeout_in_milliseconds(milliseconds)
+                    .and_then(|_| git2::opts::set_server_timeout_in_milliseconds(milliseconds))
+            }
+            .map_err(Error::Network)?;
+        }
+
+        Ok(Some(lock))
+    }
+}
+
+/// Flag to abort a running operation from another thread.
+#[derive(Clone, Default)]
+pub(crate) struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub(crate) fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Install callbacks that abort the operation once it was cancelled.
+    pub(crate) fn install(&self, callbacks: &mut RemoteCallbacks<'_>) {
+        let cancel = self.clone();
+        callbacks.transfer_progress(move |_| !cancel.is_cancelled());
+
+        let cancel = self.clone();
+        callbacks.sideband_progress(move |_| !cancel.is_cancelled());
+
+        let cancel = self.clone();
+        callbacks.push_negotiation(move |_| {
+            if cancel.is_cancelled() {
+                Err(Git2Error::from_str("operation cancelled"))
+            } else {
+                Ok(())
+            }
+        });
+    }
+}
+
+impl GitHelper {
+    /// Run the operation and fail with Error::Timeout if it does not finish
+    /// in time. With a timeout the operation runs on a worker thread with its
+    /// own handle to the repository, because the repository of this handle
+    /// can not be shared between threads.
+    ///
+    /// On a timeout the worker thread is not stopped, only cancelled through
+    /// the callbacks of Cancel. It keeps running until libgit2 calls one of
+    /// them or gives up on its own, e.g. after the server timeout of the
+    /// network options. Until then it holds the lock of the process wide
+    /// settings it applied, so later operations that change those settings
+    /// wait for it instead of changing them underneath it.
+    pub(crate) fn with_timeout<T, F>(
+        &self,
+        operation: &'static str,
+        timeout: Option<Duration>,
+        run: F,
+    ) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&GitHelper, &Cancel) -> Result<T, Error> + Send + 'static,
+    {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return run(self, &Cancel::default()),
+        };
+
+        let git_dir = self.repository().path().to_path_buf();
+        let cancel = Cancel::default();
+        let (sender, receiver) = mpsc::channel();
+
+        let worker_cancel = cancel.clone();
+        let started = Instant::now();
+        thread::spawn(move || {
+            let result = GitHelper::open(&git_dir).and_then(|helper| run(&helper, &worker_cancel));
+            let _ = sender.send(result);
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(_) => {
+                cancel.cancel();
+                Err(Error::Timeout {
+                    operation,
+                    elapsed: started.elapsed(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::CertificateCheckStatus;
+    use std::{
+        net::TcpListener,
+        path::PathBuf,
+        thread,
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    use super::{
+        CertificateCheck,
+        CertificateInfo,
+        NetworkOptions,
+    };
+
+    #[test]
+    fn certificate_check_installed() {
+        assert!(NetworkOptions::default().certificate_status().is_none());
+
+        let options = NetworkOptions {
+            ca_bundle: Some(PathBuf::from("/etc/ssl/internal.pem")),
+            ..NetworkOptions::default()
+        };
+        assert!(matches!(
+            options.certificate_status(),
+            Some(CertificateCheckStatus::CertificatePassthrough)
+        ));
+
+        let options = NetworkOptions {
+            danger_accept_invalid_certs: true,
+            ..options
+        };
+        assert!(matches!(
+            options.certificate_status(),
+            Some(CertificateCheckStatus::CertificateOk)
+        ));
+    }
+
+    #[test]
+    fn certificate_check_callback() {
+        let pinned = b"pinned certificate".to_vec();
+        let expected = pinned.clone();
+        let options = NetworkOptions {
+            certificate_check: Some(CertificateCheck::new(move |certificate| {
+                certificate.x509.as_ref() == Some(&expected)
+            })),
+            ..NetworkOptions::default()
+        };
+        assert!(matches!(
+            options.certificate_status(),
+            Some(CertificateCheckStatus::CertificateOk)
+        ));
+
+        let certificate = |x509: &[u8]| CertificateInfo {
+            host: "git.internal".to_string(),
+            x509: Some(x509.to_vec()),
+            hostkey_sha256: None,
+        };
+        assert!(matches!(
+            options.check_certificate(&certificate(&pinned)),
+            Ok(CertificateCheckStatus::CertificateOk)
+        ));
+
+        let err = options
+            .check_certificate(&certificate(b"other certificate"))
+            .err()
+            .unwrap();
+        assert_eq!(git2::ErrorCode::Certificate, err.code());
+        assert_eq!("certificate of git.internal rejected", err.message());
+
+        let dir = tempfile::tempdir().unwrap();
+        let helper = crate::GitHelper::init(&dir).unwrap();
+        let resolver =
+            crate::credentials::CredentialResolver::new(helper.repository(), &[]).unwrap();
+        assert_eq!(
+            crate::Error::CertificateRejected("certificate of git.internal rejected".to_string()),
+            resolver.error(err, crate::Error::RemoteFetch)
+        );
+    }
+
+    #[test]
+    fn global_settings_locked_while_applied() {
+        assert!(NetworkOptions::default().apply_global().unwrap().is_none());
+
+        let options = NetworkOptions {
+            user_agent: Some("git/2.0 (githelper)".to_string()),
+            ..NetworkOptions::default()
+        };
+        let settings = options.apply_global().unwrap();
+        assert!(settings.is_some());
+        assert!(super::GLOBAL_SETTINGS.try_lock().is_err());
+    }
+
+    #[test]
+    fn fetch_with_network_options() {
+        let upstream = tempfile::tempdir().unwrap();
+        let helper = crate::GitHelper::init(&upstream).unwrap();
+        helper.commit_empty("Initial commit").unwrap();
+
+        let local = tempfile::tempdir().unwrap();
+        crate::GitHelper::init(&local)
+            .unwrap()
+            .repository()
+            .remote("origin", upstream.path().to_str().unwrap())
+            .unwrap();
+
+        let options = crate::FetchOptions {
+            network: NetworkOptions {
+                proxy: Some("http://127.0.0.1:9".to_string()),
+                danger_accept_invalid_certs: true,
+                ..NetworkOptions::default()
+            },
+            ..crate::FetchOptions::default()
+        };
+        let report = crate::fetch(&local, &options).unwrap();
+        assert_eq!(
+            vec!["refs/remotes/origin/master".to_string()],
+            report.updated_refs
+        );
+    }
+
+    #[test]
+    fn fetch_timeout() {
+        // accept connections but never answer
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut streams = Vec::new();
+            for stream in listener.incoming() {
+                streams.push(stream);
+            }
+        });
+
+        let local = tempfile::tempdir().unwrap();
+        crate::GitHelper::init(&local)
+            .unwrap()
+            .repository()
+            .remote("origin", &format!("git://127.0.0.1:{}/repo.git", port))
+            .unwrap();
+
+        let timeout = Duration::from_millis(200);
+        let options = crate::FetchOptions {
+            network: NetworkOptions {
+                operation_timeout: Some(timeout),
+                ..NetworkOptions::default()
+            },
+            ..crate::FetchOptions::default()
+        };
+
+        let started = Instant::now();
+        match crate::fetch(&local, &options) {
+            Err(crate::Error::Timeout { operation, elapsed }) => {
+                assert_eq!("fetch", operation);
+                assert!(elapsed >= timeout);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,243 @@
+//! Detection of repositories owned by another user and configuration of
+//! `safe.directory` to trust them.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use git2::{
+    Config,
+    ConfigLevel,
+    Error as Git2Error,
+    ErrorCode,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// Configuration key listing the directories that are trusted regardless of
+/// their owner.
+const SAFE_DIRECTORY: &str = "safe.directory";
+
+/// Source of the user ids compared to detect repositories owned by another
+/// user.
+pub(crate) trait Ownership {
+    /// Return the user id of the owner of the path. None if it can not be
+    /// determined.
+    fn owner_uid(&self, path: &Path) -> Option<u32>;
+
+    /// Return the effective user id of the current process. None on
+    /// platforms without user ids.
+    fn effective_uid(&self) -> Option<u32>;
+}
+
+/// User ids of the operating system.
+pub(crate) struct SystemOwnership;
+
+#[cfg(unix)]
+impl Ownership for SystemOwnership {
+    fn owner_uid(&self, path: &Path) -> Option<u32> {
+        use std::os::unix::fs::MetadataExt;
+
+        path.metadata().ok().map(|metadata| metadata.uid())
+    }
+
+    fn effective_uid(&self) -> Option<u32> {
+        // SAFETY: geteuid has no preconditions and always succeeds
+        Some(unsafe { libc::geteuid() })
+    }
+}
+
+#[cfg(not(unix))]
+impl Ownership for SystemOwnership {
+    fn owner_uid(&self, _path: &Path) -> Option<u32> {
+        None
+    }
+
+    fn effective_uid(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Return the user id of the owner of the path if it is owned by another
+/// user than the current one.
+pub(crate) fn foreign_owner(ownership: &dyn Ownership, path: &Path) -> Option<u32> {
+    let owner = ownership.owner_uid(path)?;
+
+    match ownership.effective_uid() {
+        Some(uid) if uid != owner => Some(owner),
+        _ => None,
+    }
+}
+
+/// Convert an error of opening the repository at the path. Ownership errors
+/// of libgit2 become Error::UnsafeOwnership with the owner of the path.
+pub(crate) fn open_error(path: &Path, err: Git2Error) -> Error {
+    if err.code() != ErrorCode::Owner {
+        return Error::RepositoryOpen(err);
+    }
+
+    Error::UnsafeOwnership {
+        path: path.to_path_buf(),
+        owner_uid: foreign_owner(&SystemOwnership, path),
+    }
+}
+
+impl GitHelper {
+    /// Trust the repository at the path regardless of its owner by adding it
+    /// to `safe.directory` in the global configuration, like `git config
+    /// --global --add safe.directory <path>`. Only call this when the user
+    /// opted in, as it allows the owner of the repository to run code
+    /// through hooks and filters. Relative paths are resolved against the
+    /// current directory. Adding a path that is already listed does nothing.
+    pub fn add_safe_directory<P: AsRef<Path>>(path: P) -> Result<(), Error> {
+        let mut config = Config::open_default()
+            .and_then(|config| config.open_level(ConfigLevel::Global))
+            .map_err(Error::Config)?;
+
+        add_safe_directory_to(&mut config, path.as_ref())
+    }
+}
+
+/// Add the path to `safe.directory` in the configuration unless it is
+/// already listed.
+pub(crate) fn add_safe_directory_to(config: &mut Config, path: &Path) -> Result<(), Error> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map_err(Error::ResolvePath)?
+            .join(path)
+    };
+    // git expects `/` as separator on every platform
+    let path = path.to_string_lossy().replace('\\', "/");
+
+    if safe_directories(config)?.contains(&PathBuf::from(&path)) {
+        return Ok(());
+    }
+
+    // the regex matches no existing value so a new entry is added
+    config
+        .set_multivar(SAFE_DIRECTORY, "^$^", &path)
+        .map_err(Error::Config)
+}
+
+/// Return the directories listed in `safe.directory` in the configuration.
+pub(crate) fn safe_directories(config: &Config) -> Result<Vec<PathBuf>, Error> {
+    let mut directories = Vec::new();
+    let mut entries = config
+        .multivar(SAFE_DIRECTORY, None)
+        .map_err(Error::Config)?;
+    while let Some(entry) = entries.next() {
+        let entry = entry.map_err(Error::Config)?;
+        directories.push(PathBuf::from(
+            String::from_utf8_lossy(entry.value_bytes()).as_ref(),
+        ));
+    }
+
+    Ok(directories)
+}
+
+#[cfg(test)]
+mod test {
+    use git2::Config;
+    use std::path::{
+        Path,
+        PathBuf,
+    };
+    use tempfile::tempdir;
+
+    use super::{
+        add_safe_directory_to,
+        foreign_owner,
+        safe_directories,
+        Ownership,
+    };
+
+    struct FakeOwnership {
+        owner: Option<u32>,
+        effective: Option<u32>,
+    }
+
+    impl Ownership for FakeOwnership {
+        fn owner_uid(&self, _path: &Path) -> Option<u32> {
+            self.owner
+        }
+
+        fn effective_uid(&self) -> Option<u32> {
+            self.effective
+        }
+    }
+
+    #[test]
+    fn foreign_owner_comparison() {
+        let path = Path::new("/repo");
+        let owner = |owner, effective| foreign_owner(&FakeOwnership { owner, effective }, path);
+
+        assert_eq!(Some(1000), owner(Some(1000), Some(0)));
+        assert_eq!(None, owner(Some(1000), Some(1000)));
+        assert_eq!(None, owner(None, Some(0)));
+        assert_eq!(None, owner(Some(1000), None));
+    }
+
+    #[test]
+    fn add_safe_directory() {
+        let dir = tempdir().unwrap();
+        let mut config = Config::open(&dir.path().join("gitconfig")).unwrap();
+        assert!(safe_directories(&config).unwrap().is_empty());
+
+        add_safe_directory_to(&mut config, Path::new("/srv/first")).unwrap();
+        add_safe_directory_to(&mut config, Path::new("/srv/second")).unwrap();
+        add_safe_directory_to(&mut config, Path::new("/srv/first")).unwrap();
+
+        assert_eq!(
+            vec![PathBuf::from("/srv/first"), PathBuf::from("/srv/second")],
+            safe_directories(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn unsafe_ownership_error() {
+        let err = super::open_error(
+            Path::new("/repo"),
+            git2::Error::new(
+                git2::ErrorCode::Owner,
+                git2::ErrorClass::Config,
+                "repository path '/repo' is not owned by current user",
+            ),
+        );
+
+        match err {
+            crate::Error::UnsafeOwnership { path, .. } => assert_eq!(PathBuf::from("/repo"), path),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        // the owner is only reported if it differs from the current user
+        let dir = tempdir().unwrap();
+        assert_eq!(
+            crate::Error::UnsafeOwnership {
+                path: dir.path().to_path_buf(),
+                owner_uid: None,
+            },
+            super::open_error(
+                dir.path(),
+                git2::Error::new(
+                    git2::ErrorCode::Owner,
+                    git2::ErrorClass::Config,
+                    "repository path is not owned by current user",
+                ),
+            )
+        );
+        assert_eq!(
+            std::io::ErrorKind::PermissionDenied,
+            std::io::Error::from(crate::Error::UnsafeOwnership {
+                path: PathBuf::from("/repo"),
+                owner_uid: Some(1000),
+            })
+            .kind()
+        );
+    }
+}
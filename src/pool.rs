@@ -0,0 +1,151 @@
+//! Pool of opened repositories that can be shared between threads.
+//!
+//! A [`GitHelper`] can be moved to another thread but not used by several
+//! threads at once because the underlying git2 repository is not `Sync`.
+//! Instead of locking a single handle, which would serialize all operations,
+//! a [`RepoPool`] lends every thread its own handle of the same repository.
+
+use std::{
+    ops::Deref,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Mutex,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// Opened handles of the repository in one path. The pool is `Sync` and can
+/// be shared between threads, e.g. behind an `Arc`. Handles are opened when
+/// all existing handles are in use and are kept for reuse when they are
+/// returned.
+pub struct RepoPool {
+    path: PathBuf,
+    idle: Mutex<Vec<GitHelper>>,
+}
+
+/// Handle lent from a [`RepoPool`]. Derefs to the [`GitHelper`] and returns
+/// it to the pool when dropped.
+pub struct PooledHelper<'pool> {
+    pool: &'pool RepoPool,
+    helper: Option<GitHelper>,
+}
+
+impl RepoPool {
+    /// Create a pool for the repository in the given path. The repository
+    /// is opened once to check that it exists.
+    pub fn new<P: AsRef<Path>>(repo_path: P) -> Result<Self, Error> {
+        let helper = GitHelper::open(&repo_path)?;
+
+        Ok(Self {
+            path: repo_path.as_ref().to_path_buf(),
+            idle: Mutex::new(vec![helper]),
+        })
+    }
+
+    /// Lend an idle handle of the repository or open a new one if all
+    /// handles are in use.
+    pub fn get(&self) -> Result<PooledHelper<'_>, Error> {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .pop();
+
+        let helper = match idle {
+            Some(helper) => helper,
+            None => GitHelper::open(&self.path)?,
+        };
+
+        Ok(PooledHelper {
+            pool: self,
+            helper: Some(helper),
+        })
+    }
+
+    /// Return the number of idle handles kept by the pool.
+    pub fn idle(&self) -> usize {
+        self.idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .len()
+    }
+}
+
+impl Deref for PooledHelper<'_> {
+    type Target = GitHelper;
+
+    fn deref(&self) -> &GitHelper {
+        // only taken in drop
+        self.helper.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledHelper<'_> {
+    fn drop(&mut self) {
+        if let Some(helper) = self.helper.take() {
+            self.pool
+                .idle
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .push(helper);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        sync::Arc,
+        thread,
+    };
+    use tempfile::tempdir;
+
+    use super::RepoPool;
+    use crate::LogFilter;
+
+    #[test]
+    fn repo_pool_is_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<RepoPool>();
+    }
+
+    #[test]
+    fn repo_pool_concurrent_reads() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        for index in 0..5 {
+            let name = format!("file_{}", index);
+            fs::write(dir.path().join(&name), "data").unwrap();
+            crate::stage(&dir, &[&name]).unwrap();
+            crate::commit(&dir, &name).unwrap();
+        }
+        fs::write(dir.path().join("untracked"), "data").unwrap();
+
+        let pool = Arc::new(RepoPool::new(&dir).unwrap());
+        let threads = (0..4)
+            .map(|_| {
+                let pool = Arc::clone(&pool);
+                thread::spawn(move || {
+                    for _ in 0..20 {
+                        let helper = pool.get().unwrap();
+                        assert_eq!(1, helper.status().unwrap().len());
+                        assert_eq!(5, helper.log_filtered(&LogFilter::default()).unwrap().len());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let idle = pool.idle();
+        assert!((1..=4).contains(&idle), "{}", idle);
+    }
+}
@@ -0,0 +1,877 @@
+//! Pushing of references to a remote with a result for every pushed
+//! reference.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+};
+
+use git2::{
+    Direction,
+    Oid,
+    PushOptions as Git2PushOptions,
+    Remote,
+};
+
+use crate::{
+    credentials::{
+        default_credential_sources,
+        CredentialResolver,
+        CredentialSource,
+    },
+    network::{
+        Cancel,
+        NetworkOptions,
+    },
+    Error,
+    GitHelper,
+};
+
+/// Options for push.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushOptions {
+    /// Name of the remote to push to.
+    pub remote: String,
+
+    /// Refspecs to push like `refs/heads/master:refs/heads/master`. A leading
+    /// `+` allows non fast-forward updates of that reference. If empty the
+    /// current branch is pushed to the branch with the same name.
+    pub refspecs: Vec<String>,
+
+    /// Push nothing if the client can already tell that any reference would
+    /// be rejected, e.g. because the update is not a fast-forward. The other
+    /// references are then reported as rejected with `atomic push failed`.
+    ///
+    /// This is a client-side pre-check only and not `git push --atomic`:
+    /// libgit2 does not support the atomic capability of the git protocol.
+    /// If the remote or one of its hooks rejects a reference, the other
+    /// references are still updated.
+    pub atomic: bool,
+
+    /// Fail with Error::PushRejected listing every rejected reference with
+    /// the reason instead of returning the rejections in the report.
+    pub fail_on_reject: bool,
+
+    /// Sources tried in order when the remote requires authentication.
+    pub credentials: Vec<CredentialSource>,
+
+    /// Options for the transport to the remote.
+    pub network: NetworkOptions,
+}
+
+impl Default for PushOptions {
+    fn default() -> Self {
+        Self {
+            remote: "origin".to_string(),
+            refspecs: Vec::new(),
+            atomic: false,
+            fail_on_reject: false,
+            credentials: default_credential_sources(),
+            network: NetworkOptions::default(),
+        }
+    }
+}
+
+/// Outcome of pushing a single reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushStatus {
+    /// The remote reference was updated.
+    Updated,
+
+    /// The remote reference already pointed to the pushed object.
+    UpToDate,
+
+    /// The update of the remote reference was rejected. Contains the reason
+    /// reported by the server or detected before pushing.
+    Rejected(String),
+}
+
+/// Outcome of pushing a single reference to a remote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefPushResult {
+    /// Name of the reference on the remote.
+    pub reference: String,
+
+    /// Outcome of the push.
+    pub status: PushStatus,
+}
+
+/// Result of push.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PushReport {
+    /// Outcome for every pushed reference in the order of the refspecs.
+    pub refs: Vec<RefPushResult>,
+}
+
+impl PushReport {
+    /// True if no reference was rejected.
+    pub fn is_ok(&self) -> bool {
+        self.rejected().next().is_none()
+    }
+
+    /// Return the results of all rejected references.
+    pub fn rejected(&self) -> impl Iterator<Item = &RefPushResult> {
+        self.refs
+            .iter()
+            .filter(|result| matches!(result.status, PushStatus::Rejected(_)))
+    }
+}
+
+/// Predicted outcome of pushing a single reference to a remote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefPushPreview {
+    /// Name of the reference on the remote.
+    pub reference: String,
+
+    /// Object the reference points to on the remote. None if the remote
+    /// does not have the reference.
+    pub remote: Option<Oid>,
+
+    /// Object the reference would point to after the push. None if the
+    /// reference would be deleted.
+    pub local: Option<Oid>,
+
+    /// True if the reference would be created or the object on the remote
+    /// is an ancestor of the local one.
+    pub fast_forward: bool,
+
+    /// Predicted outcome of the push.
+    pub status: PushStatus,
+}
+
+/// Result of push_dry_run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PushPreview {
+    /// Predicted outcome for every reference in the order of the refspecs.
+    pub refs: Vec<RefPushPreview>,
+}
+
+struct Update {
+    refspec: String,
+    destination: String,
+    local: Option<Oid>,
+    force: bool,
+}
+
+impl GitHelper {
+    /// Push references to a remote and report the outcome for every
+    /// reference. Rejected references do not fail the push unless
+    /// `fail_on_reject` is set.
+    pub fn push(&self, options: &PushOptions) -> Result<PushReport, Error> {
+        let options = options.clone();
+
+        self.with_timeout(
+            "push",
+            options.network.operation_timeout,
+            move |helper, cancel| helper.push_cancellable(&options, cancel),
+        )
+    }
+
+    /// Push the refspecs to the remote unless the client can already tell
+    /// that one of the references would be rejected, e.g. because the update
+    /// is not a fast-forward. Fails with Error::PushRejected listing the
+    /// rejected references then and nothing is pushed.
+    ///
+    /// This is a client-side pre-check only and not `git push --atomic`:
+    /// libgit2 does not support the atomic capability of the git protocol.
+    /// If the remote or one of its hooks rejects a reference, the other
+    /// references are still updated and the rejection is returned as
+    /// Error::PushRejected. Credentials are resolved from the default
+    /// sources.
+    pub fn push_prechecked(&self, remote: &str, refspecs: &[&str]) -> Result<PushReport, Error> {
+        self.push(&PushOptions {
+            remote: remote.to_string(),
+            refspecs: refspecs.iter().map(|refspec| refspec.to_string()).collect(),
+            atomic: true,
+            fail_on_reject: true,
+            ..PushOptions::default()
+        })
+    }
+
+    /// Push every local reference to the same name on the remote like `git
+    /// push --mirror`. References are force pushed and references of the
+    /// remote that do not exist locally are deleted. The `refspecs` of the
+    /// options are ignored, symbolic references are not pushed.
+    pub fn push_mirror(&self, options: &PushOptions) -> Result<PushReport, Error> {
+        let options = options.clone();
+
+        self.with_timeout(
+            "push",
+            options.network.operation_timeout,
+            move |helper, cancel| helper.push_mirror_cancellable(&options, cancel),
+        )
+    }
+
+    fn push_mirror_cancellable(
+        &self,
+        options: &PushOptions,
+        cancel: &Cancel,
+    ) -> Result<PushReport, Error> {
+        let repository = self.repository();
+        let mut remote = repository
+            .find_remote(&options.remote)
+            .map_err(|_| Error::RemoteNotFound(options.remote.clone()))?;
+
+        let settings = options.network.apply_global()?;
+        let resolver = RefCell::new(CredentialResolver::new(repository, &options.credentials)?);
+        let remote_heads = self.remote_heads(&mut remote, options, &resolver, cancel)?;
+
+        let mut local = Vec::new();
+        for reference in repository
+            .references()
+            .map_err(Error::RepositoryReferences)?
+        {
+            let reference = reference.map_err(Error::RepositoryReferences)?;
+            if reference.target().is_some() {
+                local.push(String::from_utf8_lossy(reference.name_bytes()).into_owned());
+            }
+        }
+
+        let mut deleted = remote_heads
+            .keys()
+            .filter(|name| name.starts_with("refs/") && !name.ends_with("^{}"))
+            .filter(|name| !local.contains(name))
+            .map(|name| format!(":{}", name))
+            .collect::<Vec<_>>();
+        deleted.sort();
+
+        let refspecs = local
+            .iter()
+            .map(|name| format!("+{}:{}", name, name))
+            .chain(deleted)
+            .collect::<Vec<_>>();
+
+        if refspecs.is_empty() {
+            return Ok(PushReport::default());
+        }
+
+        // the push applies the settings again and would wait for this lock
+        drop(settings);
+        self.push_cancellable(
+            &PushOptions {
+                refspecs,
+                ..options.clone()
+            },
+            cancel,
+        )
+    }
+
+    fn push_cancellable(
+        &self,
+        options: &PushOptions,
+        cancel: &Cancel,
+    ) -> Result<PushReport, Error> {
+        let repository = self.repository();
+        let mut remote = repository
+            .find_remote(&options.remote)
+            .map_err(|_| Error::RemoteNotFound(options.remote.clone()))?;
+
+        let updates = self.push_updates(options)?;
+        let _settings = options.network.apply_global()?;
+        let resolver = RefCell::new(CredentialResolver::new(repository, &options.credentials)?);
+
+        let remote_heads = self.remote_heads(&mut remote, options, &resolver, cancel)?;
+        let mut statuses = self.predict_statuses(options, &updates, &remote_heads);
+
+        let refspecs = updates
+            .iter()
+            .filter(|update| !statuses.contains_key(&update.destination))
+            .map(|update| update.refspec.as_str())
+            .collect::<Vec<_>>();
+
+        if !refspecs.is_empty() {
+            let server_statuses = RefCell::new(HashMap::new());
+
+            let mut callbacks = CredentialResolver::callbacks(&resolver);
+            options.network.install(&mut callbacks);
+            cancel.install(&mut callbacks);
+            callbacks.push_update_reference(|reference, status| {
+                let status = match status {
+                    Some(message) => PushStatus::Rejected(message.to_string()),
+                    None => PushStatus::Updated,
+                };
+                server_statuses
+                    .borrow_mut()
+                    .insert(reference.to_string(), status);
+                Ok(())
+            });
+
+            let mut push_options = Git2PushOptions::new();
+            push_options
+                .remote_callbacks(callbacks)
+                .proxy_options(options.network.proxy_options());
+
+            remote
+                .push(&refspecs, Some(&mut push_options))
+                .map_err(|err| resolver.borrow().error(err, Error::RemotePush))?;
+            drop(push_options);
+
+            statuses.extend(server_statuses.into_inner());
+        }
+
+        let report = PushReport {
+            refs: updates
+                .into_iter()
+                .map(|update| RefPushResult {
+                    status: statuses
+                        .remove(&update.destination)
+                        .unwrap_or(PushStatus::Updated),
+                    reference: update.destination,
+                })
+                .collect(),
+        };
+
+        if options.fail_on_reject && !report.is_ok() {
+            return Err(Error::PushRejected(
+                report
+                    .rejected()
+                    .filter_map(|result| match &result.status {
+                        PushStatus::Rejected(reason) => {
+                            Some((result.reference.clone(), reason.clone()))
+                        }
+                        _ => None,
+                    })
+                    .collect(),
+            ));
+        }
+
+        Ok(report)
+    }
+
+    /// Return what pushing would do without pushing anything. The remote is
+    /// asked for its references to predict for every reference whether it
+    /// would be updated, is up to date or would be rejected. The remote can
+    /// still reject updates the prediction allows, e.g. in hooks.
+    pub fn push_dry_run(&self, options: &PushOptions) -> Result<PushPreview, Error> {
+        let options = options.clone();
+
+        self.with_timeout(
+            "push",
+            options.network.operation_timeout,
+            move |helper, cancel| helper.push_dry_run_cancellable(&options, cancel),
+        )
+    }
+
+    fn push_dry_run_cancellable(
+        &self,
+        options: &PushOptions,
+        cancel: &Cancel,
+    ) -> Result<PushPreview, Error> {
+        let repository = self.repository();
+        let mut remote = repository
+            .find_remote(&options.remote)
+            .map_err(|_| Error::RemoteNotFound(options.remote.clone()))?;
+
+        let updates = self.push_updates(options)?;
+        let _settings = options.network.apply_global()?;
+        let resolver = RefCell::new(CredentialResolver::new(repository, &options.credentials)?);
+
+        let remote_heads = self.remote_heads(&mut remote, options, &resolver, cancel)?;
+        let mut statuses = self.predict_statuses(options, &updates, &remote_heads);
+
+        let refs = updates
+            .into_iter()
+            .map(|update| {
+                let remote_oid = remote_heads.get(&update.destination).copied();
+                let fast_forward = match (update.local, remote_oid) {
+                    (Some(local), Some(remote_oid)) => self.is_fast_forward(local, remote_oid),
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+
+                RefPushPreview {
+                    status: statuses
+                        .remove(&update.destination)
+                        .unwrap_or(PushStatus::Updated),
+                    reference: update.destination,
+                    remote: remote_oid,
+                    local: update.local,
+                    fast_forward,
+                }
+            })
+            .collect();
+
+        Ok(PushPreview { refs })
+    }
+
+    /// Connect to the remote and list its references with the objects they
+    /// point to.
+    fn remote_heads(
+        &self,
+        remote: &mut Remote<'_>,
+        options: &PushOptions,
+        resolver: &RefCell<CredentialResolver>,
+        cancel: &Cancel,
+    ) -> Result<HashMap<String, Oid>, Error> {
+        let mut callbacks = CredentialResolver::callbacks(resolver);
+        options.network.install(&mut callbacks);
+        cancel.install(&mut callbacks);
+
+        let connection = remote
+            .connect_auth(
+                Direction::Push,
+                Some(callbacks),
+                Some(options.network.proxy_options()),
+            )
+            .map_err(|err| resolver.borrow().error(err, Error::RemoteConnect))?;
+
+        let heads = connection
+            .list()
+            .map_err(Error::RemoteList)?
+            .iter()
+            .map(|head| (head.name().to_string(), head.oid()))
+            .collect();
+
+        Ok(heads)
+    }
+
+    /// Return the status of the updates whose outcome is known before
+    /// pushing: up to date references and rejected non fast-forward
+    /// updates. With `atomic` every update is rejected if one is.
+    fn predict_statuses(
+        &self,
+        options: &PushOptions,
+        updates: &[Update],
+        remote_heads: &HashMap<String, Oid>,
+    ) -> HashMap<String, PushStatus> {
+        let mut statuses = HashMap::new();
+        for update in updates {
+            let remote_oid = remote_heads.get(&update.destination).copied();
+
+            let status = if remote_oid == update.local {
+                Some(PushStatus::UpToDate)
+            } else if update.force {
+                None
+            } else {
+                match (update.local, remote_oid) {
+                    (Some(local), Some(remote_oid)) if !self.is_fast_forward(local, remote_oid) => {
+                        Some(PushStatus::Rejected("non-fast-forward".to_string()))
+                    }
+                    _ => None,
+                }
+            };
+
+            if let Some(status) = status {
+                statuses.insert(update.destination.clone(), status);
+            }
+        }
+
+        let any_rejected = statuses
+            .values()
+            .any(|status| matches!(status, PushStatus::Rejected(_)));
+
+        if options.atomic && any_rejected {
+            for update in updates {
+                statuses
+                    .entry(update.destination.clone())
+                    .or_insert_with(|| PushStatus::Rejected("atomic push failed".to_string()));
+            }
+        }
+
+        statuses
+    }
+
+    /// Resolve the refspecs of the options into full reference names and the
+    /// local objects they point to.
+    fn push_updates(&self, options: &PushOptions) -> Result<Vec<Update>, Error> {
+        let repository = self.repository();
+
+        if options.refspecs.is_empty() {
+            let head = repository.head().map_err(Error::RepositoryHead)?;
+            if !head.is_branch() {
+                return Err(Error::HeadNotBranch);
+            }
+
+            let name = String::from_utf8_lossy(head.name_bytes()).into_owned();
+            return Ok(vec![Update {
+                refspec: format!("{}:{}", name, name),
+                destination: name,
+                local: head.target(),
+                force: false,
+            }]);
+        }
+
+        options
+            .refspecs
+            .iter()
+            .map(|refspec| {
+                let (force, refspec) = match refspec.strip_prefix('+') {
+                    Some(refspec) => (true, refspec),
+                    None => (false, refspec.as_str()),
+                };
+
+                let (source, destination) = match refspec.split_once(':') {
+                    Some((source, destination)) => (source, destination),
+                    None => (refspec, refspec),
+                };
+
+                let local = if source.is_empty() {
+                    None
+                } else {
+                    let object = repository
+                        .revparse_single(source)
+                        .map_err(|_| Error::RevisionNotFound(source.to_string()))?;
+                    Some(object.id())
+                };
+
+                let destination = if destination.starts_with("refs/") {
+                    destination.to_string()
+                } else {
+                    format!("refs/heads/{}", destination)
+                };
+
+                Ok(Update {
+                    refspec: format!("{}{}:{}", if force { "+" } else { "" }, source, destination),
+                    destination,
+                    local,
+                    force,
+                })
+            })
+            .collect()
+    }
+
+    /// True if the remote object is known locally and an ancestor of the
+    /// local object.
+    fn is_fast_forward(&self, local: Oid, remote: Oid) -> bool {
+        let repository = self.repository();
+
+        repository
+            .merge_base(local, remote)
+            .map(|base| base == remote)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::{
+        Repository,
+        ResetType,
+    };
+    use std::fs;
+    use tempfile::{
+        tempdir,
+        TempDir,
+    };
+
+    use super::{
+        PushOptions,
+        PushStatus,
+        RefPushPreview,
+    };
+    use crate::GitHelper;
+
+    /// Local repository with the branches master, feature and stable and a
+    /// bare remote as origin. The remote has all branches, but its feature
+    /// branch points to a commit that is not an ancestor of the local one.
+    /// Master has one local commit that is not pushed yet.
+    fn diverged_remote() -> (TempDir, TempDir) {
+        let local = tempdir().unwrap();
+        let remote = tempdir().unwrap();
+        Repository::init_bare(&remote).unwrap();
+
+        let helper = GitHelper::init(&local).unwrap();
+        let repository = helper.repository();
+        repository
+            .remote("origin", remote.path().to_str().unwrap())
+            .unwrap();
+
+        fs::write(local.path().join("first_file"), "first data").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Added first_file").unwrap();
+
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        repository.branch("feature", &head, false).unwrap();
+        repository.branch("stable", &head, false).unwrap();
+
+        repository.set_head("refs/heads/feature").unwrap();
+        fs::write(local.path().join("feature_file"), "remote feature").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Remote feature").unwrap();
+
+        let options = PushOptions {
+            refspecs: vec![
+                "refs/heads/master".to_string(),
+                "refs/heads/feature".to_string(),
+                "refs/heads/stable".to_string(),
+            ],
+            ..PushOptions::default()
+        };
+        assert!(helper.push(&options).unwrap().is_ok());
+
+        // rewrite feature so the remote branch is no longer an ancestor
+        repository
+            .reset(head.as_object(), ResetType::Hard, None)
+            .unwrap();
+        fs::write(local.path().join("feature_file"), "local feature").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Local feature").unwrap();
+
+        repository.set_head("refs/heads/master").unwrap();
+        repository
+            .reset(head.as_object(), ResetType::Hard, None)
+            .unwrap();
+        fs::write(local.path().join("second_file"), "second data").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Added second_file").unwrap();
+
+        (local, remote)
+    }
+
+    fn all_branches() -> PushOptions {
+        PushOptions {
+            refspecs: vec![
+                "refs/heads/master:refs/heads/master".to_string(),
+                "feature".to_string(),
+                "refs/heads/stable".to_string(),
+            ],
+            ..PushOptions::default()
+        }
+    }
+
+    fn remote_target(remote: &TempDir, branch: &str) -> git2::Oid {
+        Repository::open_bare(remote)
+            .unwrap()
+            .refname_to_id(branch)
+            .unwrap()
+    }
+
+    #[test]
+    fn push_reports_every_ref() {
+        let (local, remote) = diverged_remote();
+
+        let report = crate::push(&local, &all_branches()).unwrap();
+        assert!(!report.is_ok());
+
+        let statuses = report
+            .refs
+            .iter()
+            .map(|result| (result.reference.as_str(), result.status.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("refs/heads/master", PushStatus::Updated),
+                (
+                    "refs/heads/feature",
+                    PushStatus::Rejected("non-fast-forward".to_string())
+                ),
+                ("refs/heads/stable", PushStatus::UpToDate),
+            ],
+            statuses
+        );
+
+        let helper = GitHelper::open(&local).unwrap();
+        assert_eq!(
+            helper
+                .repository()
+                .refname_to_id("refs/heads/master")
+                .unwrap(),
+            remote_target(&remote, "refs/heads/master")
+        );
+        assert_ne!(
+            helper
+                .repository()
+                .refname_to_id("refs/heads/feature")
+                .unwrap(),
+            remote_target(&remote, "refs/heads/feature")
+        );
+    }
+
+    #[test]
+    fn push_dry_run() {
+        let (local, remote) = diverged_remote();
+        let remote_repository = Repository::open_bare(&remote).unwrap();
+        let before = crate::GitHelper::open(&remote).unwrap().list_references();
+
+        let preview = crate::push_dry_run(&local, &all_branches()).unwrap();
+
+        let helper = GitHelper::open(&local).unwrap();
+        let local_target = |branch| helper.repository().refname_to_id(branch).unwrap();
+        assert_eq!(
+            vec![
+                RefPushPreview {
+                    reference: "refs/heads/master".to_string(),
+                    remote: Some(remote_target(&remote, "refs/heads/master")),
+                    local: Some(local_target("refs/heads/master")),
+                    fast_forward: true,
+                    status: PushStatus::Updated,
+                },
+                RefPushPreview {
+                    reference: "refs/heads/feature".to_string(),
+                    remote: Some(remote_target(&remote, "refs/heads/feature")),
+                    local: Some(local_target("refs/heads/feature")),
+                    fast_forward: false,
+                    status: PushStatus::Rejected("non-fast-forward".to_string()),
+                },
+                RefPushPreview {
+                    reference: "refs/heads/stable".to_string(),
+                    remote: Some(remote_target(&remote, "refs/heads/stable")),
+                    local: Some(local_target("refs/heads/stable")),
+                    fast_forward: true,
+                    status: PushStatus::UpToDate,
+                },
+            ],
+            preview.refs
+        );
+
+        assert_eq!(
+            before,
+            crate::GitHelper::open(&remote).unwrap().list_references()
+        );
+        assert!(remote_repository
+            .find_commit(local_target("refs/heads/master"))
+            .is_err());
+    }
+
+    #[test]
+    fn push_atomic() {
+        let (local, remote) = diverged_remote();
+        let master_before = remote_target(&remote, "refs/heads/master");
+
+        let options = PushOptions {
+            atomic: true,
+            ..all_branches()
+        };
+        let report = crate::push(&local, &options).unwrap();
+
+        assert_eq!(
+            PushStatus::Rejected("atomic push failed".to_string()),
+            report.refs[0].status
+        );
+        assert_eq!(master_before, remote_target(&remote, "refs/heads/master"));
+    }
+
+    #[test]
+    fn push_prechecked_rejected() {
+        let (local, remote) = diverged_remote();
+        let master_before = remote_target(&remote, "refs/heads/master");
+        let feature_before = remote_target(&remote, "refs/heads/feature");
+
+        assert_eq!(
+            Err(crate::Error::PushRejected(vec![
+                (
+                    "refs/heads/master".to_string(),
+                    "atomic push failed".to_string()
+                ),
+                (
+                    "refs/heads/feature".to_string(),
+                    "non-fast-forward".to_string()
+                ),
+            ])),
+            crate::push_prechecked(
+                &local,
+                "origin",
+                &["refs/heads/master", "refs/heads/feature"]
+            )
+        );
+        assert_eq!(master_before, remote_target(&remote, "refs/heads/master"));
+        assert_eq!(feature_before, remote_target(&remote, "refs/heads/feature"));
+
+        let report = crate::push_prechecked(&local, "origin", &["refs/heads/master"]).unwrap();
+        assert_eq!(PushStatus::Updated, report.refs[0].status);
+    }
+
+    #[test]
+    fn push_prechecked_rejected_by_remote() {
+        let (local, remote) = diverged_remote();
+        let master = GitHelper::open(&local)
+            .unwrap()
+            .repository()
+            .refname_to_id("refs/heads/master")
+            .unwrap();
+
+        // the locked reference can not be updated by the remote which the
+        // client can not predict, so the other reference is still pushed
+        fs::write(remote.path().join("refs/heads/copy.lock"), "").unwrap();
+        match crate::push_prechecked(
+            &local,
+            "origin",
+            &["refs/heads/master", "refs/heads/master:refs/heads/copy"],
+        ) {
+            Err(crate::Error::PushRejected(rejected)) => {
+                assert_eq!(1, rejected.len());
+                assert_eq!("refs/heads/copy", rejected[0].0);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert_eq!(master, remote_target(&remote, "refs/heads/master"));
+        assert!(Repository::open_bare(&remote)
+            .unwrap()
+            .refname_to_id("refs/heads/copy")
+            .is_err());
+    }
+
+    #[test]
+    fn push_force_and_fail_on_reject() {
+        let (local, remote) = diverged_remote();
+
+        let options = PushOptions {
+            fail_on_reject: true,
+            ..all_branches()
+        };
+        match crate::push(&local, &options) {
+            Err(crate::Error::PushRejected(refs)) => assert_eq!(
+                vec![(
+                    "refs/heads/feature".to_string(),
+                    "non-fast-forward".to_string()
+                )],
+                refs
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let options = PushOptions {
+            refspecs: vec!["+refs/heads/feature".to_string()],
+            fail_on_reject: true,
+            ..PushOptions::default()
+        };
+        let report = crate::push(&local, &options).unwrap();
+        assert_eq!(PushStatus::Updated, report.refs[0].status);
+
+        let helper = GitHelper::open(&local).unwrap();
+        assert_eq!(
+            helper
+                .repository()
+                .refname_to_id("refs/heads/feature")
+                .unwrap(),
+            remote_target(&remote, "refs/heads/feature")
+        );
+    }
+
+    #[test]
+    fn push_mirror() {
+        let (local, remote) = diverged_remote();
+
+        let remote_repository = Repository::open_bare(&remote).unwrap();
+        let stable = remote_target(&remote, "refs/heads/stable");
+        remote_repository
+            .reference("refs/heads/obsolete", stable, false, "obsolete")
+            .unwrap();
+
+        let helper = GitHelper::open(&local).unwrap();
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        repository
+            .tag_lightweight("v1.0", head.as_object(), false)
+            .unwrap();
+
+        let report = crate::push_mirror(&local, &PushOptions::default()).unwrap();
+        assert!(report.is_ok());
+        assert!(report
+            .refs
+            .iter()
+            .any(|result| result.reference == "refs/heads/obsolete"));
+
+        // the remote-tracking references of the local repository are
+        // updated by the push itself after they were pushed
+        let branches_and_tags = |dir: &TempDir| {
+            crate::list_references(dir)
+                .unwrap()
+                .into_iter()
+                .filter(|(name, _)| !name.starts_with("refs/remotes/"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(branches_and_tags(&local), branches_and_tags(&remote));
+    }
+}
@@ -0,0 +1,72 @@
+//! Listing of all references of a repository.
+
+use git2::Oid;
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+impl GitHelper {
+    /// List all references sorted by their full name together with the oid
+    /// they point to. Symbolic references are resolved to the oid of their
+    /// final target, annotated tags point to the tag object.
+    pub fn list_references(&self) -> Result<Vec<(String, Oid)>, Error> {
+        let mut references = Vec::new();
+
+        for reference in self
+            .repository()
+            .references()
+            .map_err(Error::RepositoryReferences)?
+        {
+            let reference = reference.map_err(Error::RepositoryReferences)?;
+            let name = String::from_utf8_lossy(reference.name_bytes()).into_owned();
+
+            let resolved = reference.resolve().map_err(Error::RepositoryReferences)?;
+            if let Some(oid) = resolved.target() {
+                references.push((name, oid));
+            }
+        }
+
+        references.sort();
+
+        Ok(references)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::tempdir;
+
+    use crate::GitHelper;
+
+    #[test]
+    fn list_references() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        assert!(crate::list_references(&dir).unwrap().is_empty());
+
+        fs::write(dir.path().join("file"), "data").unwrap();
+        helper.stage(&["file"]).unwrap();
+        helper.commit("Added file").unwrap();
+
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        repository
+            .tag_lightweight("v1.0", head.as_object(), false)
+            .unwrap();
+        repository
+            .reference_symbolic("refs/heads/alias", "refs/heads/master", false, "alias")
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                ("refs/heads/alias".to_string(), head.id()),
+                ("refs/heads/master".to_string(), head.id()),
+                ("refs/tags/v1.0".to_string(), head.id()),
+            ],
+            crate::list_references(&dir).unwrap()
+        );
+    }
+}
@@ -0,0 +1,454 @@
+//! Inspection and setup of the remotes configured for a repository.
+
+use std::{
+    cell::RefCell,
+    path::Path,
+};
+
+use git2::{
+    build::RepoBuilder,
+    Config,
+    Direction,
+    ErrorCode,
+    FetchOptions as Git2FetchOptions,
+    Repository,
+};
+
+use crate::{
+    credentials::{
+        default_credential_sources,
+        CredentialResolver,
+        CredentialSource,
+    },
+    fetch::MIRROR_REFSPEC,
+    network::NetworkOptions,
+    ownership::open_error,
+    Error,
+    GitHelper,
+};
+
+/// Options for clone_or_open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloneOptions {
+    /// Point `origin` of an existing repository to the url if it points
+    /// somewhere else instead of failing with Error::RemoteMismatch.
+    pub fix_remote: bool,
+
+    /// Only clone this many commits from the tips of the branches like `git
+    /// clone --depth`. If None the complete history is cloned.
+    pub depth: Option<i32>,
+
+    /// Sources tried in order when the remote requires authentication.
+    pub credentials: Vec<CredentialSource>,
+
+    /// Options for the transport to the remote. The operation timeout is not
+    /// supported for clones.
+    pub network: NetworkOptions,
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self {
+            fix_remote: false,
+            depth: None,
+            credentials: default_credential_sources(),
+            network: NetworkOptions::default(),
+        }
+    }
+}
+
+/// What ensure_remote did to the remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteAction {
+    /// The remote did not exist and was added.
+    Added,
+
+    /// The remote pointed to another url and was changed.
+    Updated,
+
+    /// The remote already pointed to the url.
+    Unchanged,
+}
+
+impl GitHelper {
+    /// Clone the repository at the url into the path and open it. If the
+    /// path already is a repository it is opened instead and its `origin`
+    /// has to point to the url, otherwise this fails with
+    /// Error::RemoteMismatch unless `fix_remote` is set. Calling this again
+    /// for the same url and path does nothing but open the repository.
+    pub fn clone_or_open<P: AsRef<Path>>(
+        url: &str,
+        repo_path: P,
+        options: &CloneOptions,
+    ) -> Result<Self, Error> {
+        let repository = match Repository::open(&repo_path) {
+            Ok(repository) => repository,
+            Err(err) if err.code() == ErrorCode::NotFound => {
+                return Self::clone(url, repo_path.as_ref(), options, false)
+            }
+            Err(err) => return Err(open_error(repo_path.as_ref(), err)),
+        };
+        let helper = Self::from_repository(repository);
+
+        if options.fix_remote {
+            helper.ensure_remote("origin", url)?;
+            return Ok(helper);
+        }
+
+        let found = match helper.repository().find_remote("origin") {
+            Ok(remote) => Some(String::from_utf8_lossy(remote.url_bytes()).into_owned()),
+            Err(_) => None,
+        };
+        if found.as_deref() != Some(url) {
+            return Err(Error::RemoteMismatch {
+                expected: url.to_string(),
+                found,
+            });
+        }
+
+        Ok(helper)
+    }
+
+    /// Add the named remote with the url or change its url if it points
+    /// somewhere else. Returns what was done.
+    pub fn ensure_remote(&self, name: &str, url: &str) -> Result<RemoteAction, Error> {
+        let repository = self.repository();
+
+        match repository.find_remote(name) {
+            Ok(remote) if remote.url_bytes() == url.as_bytes() => Ok(RemoteAction::Unchanged),
+            Ok(_) => repository
+                .remote_set_url(name, url)
+                .map(|_| RemoteAction::Updated)
+                .map_err(Error::RemoteConfig),
+            Err(_) => repository
+                .remote(name, url)
+                .map(|_| RemoteAction::Added)
+                .map_err(Error::RemoteConfig),
+        }
+    }
+
+    /// Clone the repository at the url into a bare repository at the path
+    /// like `git clone --mirror`. Every reference including tags and notes
+    /// is fetched to the same name and `origin` is configured as mirror, so
+    /// fetching with `mirror` set keeps the repository in sync. Fails if
+    /// the path already exists and is not empty.
+    pub fn clone_mirror<P: AsRef<Path>>(
+        url: &str,
+        repo_path: P,
+        options: &CloneOptions,
+    ) -> Result<Self, Error> {
+        Self::clone(url, repo_path.as_ref(), options, true)
+    }
+
+    fn clone(
+        url: &str,
+        repo_path: &Path,
+        options: &CloneOptions,
+        mirror: bool,
+    ) -> Result<Self, Error> {
+        let _settings = options.network.apply_global()?;
+        let config = Config::open_default().map_err(Error::Config)?;
+        let resolver = RefCell::new(CredentialResolver::with_config(
+            config,
+            &options.credentials,
+        ));
+
+        let mut callbacks = CredentialResolver::callbacks(&resolver);
+        options.network.install(&mut callbacks);
+
+        let mut fetch_options = Git2FetchOptions::new();
+        fetch_options
+            .remote_callbacks(callbacks)
+            .proxy_options(options.network.proxy_options());
+        if let Some(depth) = options.depth {
+            fetch_options.depth(depth);
+        }
+
+        let mut builder = RepoBuilder::new();
+        builder.fetch_options(fetch_options);
+        if mirror {
+            builder.bare(true).remote_create(|repository, name, url| {
+                let remote = repository.remote_with_fetch(name, url, MIRROR_REFSPEC)?;
+                repository
+                    .config()?
+                    .set_bool(&format!("remote.{}.mirror", name), true)?;
+
+                Ok(remote)
+            });
+        }
+
+        let repository = builder
+            .clone(url, repo_path)
+            .map_err(|err| resolver.borrow().error(err, Error::Clone))?;
+
+        // libgit2 always records the default branch of the remote, a mirror
+        // only has the references of the remote.
+        if mirror {
+            if let Ok(mut reference) = repository.find_reference("refs/remotes/origin/HEAD") {
+                reference.delete().map_err(Error::Clone)?;
+            }
+        }
+
+        Ok(Self::from_repository(repository))
+    }
+
+    /// Return the fetch url of the named remote after applying the
+    /// `insteadOf` rules of the configuration. Fails with
+    /// Error::RemoteNotFound if the remote does not exist and with
+    /// Error::RemoteNoUrl if the remote only has a push url.
+    pub fn remote_url(&self, name: &str) -> Result<String, Error> {
+        let remote = self
+            .repository()
+            .find_remote(name)
+            .map_err(|_| Error::RemoteNotFound(name.to_string()))?;
+
+        match remote.url_bytes() {
+            [] => Err(Error::RemoteNoUrl(name.to_string())),
+            url => Ok(String::from_utf8_lossy(url).into_owned()),
+        }
+    }
+
+    /// Connect to the named remote and return the branch its HEAD points to
+    /// without the `refs/heads/` prefix, e.g. `main`. Fails with
+    /// Error::NoRemoteHead if the remote does not advertise a HEAD.
+    /// Credentials are resolved from the default sources.
+    pub fn remote_default_branch(&self, name: &str) -> Result<String, Error> {
+        let repository = self.repository();
+        let mut remote = repository
+            .find_remote(name)
+            .map_err(|_| Error::RemoteNotFound(name.to_string()))?;
+
+        let resolver = RefCell::new(CredentialResolver::new(
+            repository,
+            &default_credential_sources(),
+        )?);
+        let connection = remote
+            .connect_auth(
+                Direction::Fetch,
+                Some(CredentialResolver::callbacks(&resolver)),
+                None,
+            )
+            .map_err(|err| resolver.borrow().error(err, Error::RemoteConnect))?;
+
+        let head = match connection.default_branch() {
+            Ok(head) => head,
+            Err(err) if err.code() == ErrorCode::NotFound => {
+                return Err(Error::NoRemoteHead(name.to_string()))
+            }
+            Err(err) => return Err(Error::RemoteList(err)),
+        };
+        let head = String::from_utf8_lossy(&head);
+
+        Ok(head
+            .strip_prefix("refs/heads/")
+            .unwrap_or(&head)
+            .to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::{
+        tempdir,
+        TempDir,
+    };
+
+    use super::{
+        CloneOptions,
+        RemoteAction,
+    };
+    use crate::GitHelper;
+
+    /// Create a repository with a single commit to clone from. Returns the
+    /// directory and its url.
+    fn upstream() -> (TempDir, String) {
+        let dir = tempdir().unwrap();
+        GitHelper::init(&dir)
+            .unwrap()
+            .commit_empty("Initial commit")
+            .unwrap();
+        let url = dir.path().to_str().unwrap().to_string();
+
+        (dir, url)
+    }
+
+    #[test]
+    fn remote_default_branch() {
+        let (upstream, url) = upstream();
+        let helper = GitHelper::open(&upstream).unwrap();
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        repository.branch("trunk", &head, false).unwrap();
+        repository.set_head("refs/heads/trunk").unwrap();
+
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        crate::ensure_remote(&dir, "origin", &url).unwrap();
+        assert_eq!(
+            "trunk",
+            crate::remote_default_branch(&dir, "origin").unwrap()
+        );
+
+        let empty = tempdir().unwrap();
+        crate::init(&empty).unwrap();
+        crate::ensure_remote(&dir, "empty", empty.path().to_str().unwrap()).unwrap();
+        assert_eq!(
+            Err(crate::Error::NoRemoteHead("empty".to_string())),
+            crate::remote_default_branch(&dir, "empty")
+        );
+    }
+
+    #[test]
+    fn clone_or_open() {
+        let (upstream, url) = upstream();
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("clone");
+
+        let helper = GitHelper::clone_or_open(&url, &path, &CloneOptions::default()).unwrap();
+        let head = helper.repository().head().unwrap().target().unwrap();
+        assert_eq!(
+            GitHelper::open(&upstream)
+                .unwrap()
+                .repository()
+                .head()
+                .unwrap()
+                .target()
+                .unwrap(),
+            head
+        );
+        assert_eq!(url, crate::remote_url(&path, "origin").unwrap());
+
+        // an existing clone is only opened
+        fs::write(path.join("local_file"), "local data").unwrap();
+        crate::clone_or_open(&url, &path, &CloneOptions::default()).unwrap();
+        assert!(path.join("local_file").exists());
+    }
+
+    #[test]
+    fn clone_or_open_mismatch() {
+        let (_upstream, url) = upstream();
+        let (_other, other_url) = upstream();
+        let dir = tempdir().unwrap();
+        crate::clone_or_open(&url, &dir, &CloneOptions::default()).unwrap();
+
+        assert_eq!(
+            Err(crate::Error::RemoteMismatch {
+                expected: other_url.clone(),
+                found: Some(url),
+            }),
+            crate::clone_or_open(&other_url, &dir, &CloneOptions::default())
+        );
+
+        let options = CloneOptions {
+            fix_remote: true,
+            ..CloneOptions::default()
+        };
+        crate::clone_or_open(&other_url, &dir, &options).unwrap();
+        assert_eq!(other_url, crate::remote_url(&dir, "origin").unwrap());
+    }
+
+    #[test]
+    fn ensure_remote() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        let url = "https://example.com/repo.git";
+        assert_eq!(
+            RemoteAction::Added,
+            crate::ensure_remote(&dir, "origin", url).unwrap()
+        );
+        assert_eq!(
+            RemoteAction::Unchanged,
+            crate::ensure_remote(&dir, "origin", url).unwrap()
+        );
+
+        let moved = "https://example.com/moved.git";
+        assert_eq!(
+            RemoteAction::Updated,
+            crate::ensure_remote(&dir, "origin", moved).unwrap()
+        );
+        assert_eq!(moved, crate::remote_url(&dir, "origin").unwrap());
+    }
+
+    #[test]
+    fn remote_url() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper
+            .repository()
+            .remote("origin", "https://example.com/repo.git")
+            .unwrap();
+
+        assert_eq!(
+            "https://example.com/repo.git",
+            crate::remote_url(&dir, "origin").unwrap()
+        );
+
+        match crate::remote_url(&dir, "upstream") {
+            Err(crate::Error::RemoteNotFound(name)) => assert_eq!("upstream", name),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        helper
+            .config_set("remote.mirror.pushurl", "https://example.com/mirror.git")
+            .unwrap();
+        match crate::remote_url(&dir, "mirror") {
+            Err(crate::Error::RemoteNoUrl(name)) => assert_eq!("mirror", name),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn clone_mirror() {
+        let (upstream, url) = upstream();
+        let helper = GitHelper::open(&upstream).unwrap();
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        repository.branch("feature", &head, false).unwrap();
+        repository
+            .tag_lightweight("v1.0", head.as_object(), false)
+            .unwrap();
+        repository
+            .note(
+                &head.author(),
+                &head.author(),
+                None,
+                head.id(),
+                "a note",
+                false,
+            )
+            .unwrap();
+
+        let dir = tempdir().unwrap();
+        let mirror = GitHelper::clone_mirror(&url, &dir, &CloneOptions::default()).unwrap();
+        assert!(mirror.repository().is_bare());
+        assert_eq!(
+            crate::list_references(&upstream).unwrap(),
+            crate::list_references(&dir).unwrap()
+        );
+        assert!(mirror
+            .repository()
+            .config()
+            .unwrap()
+            .get_bool("remote.origin.mirror")
+            .unwrap());
+
+        repository
+            .find_branch("feature", git2::BranchType::Local)
+            .unwrap()
+            .delete()
+            .unwrap();
+
+        let options = crate::FetchOptions {
+            mirror: true,
+            ..crate::FetchOptions::default()
+        };
+        let report = crate::fetch(&dir, &options).unwrap();
+        assert_eq!(vec!["refs/heads/feature".to_string()], report.pruned_refs);
+        assert_eq!(
+            crate::list_references(&upstream).unwrap(),
+            crate::list_references(&dir).unwrap()
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! Rendering of diffs as text for display in terminals.
+
+use git2::DiffFormat;
+
+use crate::{
+    DiffConfig,
+    Error,
+    GitHelper,
+};
+
+const BOLD: &str = "\x1b[1m";
+const CYAN: &str = "\x1b[36m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[m";
+
+/// Options controlling how a diff is rendered as text. The default renders a
+/// plain unified diff like `git diff`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Color headers and changes with ANSI escape sequences like git does
+    /// for terminals. The crate does not detect terminals, callers should
+    /// only set this when the output is a terminal.
+    pub color: bool,
+
+    /// Show changed lines as single lines with the removed and added words
+    /// marked, like `git diff --word-diff`. Without color removed words are
+    /// enclosed in `[-` and `-]` and added words in `{+` and `+}`, with color
+    /// they are only colored.
+    pub word_diff: bool,
+}
+
+/// Line of a unified diff with its origin as reported by libgit2, e.g. `+`
+/// for added lines or `H` for hunk headers.
+struct DiffLine {
+    origin: char,
+    content: String,
+}
+
+/// Change of a token between two lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WordChange<'a> {
+    Equal(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl GitHelper {
+    /// Render the diff between the commits the revspecs resolve to as text.
+    pub fn diff_text(
+        &self,
+        old: &str,
+        new: &str,
+        config: &DiffConfig,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        let mut diff = self.commits_diff(old, new, config)?;
+        config.find_similar(&mut diff)?;
+
+        let mut lines = Vec::new();
+        diff.print(DiffFormat::Patch, |_, _, line| {
+            lines.push(DiffLine {
+                origin: line.origin(),
+                content: String::from_utf8_lossy(line.content()).into_owned(),
+            });
+            true
+        })
+        .map_err(Error::Diff)?;
+
+        Ok(render(&lines, options))
+    }
+}
+
+/// Render the lines of a unified diff as configured by the options.
+fn render(lines: &[DiffLine], options: &RenderOptions) -> String {
+    let mut output = String::new();
+
+    let mut index = 0;
+    while index < lines.len() {
+        let line = &lines[index];
+        index += 1;
+
+        match line.origin {
+            'F' => {
+                for header in line.content.lines() {
+                    push_line(&mut output, options, BOLD, header);
+                }
+            }
+            'H' => push_line(&mut output, options, CYAN, &line.content),
+            '-' | '+' if options.word_diff => {
+                let start = index - 1;
+                let removed_end = end_of_run(lines, start, '-');
+                let added_end = end_of_run(lines, removed_end, '+');
+                index = added_end;
+
+                push_word_diff(
+                    &mut output,
+                    options,
+                    &lines[start..removed_end],
+                    &lines[removed_end..added_end],
+                );
+            }
+            '-' => push_line(&mut output, options, RED, &format!("-{}", line.content)),
+            '+' => push_line(&mut output, options, GREEN, &format!("+{}", line.content)),
+            ' ' if options.word_diff => output.push_str(&line.content),
+            ' ' => {
+                output.push(' ');
+                output.push_str(&line.content);
+            }
+            _ => output.push_str(&line.content),
+        }
+    }
+
+    output
+}
+
+/// Return the index after the run of lines with the origin starting at the
+/// index.
+fn end_of_run(lines: &[DiffLine], start: usize, origin: char) -> usize {
+    lines[start..]
+        .iter()
+        .position(|line| line.origin != origin)
+        .map_or(lines.len(), |offset| start + offset)
+}
+
+/// Push a single line, colored if enabled, followed by a newline.
+fn push_line(output: &mut String, options: &RenderOptions, color: &str, line: &str) {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+
+    if options.color {
+        output.push_str(color);
+        output.push_str(line);
+        output.push_str(RESET);
+    } else {
+        output.push_str(line);
+    }
+    output.push('\n');
+}
+
+/// Push a removed or added span of a word diff.
+fn push_span(output: &mut String, options: &RenderOptions, removed: bool, span: &str) {
+    if span.is_empty() {
+        return;
+    }
+
+    match (options.color, removed) {
+        (true, true) => output.push_str(&format!("{}{}{}", RED, span, RESET)),
+        (true, false) => output.push_str(&format!("{}{}{}", GREEN, span, RESET)),
+        (false, true) => output.push_str(&format!("[-{}-]", span)),
+        (false, false) => output.push_str(&format!("{{+{}+}}", span)),
+    }
+}
+
+/// Push a block of removed lines followed by added lines as word diff. The
+/// lines are paired in order, lines without a partner are shown as removed
+/// or added completely.
+fn push_word_diff(
+    output: &mut String,
+    options: &RenderOptions,
+    removed: &[DiffLine],
+    added: &[DiffLine],
+) {
+    let content = |line: &DiffLine| line.content.trim_end_matches('\n').to_string();
+    let paired = removed.len().min(added.len());
+
+    for (old, new) in removed.iter().zip(added) {
+        let (old, new) = (content(old), content(new));
+        let changes = word_changes(&tokenize(&old), &tokenize(&new));
+
+        let mut removed_span = String::new();
+        let mut added_span = String::new();
+        for change in changes {
+            match change {
+                WordChange::Equal(token) => {
+                    push_span(output, options, true, &removed_span);
+                    push_span(output, options, false, &added_span);
+                    removed_span.clear();
+                    added_span.clear();
+                    output.push_str(token);
+                }
+                WordChange::Removed(token) => removed_span.push_str(token),
+                WordChange::Added(token) => added_span.push_str(token),
+            }
+        }
+        push_span(output, options, true, &removed_span);
+        push_span(output, options, false, &added_span);
+        output.push('\n');
+    }
+
+    for line in &removed[paired..] {
+        push_span(output, options, true, &content(line));
+        output.push('\n');
+    }
+    for line in &added[paired..] {
+        push_span(output, options, false, &content(line));
+        output.push('\n');
+    }
+}
+
+/// Split the line into words, runs of whitespace and single punctuation
+/// characters.
+fn tokenize(line: &str) -> Vec<&str> {
+    fn class(c: char) -> u8 {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut previous = None;
+
+    for (index, c) in line.char_indices() {
+        let current = class(c);
+        if index > start && (previous != Some(current) || current == 2) {
+            tokens.push(&line[start..index]);
+            start = index;
+        }
+        previous = Some(current);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+
+    tokens
+}
+
+/// Return the changes from the old to the new tokens based on their longest
+/// common subsequence.
+fn word_changes<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<WordChange<'a>> {
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            changes.push(WordChange::Equal(old[i]));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            changes.push(WordChange::Removed(old[i]));
+            i += 1;
+        } else {
+            changes.push(WordChange::Added(new[j]));
+            j += 1;
+        }
+    }
+    changes.extend(old[i..].iter().map(|token| WordChange::Removed(token)));
+    changes.extend(new[j..].iter().map(|token| WordChange::Added(token)));
+
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::{
+        tempdir,
+        TempDir,
+    };
+
+    use super::{
+        tokenize,
+        RenderOptions,
+    };
+    use crate::{
+        DiffConfig,
+        GitHelper,
+    };
+
+    /// Create a repository with two commits changing a word in the first
+    /// line of the file `fox`. Returns the directory and the abbreviated
+    /// blob ids of the old and the new file.
+    fn changed_word() -> (TempDir, String, String) {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        let mut blobs = Vec::new();
+        for content in &[
+            "the quick brown fox\njumps over\n",
+            "the slow brown fox\njumps over\n",
+        ] {
+            fs::write(dir.path().join("fox"), content).unwrap();
+            helper.stage(&["fox"]).unwrap();
+            helper.commit("Changed fox").unwrap();
+
+            let blob = git2::Oid::hash_object(git2::ObjectType::Blob, content.as_bytes()).unwrap();
+            blobs.push(blob.to_string()[..7].to_string());
+        }
+
+        (dir, blobs.remove(0), blobs.remove(0))
+    }
+
+    #[test]
+    fn diff_text_plain() {
+        let (dir, old, new) = changed_word();
+
+        let text = crate::diff_text(
+            &dir,
+            "HEAD~1",
+            "HEAD",
+            &DiffConfig::default(),
+            &RenderOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            format!(
+                "diff --git a/fox b/fox\nindex {}..{} 100644\n--- a/fox\n+++ b/fox\n@@ -1,2 +1,2 \
+                 @@\n-the quick brown fox\n+the slow brown fox\n jumps over\n",
+                old, new
+            ),
+            text
+        );
+    }
+
+    #[test]
+    fn diff_text_word_diff() {
+        let (dir, old, new) = changed_word();
+
+        let options = RenderOptions {
+            word_diff: true,
+            ..RenderOptions::default()
+        };
+        let text =
+            crate::diff_text(&dir, "HEAD~1", "HEAD", &DiffConfig::default(), &options).unwrap();
+
+        assert_eq!(
+            format!(
+                "diff --git a/fox b/fox\nindex {}..{} 100644\n--- a/fox\n+++ b/fox\n@@ -1,2 +1,2 \
+                 @@\nthe [-quick-]{{+slow+}} brown fox\njumps over\n",
+                old, new
+            ),
+            text
+        );
+    }
+
+    #[test]
+    fn diff_text_color() {
+        let (dir, ..) = changed_word();
+
+        let options = RenderOptions {
+            color: true,
+            word_diff: true,
+        };
+        let text =
+            crate::diff_text(&dir, "HEAD~1", "HEAD", &DiffConfig::default(), &options).unwrap();
+
+        assert!(text.contains("\x1b[1mdiff --git a/fox b/fox\x1b[m\n"));
+        assert!(text.contains("\x1b[36m@@ -1,2 +1,2 @@\x1b[m\n"));
+        assert!(text.contains("the \x1b[31mquick\x1b[m\x1b[32mslow\x1b[m brown fox\n"));
+
+        let options = RenderOptions {
+            color: true,
+            word_diff: false,
+        };
+        let text =
+            crate::diff_text(&dir, "HEAD~1", "HEAD", &DiffConfig::default(), &options).unwrap();
+
+        assert!(text.contains("\x1b[31m-the quick brown fox\x1b[m\n"));
+        assert!(text.contains("\x1b[32m+the slow brown fox\x1b[m\n"));
+    }
+
+    #[test]
+    fn tokenize_words() {
+        assert_eq!(
+            vec!["let", " ", "x_1", " ", "=", " ", "f", "(", "a", ",", "b", ")", ";"],
+            tokenize("let x_1 = f(a,b);")
+        );
+        assert!(tokenize("").is_empty());
+    }
+}
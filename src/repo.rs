@@ -0,0 +1,1898 @@
+//! Handle around an opened repository that can be reused for several
+//! operations.
+
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    path::{
+        Component,
+        Path,
+        PathBuf,
+    },
+};
+
+use git2::{
+    self,
+    Commit,
+    Delta,
+    ErrorCode,
+    Index,
+    IndexAddOption,
+    IndexEntry,
+    IndexTime,
+    ObjectType,
+    Oid,
+    Repository,
+    RepositoryOpenFlags,
+    RepositoryState,
+    Signature,
+    Status,
+    StatusOptions as Git2StatusOptions,
+    StatusShow,
+    Tree,
+};
+
+use crate::{
+    diff::{
+        diff_entries,
+        DiffConfig,
+        DiffEntry,
+    },
+    identity::Sig,
+    index_flags::skip_worktree_paths,
+    ownership::open_error,
+    snapshot::{
+        Snapshot,
+        MEMPACK_PRIORITY,
+    },
+    time::Timestamp,
+    Error,
+};
+
+/// Status of a single path in the repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusEntry {
+    /// Path of the entry relative to the repository root.
+    pub path: PathBuf,
+
+    /// Status flags of the entry.
+    pub status: git2::Status,
+
+    /// Path the entry was renamed from if renames are detected.
+    pub old_path: Option<PathBuf>,
+}
+
+/// Status of a single file, returned by GitHelper::file_status. The index
+/// and working directory flags of git are merged into one state: a file that
+/// was staged and then changed again in the working directory is Modified, as
+/// committing would not include the latest changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileState {
+    /// The file is tracked and neither staged nor modified.
+    Unmodified,
+
+    /// The file was changed or deleted in the working directory and the
+    /// change is not staged, regardless of whether other changes are staged.
+    Modified,
+
+    /// All changes of the file are staged, including staged deletions.
+    Staged,
+
+    /// The file is not tracked and not ignored.
+    Untracked,
+
+    /// The file is not tracked and ignored.
+    Ignored,
+
+    /// The file has conflicts from a merge, cherry-pick or similar.
+    Conflicted,
+
+    /// The file exists neither in HEAD, the index nor the working directory.
+    NotFound,
+}
+
+/// Bits of the flags of an index entry that hold the conflict stage.
+const INDEX_ENTRY_STAGE_MASK: u16 = 0x3000;
+const INDEX_ENTRY_STAGE_SHIFT: u16 = 12;
+
+/// Options for opening a repository with GitHelper::open_with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpenOptions {
+    /// Only open the given path. Without this the parent directories are
+    /// searched for a repository like git does.
+    pub no_search: bool,
+
+    /// Open the repository as bare repository even if it has a working
+    /// directory. The given path has to be the git directory then, e.g. the
+    /// `.git` directory of a nonbare repository.
+    pub bare: bool,
+
+    /// Continue the search for a repository across filesystem boundaries.
+    pub cross_filesystem: bool,
+
+    /// Directories at which the search for a repository stops. The
+    /// directories themselves are not searched.
+    pub ceiling_dirs: Vec<PathBuf>,
+
+    /// Resolve symbolic links in the given path before opening it, so the
+    /// search for a repository walks the parents of the real directory
+    /// instead of the parents of the link.
+    pub follow_symlinks: bool,
+}
+
+/// Options for GitHelper::commit_with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommitOptions {
+    /// Author of the commit. If None the author is resolved like
+    /// GitHelper::effective_signature does.
+    pub author: Option<Sig>,
+
+    /// Committer of the commit. If None the committer is resolved from the
+    /// `GIT_COMMITTER_*` environment variables and the configuration.
+    pub committer: Option<Sig>,
+
+    /// Time the commit was authored at, overriding the time of the author.
+    /// If None the current time is used.
+    pub author_time: Option<Timestamp>,
+
+    /// Time the commit was committed at, overriding the time of the
+    /// committer. If None the current time is used.
+    pub committer_time: Option<Timestamp>,
+}
+
+/// What a commit would contain, returned by GitHelper::commit_dry_run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitPreview {
+    /// Id of the tree of the commit.
+    pub tree: Oid,
+
+    /// Parents of the commit. HEAD first followed by the merged commits
+    /// during a merge. Empty for the first commit.
+    pub parents: Vec<Oid>,
+
+    /// Message of the commit.
+    pub message: String,
+
+    /// Author of the commit.
+    pub author: Sig,
+
+    /// Committer of the commit.
+    pub committer: Sig,
+
+    /// Files the commit adds, modifies or deletes compared to its first
+    /// parent with renames detected.
+    pub changes: Vec<DiffEntry>,
+}
+
+/// Options for GitHelper::status_with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusOptions {
+    /// Do not report submodules, e.g. when their working directory is dirty
+    /// or they point to another commit. Like git submodules are reported by
+    /// default.
+    pub exclude_submodules: bool,
+
+    /// Detect renames between HEAD and the index and between the index and
+    /// the working directory with the rename settings of the config. Copies
+    /// are not detected. None reports a renamed file as deleted and added.
+    pub renames: Option<DiffConfig>,
+}
+
+/// Entry of the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntryInfo {
+    /// Path of the entry relative to the repository root.
+    pub path: PathBuf,
+
+    /// File mode of the entry, e.g. `0o100644` for regular files.
+    pub mode: u32,
+
+    /// Id of the blob with the staged content.
+    pub oid: Oid,
+
+    /// Conflict stage of the entry. 0 for entries without conflict, 1 to 3
+    /// for the base, our and their side of a conflict.
+    pub stage: u16,
+}
+
+/// Opened repository. All free functions of this crate open the repository
+/// on every call unless the repository cache is enabled; opening a GitHelper
+/// once and calling its methods avoids that when several operations are done
+/// in sequence.
+///
+/// A GitHelper can be moved between threads but not shared by them as git2
+/// repositories are not `Sync`. Use a [`crate::RepoPool`] to work on the same
+/// repository from several threads.
+pub struct GitHelper {
+    repository: Repository,
+    pub(crate) snapshot: Option<Snapshot>,
+}
+
+impl GitHelper {
+    /// Open the repository in the given path. Fails with
+    /// Error::UnsafeOwnership if the repository is owned by another user and
+    /// not listed in `safe.directory`.
+    pub fn open<P: AsRef<Path>>(repo_path: P) -> Result<Self, Error> {
+        let repository =
+            Repository::open(&repo_path).map_err(|err| open_error(repo_path.as_ref(), err))?;
+
+        Ok(Self {
+            repository,
+            snapshot: None,
+        })
+    }
+
+    /// Open the repository in the given path with the given options. The
+    /// path can be the working directory, any directory below it unless
+    /// `no_search` is set, the git directory or the path of a bare
+    /// repository.
+    pub fn open_with<P: AsRef<Path>>(repo_path: P, options: &OpenOptions) -> Result<Self, Error> {
+        let mut flags = RepositoryOpenFlags::empty();
+        if options.no_search {
+            flags |= RepositoryOpenFlags::NO_SEARCH;
+        }
+        if options.bare {
+            flags |= RepositoryOpenFlags::BARE;
+        }
+        if options.cross_filesystem {
+            flags |= RepositoryOpenFlags::CROSS_FS;
+        }
+
+        let repo_path = if options.follow_symlinks {
+            repo_path
+                .as_ref()
+                .canonicalize()
+                .map_err(Error::ResolvePath)?
+        } else {
+            repo_path.as_ref().to_path_buf()
+        };
+
+        let repository = Repository::open_ext(&repo_path, flags, &options.ceiling_dirs)
+            .map_err(|err| open_error(&repo_path, err))?;
+
+        Ok(Self {
+            repository,
+            snapshot: None,
+        })
+    }
+
+    /// Create a new nonbare git repository in the given path and open it.
+    pub fn init<P: AsRef<Path>>(repo_path: P) -> Result<Self, Error> {
+        let repository = Repository::init(&repo_path).map_err(Error::RepositoryInit)?;
+
+        Ok(Self {
+            repository,
+            snapshot: None,
+        })
+    }
+
+    /// Open the repository in the given path or create a new nonbare
+    /// repository there if the path is not a repository. Other errors while
+    /// opening, e.g. missing permissions, are returned as
+    /// Error::RepositoryOpen or Error::UnsafeOwnership like for `open`.
+    pub fn open_or_init<P: AsRef<Path>>(repo_path: P) -> Result<Self, Error> {
+        match Repository::open(&repo_path) {
+            Ok(repository) => Ok(Self {
+                repository,
+                snapshot: None,
+            }),
+            Err(err) if err.code() == ErrorCode::NotFound => Self::init(repo_path),
+            Err(err) => Err(open_error(repo_path.as_ref(), err)),
+        }
+    }
+
+    /// Wrap a repository opened or created by the other modules.
+    pub(crate) fn from_repository(repository: Repository) -> Self {
+        Self {
+            repository,
+            snapshot: None,
+        }
+    }
+
+    /// Return the underlying git2 repository.
+    pub fn repository(&self) -> &Repository {
+        &self.repository
+    }
+
+    /// Return the working directory of the repository. Fails with
+    /// Error::NoWorkdir for bare repositories.
+    pub fn workdir(&self) -> Result<&Path, Error> {
+        self.repository.workdir().ok_or(Error::NoWorkdir)
+    }
+
+    /// Return the state of the repository, e.g. whether a merge, rebase or
+    /// cherry-pick is in progress. A repository without an operation in
+    /// progress is in RepositoryState::Clean, as is a repository created with
+    /// `init_in_memory`.
+    pub fn repository_state(&self) -> RepositoryState {
+        if self.is_in_memory() {
+            return RepositoryState::Clean;
+        }
+
+        self.repository.state()
+    }
+
+    /// Commit current stage with given commit message.
+    pub fn commit(&self, message: &str) -> Result<(), Error> {
+        self.commit_with(message, &CommitOptions::default())
+    }
+
+    /// Commit current stage with given commit message and options. Fixed
+    /// timestamps make the commit id reproducible for the same tree, parents
+    /// and message. During a merge the merged commits become additional
+    /// parents. A merge or cherry-pick is concluded by the commit: an empty
+    /// message is replaced by the prepared `MERGE_MSG` and the state is
+    /// cleaned up afterwards.
+    pub fn commit_with(&self, message: &str, options: &CommitOptions) -> Result<(), Error> {
+        let (author, committer) = self.commit_signatures(options)?;
+
+        self.commit_signed(message, &author, &committer)
+    }
+
+    /// Commit current stage with given commit message and the signature as
+    /// author and committer. The configuration and the environment are not
+    /// read so a signature can be created once and reused for many commits.
+    pub fn commit_with_signature(
+        &self,
+        message: &str,
+        signature: &Signature<'_>,
+    ) -> Result<(), Error> {
+        self.commit_signed(message, signature, signature)
+    }
+
+    /// Return what committing the current stage with given commit message
+    /// and options would create without writing the commit or moving HEAD.
+    /// The tree of the commit is only written to memory so the object
+    /// database is not changed either.
+    pub fn commit_dry_run(
+        &self,
+        message: &str,
+        options: &CommitOptions,
+    ) -> Result<CommitPreview, Error> {
+        let (author, committer) = self.commit_signatures(options)?;
+        let parents = self.pending_parents()?;
+        let message = self.pending_message(message, self.concludes_operation());
+
+        // snapshots keep their objects in memory already, other repositories
+        // get a separate handle with an in-memory backend for the tree
+        let separate = if self.is_snapshot() {
+            None
+        } else {
+            let repository =
+                Repository::open(self.repository.path()).map_err(Error::RepositoryOpen)?;
+            repository
+                .odb()
+                .and_then(|odb| odb.add_new_mempack_backend(MEMPACK_PRIORITY).map(|_| ()))
+                .map_err(Error::OdbOpen)?;
+            Some(repository)
+        };
+        let repository = separate.as_ref().unwrap_or(&self.repository);
+
+        let tree = self
+            .repository
+            .index()
+            .and_then(|mut index| index.write_tree_to(repository))
+            .map_err(Error::IndexWriteTree)?;
+        let new_tree = repository
+            .find_tree(tree)
+            .map_err(Error::RepositoryFindTree)?;
+        let old_tree = parents
+            .first()
+            .map(|parent| repository.find_tree(parent.tree_id()))
+            .transpose()
+            .map_err(Error::RepositoryFindTree)?;
+
+        let config = DiffConfig::default();
+        let mut diff = repository
+            .diff_tree_to_tree(
+                old_tree.as_ref(),
+                Some(&new_tree),
+                Some(&mut config.diff_options()),
+            )
+            .map_err(Error::Diff)?;
+
+        Ok(CommitPreview {
+            tree,
+            parents: parents.iter().map(Commit::id).collect(),
+            message,
+            author: Sig::from(&author),
+            committer: Sig::from(&committer),
+            changes: diff_entries(&mut diff, &config)?,
+        })
+    }
+
+    /// Commit current stage like commit_with with the given signatures.
+    fn commit_signed(
+        &self,
+        message: &str,
+        author: &Signature<'_>,
+        committer: &Signature<'_>,
+    ) -> Result<(), Error> {
+        let repository = &self.repository;
+        let tree = self.index_tree()?;
+        let concludes = self.concludes_operation();
+        let message = self.pending_message(message, concludes);
+        let parent_commit = self.pending_parents()?;
+
+        let oid = repository
+            .commit(
+                self.head_update_ref(),
+                author,
+                committer,
+                &message,
+                &tree,
+                &parent_commit.iter().collect::<Vec<_>>(),
+            )
+            .map_err(Error::RepositoryCommit)?;
+        self.set_snapshot_head(oid);
+
+        if concludes {
+            repository.cleanup_state().map_err(Error::CleanupState)?;
+        }
+
+        Ok(())
+    }
+
+    /// Return true if the next commit concludes a merge or cherry-pick.
+    /// Snapshots never conclude them as they do not change the repository.
+    pub(crate) fn concludes_operation(&self) -> bool {
+        match self.repository_state() {
+            RepositoryState::Merge | RepositoryState::CherryPick => !self.is_snapshot(),
+            _ => false,
+        }
+    }
+
+    /// Return the message of the next commit. An empty message of a commit
+    /// that concludes a merge or cherry-pick is replaced by the prepared
+    /// `MERGE_MSG`.
+    pub(crate) fn pending_message(&self, message: &str, concludes: bool) -> String {
+        let prepared_message = if concludes && message.trim().is_empty() {
+            self.repository.message().ok()
+        } else {
+            None
+        };
+
+        prepared_message.unwrap_or_else(|| message.to_string())
+    }
+
+    /// Return the parents of the next commit: HEAD and during a merge the
+    /// merged commits.
+    pub(crate) fn pending_parents(&self) -> Result<Vec<Commit<'_>>, Error> {
+        let mut parents = self.head_commit().into_iter().collect::<Vec<_>>();
+        for oid in self.merge_heads()? {
+            parents.push(
+                self.repository
+                    .find_commit(oid)
+                    .map_err(Error::RepositoryFindCommit)?,
+            );
+        }
+
+        Ok(parents)
+    }
+
+    /// Create a commit with the given tree on the given branch without
+    /// touching the index or HEAD. The current tip of the branch becomes the
+    /// parent, a branch that does not exist yet is created with a root
+    /// commit. This works in bare repositories which have no index to commit
+    /// from.
+    pub fn commit_to_bare(&self, branch: &str, tree_oid: Oid, message: &str) -> Result<(), Error> {
+        let repository = &self.repository;
+        let tree = repository
+            .find_tree(tree_oid)
+            .map_err(Error::RepositoryFindTree)?;
+
+        let reference = format!("refs/heads/{}", branch);
+        let parent_commit = repository
+            .find_reference(&reference)
+            .and_then(|reference| reference.peel_to_commit())
+            .ok()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let (author, committer) = self.commit_signatures(&CommitOptions::default())?;
+
+        repository
+            .commit(
+                Some(&reference),
+                &author,
+                &committer,
+                message,
+                &tree,
+                &parent_commit.iter().collect::<Vec<_>>(),
+            )
+            .map_err(Error::RepositoryCommit)?;
+
+        Ok(())
+    }
+
+    /// Create a root commit with an empty tree and the given commit message.
+    /// The index is not touched. Fails with Error::NotEmptyHistory if HEAD
+    /// already points to a commit.
+    pub fn commit_empty(&self, message: &str) -> Result<(), Error> {
+        let repository = &self.repository;
+        if self.head_commit().is_some() {
+            return Err(Error::NotEmptyHistory);
+        }
+
+        let oid = repository
+            .treebuilder(None)
+            .and_then(|builder| builder.write())
+            .map_err(Error::TreeBuilder)?;
+        let tree = repository
+            .find_tree(oid)
+            .map_err(Error::RepositoryFindTree)?;
+
+        let (author, committer) = self.commit_signatures(&CommitOptions::default())?;
+
+        let oid = repository
+            .commit(
+                self.head_update_ref(),
+                &author,
+                &committer,
+                message,
+                &tree,
+                &[],
+            )
+            .map_err(Error::RepositoryCommit)?;
+        self.set_snapshot_head(oid);
+
+        Ok(())
+    }
+
+    /// Write the current index as a tree and return it.
+    pub(crate) fn index_tree(&self) -> Result<Tree<'_>, Error> {
+        let mut index = self.repository.index().map_err(Error::IndexOpen)?;
+        let oid = index.write_tree().map_err(Error::IndexWriteTree)?;
+
+        self.repository
+            .find_tree(oid)
+            .map_err(Error::RepositoryFindTree)
+    }
+
+    /// Return the commit HEAD points to. None if HEAD is unborn or can not be
+    /// resolved. For snapshots this is the last commit in the snapshot.
+    pub(crate) fn head_commit(&self) -> Option<Commit<'_>> {
+        if let Some(oid) = self.snapshot_head() {
+            return self.repository.find_commit(oid).ok();
+        }
+
+        self.repository.head().ok()?.peel_to_commit().ok()
+    }
+
+    /// Return the short name of the branch HEAD points to, even if the
+    /// branch has no commits yet. None if HEAD is detached.
+    pub(crate) fn head_branch(&self) -> Result<Option<String>, Error> {
+        let head = self
+            .repository
+            .find_reference("HEAD")
+            .map_err(Error::RepositoryHead)?;
+
+        let branch = head
+            .symbolic_target()
+            .map_err(Error::RepositoryHead)?
+            .map(|target| {
+                target
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(target)
+                    .to_string()
+            });
+
+        Ok(branch)
+    }
+
+    /// Point HEAD to the given commit. If HEAD is a symbolic reference the
+    /// branch it points to is updated, otherwise HEAD is detached at the
+    /// commit. Snapshots only remember the commit.
+    pub(crate) fn update_head(&self, oid: Oid, log_message: &str) -> Result<(), Error> {
+        if self.is_snapshot() {
+            self.set_snapshot_head(oid);
+            return Ok(());
+        }
+
+        let head = self
+            .repository
+            .find_reference("HEAD")
+            .map_err(Error::RepositoryHead)?;
+
+        match head.symbolic_target().map_err(Error::RepositoryHead)? {
+            Some(branch) => self
+                .repository
+                .reference(branch, oid, true, log_message)
+                .map(|_| ())
+                .map_err(Error::HeadUpdate),
+            None => self
+                .repository
+                .set_head_detached(oid)
+                .map_err(Error::HeadUpdate),
+        }
+    }
+
+    /// Push commits to upstream. By default this will try to push to origin.
+    /// If origin does not exist this function will fail with
+    /// Error::NoOriginConfigured.
+    pub fn push_to_origin(&self) -> Result<(), Error> {
+        let mut remote = self
+            .repository
+            .find_remote("origin")
+            .map_err(|_| Error::NoOriginConfigured)?;
+
+        remote
+            .connect(git2::Direction::Push)
+            .map_err(Error::RemoteConnect)?;
+
+        remote
+            .push(&["refs/heads/master:refs/heads/master"], None)
+            .map_err(Error::RemotePush)?;
+
+        Ok(())
+    }
+
+    /// Stage given paths in the repository. Paths have to be relative to the
+    /// working directory of the repository or absolute paths inside of it.
+    /// On Windows both `\` and `/` are accepted as separator. Line endings
+    /// are converted according to `core.autocrlf` and the `text` and `eol`
+    /// attributes.
+    /// Executable files are staged with mode 100755 unless `core.fileMode`
+    /// is false, in which case the mode already in the index is kept. With
+    /// `core.ignorecase` index entries whose path only differs in case from
+    /// a staged path are replaced, so renaming `Readme.md` to `README.md` is
+    /// staged as rename instead of keeping both names.
+    pub fn stage<F: AsRef<Path>>(&self, paths: &[F]) -> Result<(), Error> {
+        let workdir = self.workdir()?;
+        let mut index = self.repository.index().map_err(Error::IndexOpen)?;
+
+        let ignore_case = self.config_get_bool("core.ignorecase")?.unwrap_or(false);
+
+        for path in paths
+            .iter()
+            .map(|path| index_path(workdir, path.as_ref()))
+            .filter(|path| fs_path(&workdir.join(path)).is_file())
+            .filter(|path| !path.starts_with(".git"))
+        {
+            if ignore_case {
+                remove_case_variants(&mut index, &path)?;
+            }
+            index.add_path(&path).map_err(Error::IndexAddPath)?;
+        }
+
+        self.write_index(&mut index)?;
+
+        Ok(())
+    }
+
+    /// Stage the given content at the given path without reading from or
+    /// writing to the working directory. The path has to be relative to the
+    /// root of the repository. An existing index entry for the path is
+    /// replaced. With `normalize_line_endings` CRLF line endings are
+    /// converted like `stage` does for files in the working directory.
+    pub fn stage_blob<F: AsRef<Path>>(
+        &self,
+        path: F,
+        content: &[u8],
+        normalize_line_endings: bool,
+    ) -> Result<(), Error> {
+        let mut index = self.repository.index().map_err(Error::IndexOpen)?;
+
+        let content = if normalize_line_endings {
+            self.normalize_line_endings(path.as_ref(), content)?
+        } else {
+            Cow::Borrowed(content)
+        };
+
+        let entry = IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100_644,
+            uid: 0,
+            gid: 0,
+            file_size: u32::try_from(content.len()).unwrap_or(u32::MAX),
+            id: Oid::ZERO_SHA1,
+            flags: 0,
+            flags_extended: 0,
+            path: path_to_bytes(path.as_ref()),
+        };
+
+        index
+            .add_frombuffer(&entry, &content)
+            .map_err(Error::IndexAdd)?;
+        self.write_index(&mut index)?;
+
+        Ok(())
+    }
+
+    /// Write the content as a blob to the object database without touching
+    /// the index or the working directory. Returns the id of the blob which
+    /// is the sha1 of the content prefixed with the `blob <size>\0` header.
+    pub fn write_blob(&self, content: &[u8]) -> Result<Oid, Error> {
+        self.repository
+            .blob(content)
+            .map_err(Error::RepositoryWriteBlob)
+    }
+
+    /// Read the raw object with the given id from the object database, like
+    /// `git cat-file <type> <oid>`. Returns the type of the object and its
+    /// content without the header. Fails with Error::InvalidOid if the id is
+    /// malformed and with Error::ObjectNotFound if the object does not exist.
+    pub fn read_object(&self, oid: &str) -> Result<(ObjectType, Vec<u8>), Error> {
+        let id = Oid::from_str(oid).map_err(|_| Error::InvalidOid(oid.to_string()))?;
+        let odb = self.repository.odb().map_err(Error::OdbOpen)?;
+
+        let object = odb.read(id).map_err(|err| match err.code() {
+            ErrorCode::NotFound => Error::ObjectNotFound(oid.to_string()),
+            _ => Error::OdbRead(err),
+        })?;
+
+        Ok((object.kind(), object.data().to_vec()))
+    }
+
+    /// Stage all paths in the repository. Ignored paths and paths that are
+    /// flagged as skip-worktree in the index will not be staged. Deleted
+    /// files stay in the index. Returns the staged paths, which are the new
+    /// and modified paths, sorted.
+    pub fn stage_all(&self) -> Result<Vec<PathBuf>, Error> {
+        let workdir = self.workdir()?;
+        let mut index = self.repository.index().map_err(Error::IndexOpen)?;
+        let skipped = skip_worktree_paths(&index);
+
+        // libgit2 walks the working directory and applies the ignore rules,
+        // the callback only has to skip the skip-worktree paths
+        let mut staged = Vec::new();
+        let mut skip_worktree = |path: &Path, _: &[u8]| -> i32 {
+            if skipped.contains(path) {
+                return 1;
+            }
+            if fs_path(&workdir.join(path)).symlink_metadata().is_ok() {
+                staged.push(path.to_path_buf());
+            }
+            0
+        };
+
+        index
+            .add_all(
+                ["."].iter(),
+                IndexAddOption::DEFAULT,
+                Some(&mut skip_worktree),
+            )
+            .map_err(Error::IndexAdd)?;
+        self.write_index(&mut index)?;
+
+        staged.sort();
+        Ok(staged)
+    }
+
+    /// Return status of the repository. Untracked files are included,
+    /// ignored files and files flagged as skip-worktree or assume-unchanged
+    /// are not. A clean repository returns an empty list.
+    pub fn status(&self) -> Result<Vec<StatusEntry>, Error> {
+        self.status_with(&StatusOptions::default())
+    }
+
+    /// Return status of the repository like `status` with the given options.
+    pub fn status_with(&self, status_options: &StatusOptions) -> Result<Vec<StatusEntry>, Error> {
+        let mut options = Git2StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false)
+            .exclude_submodules(status_options.exclude_submodules);
+
+        if let Some(config) = status_options
+            .renames
+            .filter(|config| config.detect_renames)
+        {
+            options
+                .renames_head_to_index(true)
+                .renames_index_to_workdir(true)
+                .rename_threshold(config.rename_threshold);
+        }
+
+        let statuses = self
+            .repository
+            .statuses(Some(&mut options))
+            .map_err(Error::FileStatus)?;
+
+        let entries = statuses
+            .iter()
+            .map(|entry| {
+                let renamed = entry
+                    .head_to_index()
+                    .filter(|delta| delta.status() == Delta::Renamed)
+                    .or_else(|| {
+                        entry
+                            .index_to_workdir()
+                            .filter(|delta| delta.status() == Delta::Renamed)
+                    });
+
+                match renamed {
+                    Some(delta) => StatusEntry {
+                        path: delta
+                            .new_file()
+                            .path_bytes()
+                            .map(path_from_bytes)
+                            .unwrap_or_default(),
+                        status: entry.status(),
+                        old_path: delta.old_file().path_bytes().map(path_from_bytes),
+                    },
+                    None => StatusEntry {
+                        path: path_from_bytes(entry.path_bytes()),
+                        status: entry.status(),
+                        old_path: None,
+                    },
+                }
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Return status of the repository like `status` with renames detected
+    /// with the default rename settings. A renamed file is reported once with
+    /// its new path and `old_path` set instead of as deleted and added.
+    pub fn status_with_renames(&self) -> Result<Vec<StatusEntry>, Error> {
+        self.status_with(&StatusOptions {
+            renames: Some(DiffConfig::default()),
+            ..StatusOptions::default()
+        })
+    }
+
+    /// Return the state of a single file given relative to the repository
+    /// root. Only the file is compared, which is much cheaper than the status
+    /// of the whole repository.
+    pub fn file_status<F: AsRef<Path>>(&self, file: F) -> Result<FileState, Error> {
+        let status = match self.repository.status_file(file.as_ref()) {
+            Ok(status) => status,
+            Err(err) if err.code() == ErrorCode::NotFound => return Ok(FileState::NotFound),
+            Err(err) => return Err(Error::FileStatus(err)),
+        };
+
+        let state = if status.is_conflicted() {
+            FileState::Conflicted
+        } else if status.is_ignored() {
+            FileState::Ignored
+        } else if status.is_wt_new() {
+            FileState::Untracked
+        } else if status.intersects(
+            Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_TYPECHANGE | Status::WT_RENAMED,
+        ) {
+            FileState::Modified
+        } else if status.intersects(
+            Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_DELETED
+                | Status::INDEX_TYPECHANGE
+                | Status::INDEX_RENAMED,
+        ) {
+            FileState::Staged
+        } else {
+            FileState::Unmodified
+        };
+
+        Ok(state)
+    }
+
+    /// Return the sorted paths of the untracked files in the working
+    /// directory. Only the working directory is compared, not the index
+    /// with HEAD.
+    pub fn untracked_files(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut options = Git2StatusOptions::new();
+        options
+            .show(StatusShow::Workdir)
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .exclude_submodules(true);
+
+        self.status_paths(&mut options, Status::WT_NEW)
+    }
+
+    /// Return the sorted paths of the tracked files that were changed in the
+    /// working directory but not staged. Untracked files are not searched
+    /// for.
+    pub fn modified_files(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut options = Git2StatusOptions::new();
+        options
+            .show(StatusShow::Workdir)
+            .include_untracked(false)
+            .exclude_submodules(true);
+
+        self.status_paths(
+            &mut options,
+            Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_TYPECHANGE | Status::WT_RENAMED,
+        )
+    }
+
+    /// Return the sorted paths of the files whose staged content differs from
+    /// HEAD. The working directory is not scanned.
+    pub fn staged_files(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut options = Git2StatusOptions::new();
+        options
+            .show(StatusShow::Index)
+            .include_untracked(false)
+            .exclude_submodules(true);
+
+        self.status_paths(
+            &mut options,
+            Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_DELETED
+                | Status::INDEX_TYPECHANGE
+                | Status::INDEX_RENAMED,
+        )
+    }
+
+    /// Return the sorted paths of the status entries with one of the flags.
+    fn status_paths(
+        &self,
+        options: &mut Git2StatusOptions,
+        flags: Status,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let statuses = self
+            .repository
+            .statuses(Some(options))
+            .map_err(Error::FileStatus)?;
+
+        let mut paths = statuses
+            .iter()
+            .filter(|entry| entry.status().intersects(flags))
+            .map(|entry| path_from_bytes(entry.path_bytes()))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        Ok(paths)
+    }
+
+    /// Return the entries of the index sorted by path, i.e. what the next
+    /// commit would contain.
+    pub fn index_entries(&self) -> Result<Vec<IndexEntryInfo>, Error> {
+        let index = self.repository.index().map_err(Error::IndexOpen)?;
+
+        let entries = index
+            .iter()
+            .map(|entry| IndexEntryInfo {
+                path: path_from_bytes(&entry.path),
+                mode: entry.mode,
+                oid: entry.id,
+                stage: (entry.flags & INDEX_ENTRY_STAGE_MASK) >> INDEX_ENTRY_STAGE_SHIFT,
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Return the file mode of the staged entry with the path, e.g.
+    /// `0o100755` for executable files. Fails with Error::IndexEntryNotFound
+    /// if the path is not in the index.
+    pub fn index_file_mode<F: AsRef<Path>>(&self, path: F) -> Result<u32, Error> {
+        let path = match self.repository.workdir() {
+            Some(workdir) => index_path(workdir, path.as_ref()),
+            None => path.as_ref().to_path_buf(),
+        };
+        let index = self.repository.index().map_err(Error::IndexOpen)?;
+
+        index
+            .get_path(&path, 0)
+            .map(|entry| entry.mode)
+            .ok_or(Error::IndexEntryNotFound(path))
+    }
+}
+
+/// Remove the entries from the index whose path equals the path ignoring
+/// case but not exactly, e.g. the old name of a file that was renamed by only
+/// changing the case of its name.
+fn remove_case_variants(index: &mut Index, path: &Path) -> Result<(), Error> {
+    let path = path_to_bytes(path);
+    let variants = index
+        .iter()
+        .filter(|entry| entry.path != path && entry.path.eq_ignore_ascii_case(&path))
+        .map(|entry| path_from_bytes(&entry.path))
+        .collect::<Vec<_>>();
+
+    variants
+        .iter()
+        .try_for_each(|variant| index.remove_path(variant))
+        .map_err(Error::IndexRemovePath)
+}
+
+/// Convert a path given by the caller into the path of its index entry:
+/// relative to the working directory with `/` as separator. Absolute paths
+/// inside the working directory are made relative, on Windows regardless of
+/// the case of the drive letter. Other paths are only normalized.
+pub(crate) fn index_path(workdir: &Path, path: &Path) -> PathBuf {
+    let relative = strip_workdir(workdir, path).unwrap_or(path);
+
+    PathBuf::from(String::from_utf8_lossy(&path_to_bytes(relative)).as_ref())
+}
+
+/// Return the path relative to the working directory if it is inside of it.
+fn strip_workdir<'a>(workdir: &Path, path: &'a Path) -> Option<&'a Path> {
+    let mut components = path.components();
+    for expected in workdir.components() {
+        if !same_component(expected, components.next()?) {
+            return None;
+        }
+    }
+
+    Some(components.as_path())
+}
+
+/// True if the components are equal. Drive letters of Windows paths are
+/// compared ignoring their case as Windows does.
+fn same_component(a: Component<'_>, b: Component<'_>) -> bool {
+    match (a, b) {
+        (Component::Prefix(a), Component::Prefix(b)) => a
+            .as_os_str()
+            .to_string_lossy()
+            .eq_ignore_ascii_case(&b.as_os_str().to_string_lossy()),
+        (a, b) => a == b,
+    }
+}
+
+/// Return the path to use for filesystem access. On Windows absolute paths
+/// are turned into extended-length paths (`\\?\`) which are not limited to
+/// MAX_PATH. Other paths are returned unchanged.
+pub(crate) fn fs_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        let text = path.to_string_lossy();
+        if path.is_absolute() && !text.starts_with(r"\\") {
+            // extended-length paths are passed to the filesystem as they
+            // are, so they must not contain `/`
+            let extended = format!(r"\\?\{}", text.replace('/', r"\"));
+            return Cow::Owned(PathBuf::from(extended));
+        }
+    }
+
+    Cow::Borrowed(path)
+}
+
+/// Convert a relative path into the form git stores it in.
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+        .into_bytes()
+}
+
+/// Convert a path as stored by git into a PathBuf.
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).as_ref())
+}
+
+#[cfg(test)]
+mod test {
+    use git2::{
+        Delta,
+        RepositoryState,
+    };
+    use std::{
+        fs,
+        path::{
+            Path,
+            PathBuf,
+        },
+    };
+    use tempfile::tempdir;
+
+    use super::{
+        CommitOptions,
+        FileState,
+        GitHelper,
+        OpenOptions,
+        StatusEntry,
+        StatusOptions,
+    };
+    use crate::{
+        time::Timestamp,
+        Sig,
+    };
+
+    #[test]
+    fn reuse_handle() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        helper.stage(&["first_file"]).unwrap();
+        helper.commit("Added first_file").unwrap();
+
+        fs::write(dir.path().join("second_file"), "second data").unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Added second_file").unwrap();
+
+        assert!(helper.status().unwrap().is_empty());
+
+        let head = helper
+            .repository()
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!("Added second_file", head.message().unwrap());
+        assert_eq!(1, head.parent_count());
+    }
+
+    #[test]
+    fn commit_empty() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        crate::commit_empty(&dir, "Initial commit").unwrap();
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let head = helper
+            .repository()
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!("Initial commit", head.message().unwrap());
+        assert_eq!(0, head.parent_count());
+        assert_eq!(0, head.tree().unwrap().len());
+
+        match crate::commit_empty(&dir, "Second commit") {
+            Err(crate::Error::NotEmptyHistory) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn commit_to_bare() {
+        let dir = tempdir().unwrap();
+        git2::Repository::init_bare(&dir).unwrap();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+
+        let write_tree = |content: &[u8]| {
+            let blob = repository.blob(content).unwrap();
+            let mut builder = repository.treebuilder(None).unwrap();
+            builder.insert("file", blob, 0o100_644).unwrap();
+            builder.write().unwrap()
+        };
+
+        let first_tree = write_tree(b"first data");
+        crate::commit_to_bare(&dir, "main", first_tree, "First commit").unwrap();
+        let second_tree = write_tree(b"second data");
+        crate::commit_to_bare(&dir, "main", second_tree, "Second commit").unwrap();
+
+        let commit = repository
+            .find_reference("refs/heads/main")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!("Second commit", commit.message().unwrap());
+        assert_eq!(second_tree, commit.tree_id());
+
+        let parent = commit.parent(0).unwrap();
+        assert_eq!("First commit", parent.message().unwrap());
+        assert_eq!(first_tree, parent.tree_id());
+        assert_eq!(0, parent.parent_count());
+
+        match crate::commit_to_bare(&dir, "main", git2::Oid::ZERO_SHA1, "Broken commit") {
+            Err(crate::Error::RepositoryFindTree(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stage_blob() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        crate::stage_blob(&dir, "subdir/in_memory", b"in memory data", false).unwrap();
+        crate::commit(&dir, "Added in_memory").unwrap();
+
+        assert!(!dir.path().join("subdir/in_memory").exists());
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let tree = repository.head().unwrap().peel_to_tree().unwrap();
+        let blob = tree
+            .get_path(std::path::Path::new("subdir/in_memory"))
+            .unwrap()
+            .to_object(repository)
+            .unwrap()
+            .peel_to_blob()
+            .unwrap();
+        assert_eq!(b"in memory data", blob.content());
+    }
+
+    #[test]
+    fn open_or_init() {
+        let dir = tempdir().unwrap();
+
+        crate::open_or_init(&dir).unwrap();
+        assert!(dir.path().join(".git").is_dir());
+
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        crate::stage(&dir, &["first_file"]).unwrap();
+        crate::commit(&dir, "Added first_file").unwrap();
+
+        crate::open_or_init(&dir).unwrap();
+        let helper = GitHelper::open(&dir).unwrap();
+        assert_eq!(
+            "Added first_file",
+            helper.head_commit().unwrap().message().unwrap()
+        );
+
+        let file = dir.path().join("first_file");
+        assert!(crate::open_or_init(&file).is_err());
+        assert_eq!("first data", fs::read_to_string(&file).unwrap());
+    }
+
+    #[test]
+    fn stage_all_ignored() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::create_dir(dir.path().join("subdir")).unwrap();
+        fs::write(dir.path().join("subdir/file"), "data").unwrap();
+        fs::write(dir.path().join("debug.log"), "log").unwrap();
+
+        let staged = crate::stage_all(&dir).unwrap();
+
+        assert_eq!(
+            vec![PathBuf::from(".gitignore"), PathBuf::from("subdir/file")],
+            staged
+        );
+        assert_eq!(staged, crate::staged_files(&dir).unwrap());
+
+        fs::write(dir.path().join("removed"), "removed").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Initial commit").unwrap();
+        fs::write(dir.path().join("subdir/file"), "changed").unwrap();
+        fs::write(dir.path().join("new"), "new").unwrap();
+        fs::remove_file(dir.path().join("removed")).unwrap();
+
+        assert_eq!(
+            vec![PathBuf::from("new"), PathBuf::from("subdir/file")],
+            crate::stage_all(&dir).unwrap()
+        );
+    }
+
+    /// Compare stage_all with staging every file found by walking the
+    /// working directory, which is what stage_all did before. Run with
+    /// `cargo test --release -- --ignored stage_all_many_files`.
+    #[test]
+    #[ignore]
+    fn stage_all_many_files() {
+        use std::time::Instant;
+
+        let create = || {
+            let dir = tempdir().unwrap();
+            crate::init(&dir).unwrap();
+            for index in 0..10_000 {
+                let subdir = dir.path().join(format!("dir{}", index % 100));
+                fs::create_dir_all(&subdir).unwrap();
+                fs::write(subdir.join(format!("file{}", index)), index.to_string()).unwrap();
+            }
+            dir
+        };
+
+        let dir = create();
+        let start = Instant::now();
+        let paths = walkdir::WalkDir::new(dir.path())
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.path().strip_prefix(dir.path()).unwrap().to_path_buf())
+            .filter(|path| !path.starts_with(".git"))
+            .collect::<Vec<_>>();
+        crate::stage(&dir, &paths).unwrap();
+        let walked = start.elapsed();
+
+        let dir = create();
+        let start = Instant::now();
+        let staged = crate::stage_all(&dir).unwrap();
+        let added = start.elapsed();
+        assert_eq!(10_000, staged.len());
+
+        assert_eq!(10_000, crate::index_entries(&dir).unwrap().len());
+        assert!(
+            added < walked * 2,
+            "walkdir: {:?}, add_all: {:?}",
+            walked,
+            added
+        );
+    }
+
+    #[test]
+    fn commit_with_signature() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        let signature = git2::Signature::now("Loop User", "loop@example.com").unwrap();
+
+        for index in 0..10 {
+            let name = format!("file_{}", index);
+            fs::write(dir.path().join(&name), "data").unwrap();
+            crate::stage(&dir, &[&name]).unwrap();
+            crate::commit_with_signature(&dir, &name, &signature).unwrap();
+        }
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let mut revwalk = helper.repository().revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        let commits = revwalk
+            .map(|oid| helper.repository().find_commit(oid.unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(10, commits.len());
+        for commit in commits {
+            assert_eq!("Loop User", commit.author().name().unwrap());
+            assert_eq!("loop@example.com", commit.committer().email().unwrap());
+        }
+    }
+
+    #[test]
+    fn commit_dry_run() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("modified"), "first data").unwrap();
+        fs::write(dir.path().join("deleted"), "deleted data").unwrap();
+        helper.stage(&["modified", "deleted"]).unwrap();
+        helper.commit("Initial commit").unwrap();
+
+        fs::write(dir.path().join("modified"), "changed data").unwrap();
+        fs::write(dir.path().join("added"), "added data").unwrap();
+        fs::remove_file(dir.path().join("deleted")).unwrap();
+        helper.stage(&["modified", "added"]).unwrap();
+        let mut index = helper.repository().index().unwrap();
+        index.remove_path(Path::new("deleted")).unwrap();
+        index.write().unwrap();
+
+        let repository = helper.repository();
+        let head = repository.head().unwrap().target().unwrap();
+        let count_objects = || {
+            let mut count = 0;
+            repository
+                .odb()
+                .unwrap()
+                .foreach(|_| {
+                    count += 1;
+                    true
+                })
+                .unwrap();
+            count
+        };
+        let objects = count_objects();
+
+        let options = CommitOptions {
+            author: Some(Sig {
+                name: "Preview Author".to_string(),
+                email: "preview@example.com".to_string(),
+                when: Timestamp::from_seconds(1_600_000_000, 0),
+            }),
+            committer: Some(Sig {
+                name: "Preview Author".to_string(),
+                email: "preview@example.com".to_string(),
+                when: Timestamp::from_seconds(1_600_000_000, 0),
+            }),
+            ..CommitOptions::default()
+        };
+        let preview = crate::commit_dry_run(&dir, "Changed files", &options).unwrap();
+
+        assert_eq!(vec![head], preview.parents);
+        assert_eq!("Changed files", preview.message);
+        assert_eq!("Preview Author", preview.author.name);
+        assert_eq!(
+            vec![
+                (Delta::Added, PathBuf::from("added")),
+                (Delta::Deleted, PathBuf::from("deleted")),
+                (Delta::Modified, PathBuf::from("modified")),
+            ],
+            preview
+                .changes
+                .iter()
+                .map(|entry| (
+                    entry.status,
+                    entry.new_path.clone().or(entry.old_path.clone()).unwrap()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(head, repository.head().unwrap().target().unwrap());
+        assert_eq!(objects, count_objects());
+        assert!(repository.find_tree(preview.tree).is_err());
+
+        crate::commit_with(&dir, "Changed files", &options).unwrap();
+        let commit = repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(preview.tree, commit.tree_id());
+    }
+
+    #[test]
+    fn write_blob() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        let oid = crate::write_blob(&dir, b"hello world\n").unwrap();
+        assert_eq!("3b18e512dba79e4c8300dd08aeb37f8e728b8dad", oid.to_string());
+        assert_eq!(
+            "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391",
+            crate::write_blob(&dir, b"").unwrap().to_string()
+        );
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let blob = helper.repository().find_blob(oid).unwrap();
+        assert_eq!(b"hello world\n", blob.content());
+        assert!(crate::status(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn read_object() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        let oid = crate::write_blob(&dir, b"raw data").unwrap();
+
+        let (kind, data) = crate::read_object(&dir, &oid.to_string()).unwrap();
+        assert_eq!(git2::ObjectType::Blob, kind);
+        assert_eq!(b"raw data".to_vec(), data);
+
+        match crate::read_object(&dir, "not an oid") {
+            Err(crate::Error::InvalidOid(oid)) => assert_eq!("not an oid", oid),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let missing = "1234567890123456789012345678901234567890";
+        match crate::read_object(&dir, missing) {
+            Err(crate::Error::ObjectNotFound(oid)) => assert_eq!(missing, oid),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stage_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        let script = dir.path().join("script");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        crate::stage(&dir, &["script"]).unwrap();
+        assert_eq!(0o100_755, crate::index_entries(&dir).unwrap()[0].mode);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stage_executable_without_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        crate::config_set(&dir, "core.fileMode", "false").unwrap();
+        let script = dir.path().join("script");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+
+        crate::stage(&dir, &["script"]).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(&script, "#!/bin/sh\nexit 0\n").unwrap();
+        crate::stage(&dir, &["script"]).unwrap();
+
+        assert_eq!(0o100_644, crate::index_entries(&dir).unwrap()[0].mode);
+    }
+
+    #[test]
+    fn status_exclude_submodules() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+
+        let options = StatusOptions {
+            exclude_submodules: true,
+            ..StatusOptions::default()
+        };
+        let status = GitHelper::open(&dir)
+            .unwrap()
+            .status_with(&options)
+            .unwrap();
+        assert_eq!(crate::status(&dir).unwrap(), status);
+        assert_eq!(PathBuf::from("first_file"), status[0].path);
+    }
+
+    #[test]
+    fn status_renames() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("staged_old"), "staged data\n").unwrap();
+        fs::write(dir.path().join("unstaged_old"), "unstaged data\n").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added files").unwrap();
+
+        fs::rename(dir.path().join("staged_old"), dir.path().join("staged_new")).unwrap();
+        let mut index = GitHelper::open(&dir).unwrap().repository().index().unwrap();
+        index
+            .remove_path(std::path::Path::new("staged_old"))
+            .unwrap();
+        index.write().unwrap();
+        crate::stage(&dir, &["staged_new"]).unwrap();
+        fs::rename(
+            dir.path().join("unstaged_old"),
+            dir.path().join("unstaged_new"),
+        )
+        .unwrap();
+
+        let options = StatusOptions {
+            renames: Some(crate::DiffConfig::default()),
+            ..StatusOptions::default()
+        };
+        let mut status = crate::status_with(&dir, &options).unwrap();
+        status.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(2, status.len());
+        assert_eq!(PathBuf::from("staged_new"), status[0].path);
+        assert_eq!(Some(PathBuf::from("staged_old")), status[0].old_path);
+        assert_eq!(git2::Status::INDEX_RENAMED, status[0].status);
+        assert_eq!(PathBuf::from("unstaged_new"), status[1].path);
+        assert_eq!(Some(PathBuf::from("unstaged_old")), status[1].old_path);
+        assert_eq!(git2::Status::WT_RENAMED, status[1].status);
+
+        let status = crate::status(&dir).unwrap();
+        assert_eq!(4, status.len());
+        assert!(status.iter().all(|entry| entry.old_path.is_none()));
+    }
+
+    #[test]
+    fn stage_all_detects_rename() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("a.txt"), "renamed data\n").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added a.txt").unwrap();
+
+        fs::rename(dir.path().join("a.txt"), dir.path().join("b.txt")).unwrap();
+        crate::stage_all(&dir).unwrap();
+
+        assert_eq!(
+            vec![StatusEntry {
+                path: PathBuf::from("b.txt"),
+                status: git2::Status::INDEX_RENAMED,
+                old_path: Some(PathBuf::from("a.txt")),
+            }],
+            crate::status_with_renames(&dir).unwrap()
+        );
+        assert_eq!(2, crate::status(&dir).unwrap().len());
+    }
+
+    #[test]
+    fn stage_index_paths() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::create_dir_all(dir.path().join("dir/sub")).unwrap();
+        fs::write(dir.path().join("dir/sub/nested"), "nested").unwrap();
+        fs::write(dir.path().join("absolute"), "absolute").unwrap();
+
+        let workdir = helper.workdir().unwrap().to_path_buf();
+        helper
+            .stage(&[
+                Path::new("dir").join("sub").join("nested"),
+                workdir.join("absolute"),
+            ])
+            .unwrap();
+
+        let index = helper.repository().index().unwrap();
+        let mut paths = index
+            .iter()
+            .map(|entry| String::from_utf8(entry.path).unwrap())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(vec!["absolute", "dir/sub/nested"], paths);
+    }
+
+    #[test]
+    fn stage_case_only_rename() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("Readme.md"), "readme\n").unwrap();
+        helper.stage(&["Readme.md"]).unwrap();
+        helper.commit("Added Readme.md").unwrap();
+
+        // a case-insensitive filesystem only has the new name after the
+        // rename, which is simulated by renaming the file on any filesystem
+        fs::rename(dir.path().join("Readme.md"), dir.path().join("README.md")).unwrap();
+        helper.config_set("core.ignorecase", "true").unwrap();
+        let helper = GitHelper::open(&dir).unwrap();
+        helper.stage(&["README.md"]).unwrap();
+
+        let index = helper.repository().index().unwrap();
+        assert_eq!(
+            vec![b"README.md".to_vec()],
+            index.iter().map(|entry| entry.path).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![StatusEntry {
+                path: PathBuf::from("README.md"),
+                status: git2::Status::INDEX_RENAMED,
+                old_path: Some(PathBuf::from("Readme.md")),
+            }],
+            helper.status_with_renames().unwrap()
+        );
+    }
+
+    #[test]
+    fn stage_case_sensitive() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.config_set("core.ignorecase", "false").unwrap();
+        fs::write(dir.path().join("Readme.md"), "readme\n").unwrap();
+        fs::write(dir.path().join("README.md"), "other readme\n").unwrap();
+        helper.stage(&["Readme.md", "README.md"]).unwrap();
+
+        assert_eq!(2, helper.repository().index().unwrap().len());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn stage_windows_paths() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::create_dir_all(dir.path().join("dir/sub")).unwrap();
+        fs::write(dir.path().join("dir/sub/backslash"), "data").unwrap();
+        fs::write(dir.path().join("dir/sub/drive"), "data").unwrap();
+
+        // the drive letter in a different case than the working directory
+        let workdir = helper.workdir().unwrap().to_string_lossy().into_owned();
+        let (drive, rest) = workdir.split_at(1);
+        let other_case = if drive == drive.to_lowercase() {
+            drive.to_uppercase()
+        } else {
+            drive.to_lowercase()
+        };
+        let drive_path = format!(r"{}{}dir\sub\drive", other_case, rest);
+
+        helper
+            .stage(&[
+                PathBuf::from(r"dir\sub\backslash"),
+                PathBuf::from(drive_path),
+            ])
+            .unwrap();
+
+        let index = helper.repository().index().unwrap();
+        let mut paths = index
+            .iter()
+            .map(|entry| String::from_utf8(entry.path).unwrap())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(vec!["dir/sub/backslash", "dir/sub/drive"], paths);
+
+        let extended = super::fs_path(Path::new(r"C:\dir/file"));
+        assert_eq!(Path::new(r"\\?\C:\dir\file"), extended.as_ref());
+        assert_eq!(
+            Path::new("relative"),
+            super::fs_path(Path::new("relative")).as_ref()
+        );
+    }
+
+    #[test]
+    fn file_status() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        let file = dir.path().join("file");
+        let state = || crate::file_status(&dir, "file").unwrap();
+
+        assert_eq!(FileState::NotFound, state());
+
+        fs::write(&file, "first data").unwrap();
+        assert_eq!(FileState::Untracked, state());
+
+        crate::stage(&dir, &["file"]).unwrap();
+        assert_eq!(FileState::Staged, state());
+
+        crate::commit(&dir, "Added file").unwrap();
+        assert_eq!(FileState::Unmodified, state());
+
+        fs::write(&file, "second data").unwrap();
+        assert_eq!(FileState::Modified, state());
+
+        crate::stage(&dir, &["file"]).unwrap();
+        assert_eq!(FileState::Staged, state());
+
+        // staged and modified again
+        fs::write(&file, "third data").unwrap();
+        assert_eq!(FileState::Modified, state());
+
+        fs::remove_file(&file).unwrap();
+        assert_eq!(FileState::Modified, state());
+
+        fs::write(dir.path().join(".gitignore"), "ignored\n").unwrap();
+        fs::write(dir.path().join("ignored"), "ignored data").unwrap();
+        assert_eq!(
+            FileState::Ignored,
+            crate::file_status(&dir, "ignored").unwrap()
+        );
+
+        let dir = crate::test_util::conflicted_merge();
+        assert_eq!(
+            FileState::Conflicted,
+            crate::file_status(&dir, "conflict").unwrap()
+        );
+    }
+
+    #[test]
+    fn quick_status_accessors() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("modified"), "data").unwrap();
+        fs::write(dir.path().join("deleted"), "data").unwrap();
+        fs::write(dir.path().join("unchanged"), "data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added files").unwrap();
+
+        fs::write(dir.path().join("modified"), "changed data").unwrap();
+        fs::remove_file(dir.path().join("deleted")).unwrap();
+        fs::write(dir.path().join("staged"), "staged data").unwrap();
+        crate::stage(&dir, &["staged"]).unwrap();
+        fs::write(dir.path().join("staged"), "changed staged data").unwrap();
+        fs::create_dir(dir.path().join("dir")).unwrap();
+        fs::write(dir.path().join("dir/untracked"), "data").unwrap();
+        fs::write(dir.path().join("untracked"), "data").unwrap();
+
+        assert_eq!(
+            vec![PathBuf::from("dir/untracked"), PathBuf::from("untracked")],
+            crate::untracked_files(&dir).unwrap()
+        );
+        assert_eq!(
+            vec![
+                PathBuf::from("deleted"),
+                PathBuf::from("modified"),
+                PathBuf::from("staged")
+            ],
+            crate::modified_files(&dir).unwrap()
+        );
+        assert_eq!(
+            vec![PathBuf::from("staged")],
+            crate::staged_files(&dir).unwrap()
+        );
+    }
+
+    #[test]
+    fn index_entries() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        fs::create_dir(dir.path().join("dir")).unwrap();
+        fs::write(dir.path().join("dir/second_file"), "second data").unwrap();
+        crate::stage_all(&dir).unwrap();
+
+        let entries = crate::index_entries(&dir).unwrap();
+        assert_eq!(
+            vec![
+                PathBuf::from("dir/second_file"),
+                PathBuf::from("first_file")
+            ],
+            entries
+                .iter()
+                .map(|entry| entry.path.clone())
+                .collect::<Vec<_>>()
+        );
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let odb = helper.repository().odb().unwrap();
+        for (entry, content) in entries.iter().zip(&["second data", "first data"]) {
+            assert_eq!(0o100_644, entry.mode);
+            assert_eq!(0, entry.stage);
+            assert_eq!(content.as_bytes(), odb.read(entry.oid).unwrap().data());
+        }
+    }
+
+    #[test]
+    fn index_file_mode() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("file"), "data").unwrap();
+        crate::stage(&dir, &["file"]).unwrap();
+
+        assert_eq!(0o100_644, crate::index_file_mode(&dir, "file").unwrap());
+        assert_eq!(
+            Err(crate::Error::IndexEntryNotFound(PathBuf::from("missing"))),
+            crate::index_file_mode(&dir, "missing")
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let script = dir.path().join("script");
+            fs::write(&script, "#!/bin/sh\n").unwrap();
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            crate::stage(&dir, &["script"]).unwrap();
+
+            assert_eq!(0o100_755, crate::index_file_mode(&dir, "script").unwrap());
+        }
+    }
+
+    #[test]
+    fn repository_state() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        assert_eq!(
+            RepositoryState::Clean,
+            crate::repository_state(&dir).unwrap()
+        );
+
+        let dir = crate::test_util::conflicted_merge();
+        assert_eq!(
+            RepositoryState::Merge,
+            crate::repository_state(&dir).unwrap()
+        );
+    }
+
+    #[test]
+    fn open_with_options() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::create_dir_all(dir.path().join("subdir")).unwrap();
+        let workdir = dir.path().canonicalize().unwrap();
+
+        // the working directory and the git directory open the same repository
+        for path in &[dir.path().to_path_buf(), dir.path().join(".git")] {
+            let helper = GitHelper::open_with(path, &OpenOptions::default()).unwrap();
+            assert_eq!(workdir, helper.workdir().unwrap().canonicalize().unwrap());
+        }
+
+        // subdirectories are only searched without no_search
+        let subdir = dir.path().join("subdir");
+        assert!(GitHelper::open_with(&subdir, &OpenOptions::default()).is_ok());
+        let options = OpenOptions {
+            no_search: true,
+            ..OpenOptions::default()
+        };
+        assert!(GitHelper::open_with(&subdir, &options).is_err());
+
+        let options = OpenOptions {
+            ceiling_dirs: vec![dir.path().to_path_buf()],
+            ..OpenOptions::default()
+        };
+        assert!(GitHelper::open_with(&subdir, &options).is_err());
+
+        // the git directory of a nonbare repository opens as bare repository
+        let options = OpenOptions {
+            bare: true,
+            ..OpenOptions::default()
+        };
+        let helper = GitHelper::open_with(dir.path().join(".git"), &options).unwrap();
+        assert!(helper.repository().is_bare());
+        assert!(helper.workdir().is_err());
+    }
+
+    #[test]
+    fn open_with_bare() {
+        let dir = tempdir().unwrap();
+        let bare = dir.path().join("bare.git");
+        git2::Repository::init_bare(&bare).unwrap();
+
+        let helper = GitHelper::open_with(&bare, &OpenOptions::default()).unwrap();
+        assert!(helper.repository().is_bare());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_with_symlink() {
+        let dir = tempdir().unwrap();
+        let bare = dir.path().join("bare.git");
+        git2::Repository::init_bare(&bare).unwrap();
+
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&bare, &link).unwrap();
+        let options = OpenOptions {
+            no_search: true,
+            follow_symlinks: true,
+            ..OpenOptions::default()
+        };
+        let helper = GitHelper::open_with(&link, &options).unwrap();
+        assert_eq!(
+            bare.canonicalize().unwrap(),
+            helper.repository().path().canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn open_existing() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        let helper = GitHelper::open(&dir).unwrap();
+        assert!(helper.status().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+//! Summary of the state of a repository in a single value, e.g. for tools
+//! that manage many repositories. With the `serde` feature the summary can
+//! be serialized, e.g. as JSON.
+
+use crate::{
+    log::LogFilter,
+    Error,
+    GitHelper,
+};
+
+/// Number of commits listed in RepoReport::commits.
+const REPORT_COMMITS: usize = 10;
+
+/// Summary of a repository returned by GitHelper::report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RepoReport {
+    /// Short name of the branch HEAD points to, even if it has no commits
+    /// yet. None if HEAD is detached.
+    pub branch: Option<String>,
+
+    /// Id of the commit HEAD points to. None if HEAD has no commits yet.
+    pub head: Option<String>,
+
+    /// True if the working directory or the index have changes, including
+    /// untracked files. Always false for bare repositories.
+    pub dirty: bool,
+
+    /// Configured remotes in the order of the configuration.
+    pub remotes: Vec<RemoteReport>,
+
+    /// Number of commits the branch is ahead of and behind its upstream.
+    /// None if HEAD is detached, the branch has no upstream or the upstream
+    /// was not fetched yet.
+    pub ahead_behind: Option<(usize, usize)>,
+
+    /// Last commits reachable from HEAD, newest first.
+    pub commits: Vec<CommitReport>,
+
+    /// Names of the tags sorted by name.
+    pub tags: Vec<String>,
+
+    /// Number of stash entries.
+    pub stashes: usize,
+}
+
+/// Remote listed in a RepoReport.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemoteReport {
+    /// Name of the remote, e.g. `origin`.
+    pub name: String,
+
+    /// Fetch url of the remote. None if the remote only has a push url.
+    pub url: Option<String>,
+}
+
+/// Commit listed in a RepoReport.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommitReport {
+    /// Id of the commit.
+    pub oid: String,
+
+    /// First paragraph of the commit message.
+    pub summary: String,
+
+    /// Name of the author.
+    pub author_name: String,
+
+    /// Email of the author.
+    pub author_email: String,
+
+    /// Time the commit was committed at in seconds since the unix epoch.
+    pub committed_at: i64,
+}
+
+impl GitHelper {
+    /// Gather the current branch, HEAD, whether the repository is dirty, its
+    /// remotes, how far the branch is ahead of and behind its upstream, the
+    /// last ten commits, the tags and the number of stash entries. Parts
+    /// that do not apply to the repository, like a missing upstream, are
+    /// left empty instead of failing the report.
+    pub fn report(&self) -> Result<RepoReport, Error> {
+        let repository = self.repository();
+
+        let ahead_behind = self
+            .branch_details()?
+            .into_iter()
+            .find(|branch| branch.is_head)
+            .and_then(|branch| branch.ahead_behind);
+
+        let dirty = !repository.is_bare() && !self.status()?.is_empty();
+
+        let remotes = repository
+            .remotes()
+            .map_err(Error::RemoteConfig)?
+            .iter_bytes()
+            .map(|name| {
+                let name = String::from_utf8_lossy(name).into_owned();
+                let url = match self.remote_url(&name) {
+                    Ok(url) => Some(url),
+                    Err(Error::RemoteNoUrl(_)) => None,
+                    Err(err) => return Err(err),
+                };
+
+                Ok(RemoteReport { name, url })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let commits = self
+            .log_filtered(&LogFilter {
+                limit: Some(REPORT_COMMITS),
+                ..LogFilter::default()
+            })?
+            .into_iter()
+            .map(|entry| CommitReport {
+                oid: entry.oid.to_string(),
+                summary: entry.summary,
+                author_name: entry.author_name,
+                author_email: entry.author_email,
+                committed_at: entry.committed_at.seconds(),
+            })
+            .collect();
+
+        let mut tags = repository
+            .tag_names(None)
+            .map_err(Error::RepositoryTags)?
+            .iter_bytes()
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect::<Vec<_>>();
+        tags.sort();
+
+        let stashes = if repository.is_bare() {
+            0
+        } else {
+            self.list_stashes()?.len()
+        };
+
+        Ok(RepoReport {
+            branch: self.head_branch()?,
+            head: self.head_commit().map(|commit| commit.id().to_string()),
+            dirty,
+            remotes,
+            ahead_behind,
+            commits,
+            tags,
+            stashes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::tempdir;
+
+    use super::{
+        RemoteReport,
+        RepoReport,
+    };
+    use crate::GitHelper;
+
+    #[test]
+    fn report_empty_repository() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        let head = helper.repository().find_reference("HEAD").unwrap();
+        let branch = head
+            .symbolic_target()
+            .unwrap()
+            .unwrap()
+            .trim_start_matches("refs/heads/")
+            .to_string();
+
+        assert_eq!(
+            RepoReport {
+                branch: Some(branch),
+                head: None,
+                dirty: false,
+                remotes: Vec::new(),
+                ahead_behind: None,
+                commits: Vec::new(),
+                tags: Vec::new(),
+                stashes: 0,
+            },
+            crate::report(&dir).unwrap()
+        );
+    }
+
+    #[test]
+    fn report() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.commit_empty("Commit 0").unwrap();
+        for index in 1..12 {
+            helper.commit(&format!("Commit {}", index)).unwrap();
+        }
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        repository
+            .tag_lightweight("v1.0.0", head.as_object(), false)
+            .unwrap();
+        repository
+            .remote("origin", "https://example.com/repo")
+            .unwrap();
+        fs::write(dir.path().join("untracked"), "data").unwrap();
+
+        let report = crate::report(&dir).unwrap();
+        assert_eq!(Some(head.id().to_string()), report.head);
+        assert!(report.dirty);
+        assert_eq!(
+            vec![RemoteReport {
+                name: "origin".to_string(),
+                url: Some("https://example.com/repo".to_string()),
+            }],
+            report.remotes
+        );
+        assert_eq!(None, report.ahead_behind);
+        assert_eq!(10, report.commits.len());
+        assert_eq!("Commit 11", report.commits[0].summary);
+        assert_eq!(vec!["v1.0.0".to_string()], report.tags);
+        assert_eq!(0, report.stashes);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn report_json() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.commit_empty("Initial commit").unwrap();
+
+        let report = crate::report(&dir).unwrap();
+        let json = serde_json::to_value(&report).unwrap();
+
+        let mut keys = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            vec![
+                "ahead_behind",
+                "branch",
+                "commits",
+                "dirty",
+                "head",
+                "remotes",
+                "stashes",
+                "tags",
+            ],
+            keys
+        );
+        assert_eq!(serde_json::Value::Null, json["ahead_behind"]);
+        assert_eq!(
+            "Initial commit",
+            json["commits"][0]["summary"].as_str().unwrap()
+        );
+        assert!(json["commits"][0]["committed_at"].is_i64());
+
+        assert_eq!(report, serde_json::from_value(json).unwrap());
+    }
+}
@@ -0,0 +1,573 @@
+//! Rewriting of the commit history.
+
+use git2::{
+    Commit,
+    ErrorCode,
+    Oid,
+    Rebase,
+};
+
+use crate::{
+    repo::path_from_bytes,
+    CommitOptions,
+    Error,
+    GitHelper,
+    Sig,
+};
+
+/// Options for squash_last_with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SquashOptions {
+    /// Message of the new commit. If None the messages of the squashed
+    /// commits are joined oldest first, separated by empty lines.
+    pub message: Option<String>,
+
+    /// Squash commits that are already on the upstream of the branch. The
+    /// branch then has to be force pushed.
+    pub force: bool,
+}
+
+impl GitHelper {
+    /// Replace the commits between the revision and HEAD by a single commit
+    /// on top of the revision with the tree of HEAD and the given message,
+    /// like `git reset --soft <onto> && git commit`. The current branch is
+    /// moved to the new commit, the index and the working directory are not
+    /// touched. Returns the id of the new commit or of HEAD if there is
+    /// nothing to squash. Fails with Error::NotAncestor if the revision is
+    /// not an ancestor of HEAD.
+    pub fn squash(&self, onto: &str, message: &str) -> Result<Oid, Error> {
+        self.ensure_clean_state()?;
+
+        let repository = self.repository();
+        let head = self.find_commit("HEAD")?;
+        let base = self.find_commit(onto)?;
+
+        if base.id() == head.id() {
+            return Ok(head.id());
+        }
+        if !self.is_ancestor(&base.id().to_string(), "HEAD")? {
+            return Err(Error::NotAncestor(onto.to_string()));
+        }
+
+        let tree = head.tree().map_err(Error::CommitTree)?;
+        let (author, committer) = self.commit_signatures(&CommitOptions::default())?;
+
+        let oid = repository
+            .commit(None, &author, &committer, message, &tree, &[&base])
+            .map_err(Error::RepositoryCommit)?;
+        self.update_head(oid, &format!("squash: {}", message))?;
+
+        Ok(oid)
+    }
+
+    /// Replace the last `count` commits by a single commit with the tree of
+    /// HEAD like `git reset --soft HEAD~<count> && git commit`. See
+    /// squash_last_with for details.
+    pub fn squash_last(&self, count: usize, message: Option<&str>) -> Result<Oid, Error> {
+        self.squash_last_with(
+            count,
+            &SquashOptions {
+                message: message.map(str::to_string),
+                ..SquashOptions::default()
+            },
+        )
+    }
+
+    /// Replace the last `count` commits by a single commit with the tree of
+    /// HEAD. The new commit keeps the author and author date of the oldest
+    /// squashed commit, the committer and commit date are the current ones.
+    /// The current branch is moved to the new commit, the index and the
+    /// working directory are not touched. Returns the id of the new commit
+    /// or of HEAD if `count` is 0.
+    ///
+    /// Fails with Error::SquashMerge if one of the commits is a merge, with
+    /// Error::SquashPastRoot if the root commit would be squashed and with
+    /// Error::SquashPushed if one of the commits is on the upstream of the
+    /// branch and `force` is not set.
+    pub fn squash_last_with(&self, count: usize, options: &SquashOptions) -> Result<Oid, Error> {
+        self.ensure_clean_state()?;
+
+        let head = self.find_commit("HEAD")?;
+        let mut squashed = Vec::with_capacity(count);
+        let mut base = head.clone();
+        for _ in 0..count {
+            match base.parent_count() {
+                0 => return Err(Error::SquashPastRoot(count)),
+                1 => {}
+                _ => return Err(Error::SquashMerge(base.id().to_string())),
+            }
+
+            let parent = base.parent(0).map_err(Error::RepositoryFindCommit)?;
+            squashed.push(base);
+            base = parent;
+        }
+
+        let oldest = match squashed.last() {
+            Some(oldest) => oldest,
+            None => return Ok(head.id()),
+        };
+
+        if !options.force {
+            if let Some(pushed) = self.first_pushed(&squashed)? {
+                return Err(Error::SquashPushed(pushed.to_string()));
+            }
+        }
+
+        let message = match &options.message {
+            Some(message) => message.clone(),
+            None => squashed
+                .iter()
+                .rev()
+                .map(|commit| {
+                    String::from_utf8_lossy(commit.message_bytes())
+                        .trim_end()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        };
+
+        let (_, committer) = self.commit_signatures(&CommitOptions::default())?;
+        let author = Sig::from(&oldest.author()).to_signature()?;
+        let tree = head.tree().map_err(Error::CommitTree)?;
+
+        let oid = self
+            .repository()
+            .commit(None, &author, &committer, &message, &tree, &[&base])
+            .map_err(Error::RepositoryCommit)?;
+        let summary = message.lines().next().unwrap_or_default();
+        self.update_head(oid, &format!("squash: {}", summary))?;
+
+        Ok(oid)
+    }
+
+    /// Replay the commits of the current branch that are not reachable from
+    /// `upstream` on top of `onto` like `git rebase --onto <onto>
+    /// <upstream>`. The commits keep their message, author and committer.
+    /// Commits whose changes are already in `onto` are dropped. The branch is
+    /// moved to the last replayed commit and checked out. If a commit
+    /// conflicts the rebase is aborted, the branch and the working directory
+    /// are left as before and Error::Conflicts lists the conflicted paths.
+    /// The rebase is aborted the same way if any other step fails. The error
+    /// of the failed step is returned even if aborting fails as well.
+    pub fn rebase(&self, upstream: &str, onto: &str) -> Result<(), Error> {
+        self.ensure_clean_state()?;
+
+        let repository = self.repository();
+        let upstream = repository
+            .find_annotated_commit(self.find_commit(upstream)?.id())
+            .map_err(Error::Rebase)?;
+        let onto = repository
+            .find_annotated_commit(self.find_commit(onto)?.id())
+            .map_err(Error::Rebase)?;
+
+        let mut rebase = repository
+            .rebase(None, Some(&upstream), Some(&onto), None)
+            .map_err(Error::Rebase)?;
+
+        let result = self.apply_rebase(&mut rebase);
+        if result.is_err() {
+            // the failed step is the error worth reporting, not the cleanup
+            let _ = rebase.abort();
+        }
+
+        result
+    }
+
+    /// Apply and commit every operation of the rebase and finish it. The
+    /// caller has to abort the rebase if this fails.
+    fn apply_rebase(&self, rebase: &mut Rebase<'_>) -> Result<(), Error> {
+        let repository = self.repository();
+
+        while let Some(operation) = rebase.next() {
+            let operation = operation.map_err(Error::Rebase)?;
+
+            let index = repository.index().map_err(Error::IndexOpen)?;
+            if index.has_conflicts() {
+                let mut paths = index
+                    .conflicts()
+                    .map_err(Error::IndexConflicts)?
+                    .filter_map(|conflict| {
+                        let conflict = conflict.ok()?;
+                        let entry = conflict.our.or(conflict.their).or(conflict.ancestor)?;
+                        Some(path_from_bytes(&entry.path))
+                    })
+                    .collect::<Vec<_>>();
+                paths.sort();
+
+                return Err(Error::Conflicts(paths));
+            }
+
+            let committer = repository
+                .find_commit(operation.id())
+                .map_err(Error::RepositoryFindCommit)?
+                .committer()
+                .to_owned();
+            match rebase.commit(None, &committer, None) {
+                Ok(_) => {}
+                Err(err) if err.code() == ErrorCode::Applied => {}
+                Err(err) => return Err(Error::Rebase(err)),
+            }
+        }
+
+        rebase.finish(None).map_err(Error::Rebase)
+    }
+
+    /// Return the newest of the commits that is reachable from the upstream
+    /// of the current branch. None if the branch has no upstream.
+    fn first_pushed(&self, commits: &[Commit<'_>]) -> Result<Option<Oid>, Error> {
+        let repository = self.repository();
+        let upstream = self
+            .head_branch()?
+            .and_then(|branch| {
+                repository
+                    .branch_upstream_name(&format!("refs/heads/{}", branch))
+                    .ok()
+            })
+            .and_then(|upstream| {
+                repository
+                    .refname_to_id(&String::from_utf8_lossy(&upstream))
+                    .ok()
+            });
+        let upstream = match upstream {
+            Some(upstream) => upstream,
+            None => return Ok(None),
+        };
+
+        for commit in commits {
+            if commit.id() == upstream
+                || repository
+                    .graph_descendant_of(upstream, commit.id())
+                    .map_err(Error::RepositoryRevwalk)?
+            {
+                return Ok(Some(commit.id()));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::PathBuf,
+    };
+    use tempfile::tempdir;
+
+    use super::SquashOptions;
+    use crate::{
+        CommitOptions,
+        GitHelper,
+        Sig,
+        Timestamp,
+    };
+
+    #[test]
+    fn squash() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        for (index, name) in ["first", "second", "third"].iter().enumerate() {
+            fs::write(dir.path().join(name), index.to_string()).unwrap();
+            helper.stage(&[name]).unwrap();
+            helper.commit(&format!("Added {}", name)).unwrap();
+        }
+
+        let repository = helper.repository();
+        let first = repository.revparse_single("HEAD~2").unwrap().id();
+        let tree = crate::tree_oid(&dir, "HEAD").unwrap();
+
+        let squashed = crate::squash(&dir, "HEAD~2", "Added second and third").unwrap();
+
+        let head = repository.head().unwrap();
+        assert_eq!("refs/heads/master", head.name().unwrap());
+        assert_eq!(Some(squashed), head.target());
+        assert_eq!(tree, crate::tree_oid(&dir, "HEAD").unwrap());
+        assert_eq!(
+            vec![first.to_string()],
+            crate::commit_parents(&dir, "HEAD").unwrap()
+        );
+        assert!(crate::commit_parents(&dir, "HEAD~1").unwrap().is_empty());
+        assert_eq!(
+            "Added second and third",
+            crate::commit_message(&dir, "HEAD").unwrap()
+        );
+        assert!(crate::status(&dir).unwrap().is_empty());
+
+        assert_eq!(squashed, crate::squash(&dir, "HEAD", "Nothing").unwrap());
+    }
+
+    /// Create a repository with three commits by different authors adding
+    /// the files `first`, `second` and `third`.
+    fn three_commits() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+
+        for (index, name) in ["first", "second", "third"].iter().enumerate() {
+            fs::write(dir.path().join(name), index.to_string()).unwrap();
+            helper.stage(&[name]).unwrap();
+            let author = Sig {
+                name: format!("Author {}", name),
+                email: format!("{}@example.com", name),
+                when: Timestamp::from_seconds(1_600_000_000 + index as i64 * 60, 120),
+            };
+            let options = CommitOptions {
+                author: Some(author.clone()),
+                committer: Some(author),
+                ..CommitOptions::default()
+            };
+            helper
+                .commit_with(&format!("Added {}\n\nBody of {}\n", name, name), &options)
+                .unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn squash_last() {
+        let dir = three_commits();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let first = repository.revparse_single("HEAD~2").unwrap().id();
+        let tree = crate::tree_oid(&dir, "HEAD").unwrap();
+
+        let squashed = crate::squash_last(&dir, 2, None).unwrap();
+
+        assert_eq!(Some(squashed), repository.head().unwrap().target());
+        assert_eq!(tree, crate::tree_oid(&dir, "HEAD").unwrap());
+        assert_eq!(
+            vec![first.to_string()],
+            crate::commit_parents(&dir, "HEAD").unwrap()
+        );
+        assert_eq!(
+            "Added second\n\nBody of second\n\nAdded third\n\nBody of third",
+            crate::commit_message(&dir, "HEAD").unwrap()
+        );
+
+        let commit = repository.find_commit(squashed).unwrap();
+        assert_eq!("Author second", commit.author().name().unwrap());
+        assert_eq!(
+            Timestamp::from_seconds(1_600_000_060, 120),
+            crate::authored_at(&dir, "HEAD").unwrap()
+        );
+        assert_ne!(
+            Timestamp::from_seconds(1_600_000_120, 120),
+            crate::committed_at(&dir, "HEAD").unwrap()
+        );
+
+        assert_eq!(squashed, crate::squash_last(&dir, 0, None).unwrap());
+        crate::squash_last(&dir, 1, Some("Reworded")).unwrap();
+        assert_eq!("Reworded", crate::commit_message(&dir, "HEAD").unwrap());
+
+        assert_eq!(
+            Err(crate::Error::SquashPastRoot(2)),
+            crate::squash_last(&dir, 2, None)
+        );
+    }
+
+    #[test]
+    fn squash_last_merge() {
+        let dir = three_commits();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        let side = repository
+            .revparse_single("HEAD~2")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        let signature = repository.signature().unwrap();
+        let merge = repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Merge",
+                &head.tree().unwrap(),
+                &[&head, &side],
+            )
+            .unwrap();
+        helper.commit("After merge").unwrap();
+
+        assert_eq!(
+            Err(crate::Error::SquashMerge(merge.to_string())),
+            crate::squash_last(&dir, 3, Some("Squashed"))
+        );
+    }
+
+    #[test]
+    fn squash_last_pushed() {
+        let dir = three_commits();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let pushed = repository.revparse_single("HEAD~1").unwrap().id();
+        repository
+            .reference("refs/remotes/origin/master", pushed, false, "push")
+            .unwrap();
+        crate::config_set(&dir, "branch.master.remote", "origin").unwrap();
+        crate::config_set(&dir, "branch.master.merge", "refs/heads/master").unwrap();
+        repository
+            .remote("origin", "https://example.com/repo")
+            .unwrap();
+
+        crate::squash_last(&dir, 1, Some("Only unpushed")).unwrap();
+        assert_eq!(
+            Err(crate::Error::SquashPushed(pushed.to_string())),
+            crate::squash_last(&dir, 2, Some("Squashed"))
+        );
+
+        let options = SquashOptions {
+            message: Some("Squashed".to_string()),
+            force: true,
+        };
+        crate::squash_last_with(&dir, 2, &options).unwrap();
+        assert_eq!("Squashed", crate::commit_message(&dir, "HEAD").unwrap());
+    }
+
+    #[test]
+    fn rebase() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("base"), "base").unwrap();
+        helper.stage(&["base"]).unwrap();
+        helper.commit("Added base").unwrap();
+        let repository = helper.repository();
+        let base = repository.head().unwrap().peel_to_commit().unwrap();
+        repository.branch("feature", &base, false).unwrap();
+
+        fs::write(dir.path().join("moved"), "moved").unwrap();
+        helper.stage(&["moved"]).unwrap();
+        helper.commit("Moved base").unwrap();
+        let moved = repository.head().unwrap().target().unwrap();
+
+        helper
+            .checkout_branch("feature", &Default::default())
+            .unwrap();
+        let mut originals = Vec::new();
+        for name in &["first", "second"] {
+            fs::write(dir.path().join(name), name).unwrap();
+            helper.stage(&[name]).unwrap();
+            let author = Sig {
+                name: "Feature Author".to_string(),
+                email: "feature@example.com".to_string(),
+                when: Timestamp::from_seconds(1_600_000_000, 60),
+            };
+            let options = CommitOptions {
+                author: Some(author.clone()),
+                committer: Some(author),
+                ..CommitOptions::default()
+            };
+            helper
+                .commit_with(&format!("Added {}", name), &options)
+                .unwrap();
+            originals.push(repository.head().unwrap().target().unwrap());
+        }
+
+        crate::rebase(&dir, "master", "master").unwrap();
+
+        let head = repository.head().unwrap();
+        assert_eq!("refs/heads/feature", head.name().unwrap());
+        let second = head.peel_to_commit().unwrap();
+        let first = second.parent(0).unwrap();
+        assert_eq!(vec![moved], first.parent_ids().collect::<Vec<_>>());
+        assert!(!originals.contains(&second.id()));
+        assert!(!originals.contains(&first.id()));
+
+        for (commit, original) in [first, second].iter().zip(&originals) {
+            let original = repository.find_commit(*original).unwrap();
+            assert_eq!(original.message(), commit.message());
+            assert_eq!(original.author().to_string(), commit.author().to_string());
+            assert_eq!(original.author().when(), commit.author().when());
+            assert_eq!(original.committer().when(), commit.committer().when());
+        }
+        for name in &["base", "moved", "first", "second"] {
+            assert!(dir.path().join(name).exists(), "{}", name);
+        }
+        assert!(crate::status(&dir).unwrap().is_empty());
+        assert_eq!(git2::RepositoryState::Clean, repository.state());
+    }
+
+    #[test]
+    fn rebase_conflict() {
+        let (dir, _) = crate::test_util::diverged_branches();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let head = repository.head().unwrap().target().unwrap();
+
+        assert_eq!(
+            Err(crate::Error::Conflicts(vec![PathBuf::from("conflict")])),
+            crate::rebase(&dir, "feature", "feature")
+        );
+        assert_eq!(Some(head), repository.head().unwrap().target());
+        assert_eq!(git2::RepositoryState::Clean, repository.state());
+        assert_eq!(
+            "ours\n",
+            fs::read_to_string(dir.path().join("conflict")).unwrap()
+        );
+    }
+
+    #[test]
+    fn rebase_failing_step() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.commit("Base").unwrap();
+        let repository = helper.repository();
+        let base = repository.head().unwrap().peel_to_commit().unwrap();
+        repository.branch("feature", &base, false).unwrap();
+        helper.commit("Moved base").unwrap();
+
+        helper
+            .checkout_branch("feature", &Default::default())
+            .unwrap();
+        fs::write(dir.path().join("temporary"), "lost").unwrap();
+        helper.stage(&["temporary"]).unwrap();
+        helper.commit("Added temporary").unwrap();
+        fs::remove_file(dir.path().join("temporary")).unwrap();
+        helper.stage_all().unwrap();
+        helper.commit("Removed temporary").unwrap();
+        let head = repository.head().unwrap().target().unwrap();
+
+        // replaying the first commit fails because its blob is missing
+        let blob = git2::Oid::hash_object(git2::ObjectType::Blob, b"lost")
+            .unwrap()
+            .to_string();
+        fs::remove_file(
+            repository
+                .path()
+                .join("objects")
+                .join(&blob[..2])
+                .join(&blob[2..]),
+        )
+        .unwrap();
+
+        match crate::rebase(&dir, "master", "master") {
+            Err(crate::Error::Rebase(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let head_ref = repository.head().unwrap();
+        assert_eq!("refs/heads/feature", head_ref.name().unwrap());
+        assert_eq!(Some(head), head_ref.target());
+        assert_eq!(git2::RepositoryState::Clean, repository.state());
+        assert!(!repository.path().join("rebase-merge").exists());
+        assert!(crate::status(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn squash_not_ancestor() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.commit("First commit").unwrap();
+        let tree = crate::tree_oid(&dir, "HEAD").unwrap();
+        crate::commit_to_bare(&dir, "unrelated", tree, "Unrelated commit").unwrap();
+        helper.commit("Second commit").unwrap();
+
+        match crate::squash(&dir, "unrelated", "Squashed") {
+            Err(crate::Error::NotAncestor(revspec)) => assert_eq!("unrelated", revspec),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
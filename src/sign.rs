@@ -0,0 +1,175 @@
+//! Creation of signed commits.
+
+use std::fmt::Display;
+
+use crate::{
+    CommitOptions,
+    Error,
+    GitHelper,
+};
+
+/// Header field git uses to store commit signatures, regardless of whether
+/// they were created by GPG or SSH.
+pub(crate) const SIGNATURE_FIELD: &str = "gpgsig";
+
+impl GitHelper {
+    /// Commit current stage with given commit message and sign the commit
+    /// with an SSH key like git does with `gpg.format=ssh`.
+    ///
+    /// The raw commit buffer is handed to the `sign` callback which has to
+    /// return the armored signature, e.g. the output of `ssh-keygen -Y sign
+    /// -n git`. The signature is stored in the `gpgsig` header of the commit.
+    /// Git stores GPG signatures in the same header, so a callback returning
+    /// an armored GPG signature creates a GPG signed commit instead.
+    ///
+    /// A merge or cherry-pick is concluded like with `commit_with`: the
+    /// merged commits become additional parents, an empty message is
+    /// replaced by the prepared `MERGE_MSG` and the state is cleaned up.
+    pub fn commit_ssh_signed<S, E>(&self, message: &str, sign: S) -> Result<(), Error>
+    where
+        S: FnOnce(&[u8]) -> Result<String, E>,
+        E: Display,
+    {
+        let repository = self.repository();
+        let tree = self.index_tree()?;
+        let concludes = self.concludes_operation();
+        let message = self.pending_message(message, concludes);
+        let parent_commit = self.pending_parents()?;
+
+        let (author, committer) = self.commit_signatures(&CommitOptions::default())?;
+
+        let buffer = repository
+            .commit_create_buffer(
+                &author,
+                &committer,
+                &message,
+                &tree,
+                &parent_commit.iter().collect::<Vec<_>>(),
+            )
+            .map_err(Error::CommitCreateBuffer)?;
+
+        let commit_signature = sign(&buffer).map_err(|err| Error::CommitSign(err.to_string()))?;
+
+        let content = std::str::from_utf8(&buffer).map_err(|_| Error::CommitBufferNotUtf8)?;
+
+        let oid = repository
+            .commit_signed(content, &commit_signature, Some(SIGNATURE_FIELD))
+            .map_err(Error::RepositoryCommit)?;
+
+        self.update_head(oid, &format!("commit: {}", message))?;
+
+        if concludes {
+            repository.cleanup_state().map_err(Error::CleanupState)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::tempdir;
+
+    use crate::{
+        test_util::conflicted_merge,
+        GitHelper,
+    };
+
+    const STUB_SIGNATURE: &str =
+        "-----BEGIN SSH SIGNATURE-----\nc3R1YiBzaWduYXR1cmU=\n-----END SSH SIGNATURE-----";
+
+    #[test]
+    fn commit_ssh_signed() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        crate::stage_all(&dir).unwrap();
+
+        let mut signed_content = Vec::new();
+        crate::commit_ssh_signed(&dir, "Signed commit", |content: &[u8]| {
+            signed_content = content.to_vec();
+            Ok::<_, String>(STUB_SIGNATURE.to_string())
+        })
+        .unwrap();
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!("Signed commit", head.message().unwrap());
+
+        let (signature, content) = repository.extract_signature(&head.id(), None).unwrap();
+        assert_eq!(STUB_SIGNATURE, signature.as_str().unwrap());
+        assert_eq!(signed_content, content.to_vec());
+        assert!(head.header_field_bytes("gpgsig").is_ok());
+    }
+
+    #[test]
+    fn commit_ssh_signed_second_commit() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Unsigned commit").unwrap();
+
+        fs::write(dir.path().join("second_file"), "second data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit_ssh_signed(&dir, "Signed commit", |_: &[u8]| {
+            Ok::<_, String>(STUB_SIGNATURE.to_string())
+        })
+        .unwrap();
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let head = helper
+            .repository()
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(1, head.parent_count());
+        assert_eq!(
+            "Unsigned commit",
+            head.parent(0).unwrap().message().unwrap()
+        );
+    }
+
+    #[test]
+    fn commit_ssh_signed_signer_fails() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        let result =
+            crate::commit_ssh_signed(&dir, "Signed commit", |_: &[u8]| Err("no key available"));
+
+        match result {
+            Err(crate::Error::CommitSign(message)) => assert_eq!("no key available", message),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn commit_ssh_signed_concludes_merge() {
+        let dir = conflicted_merge();
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let ours = repository.head().unwrap().peel_to_commit().unwrap().id();
+        let theirs = repository.revparse_single("feature").unwrap().id();
+        let prepared = repository.message().unwrap();
+
+        fs::write(dir.path().join("conflict"), "resolved\n").unwrap();
+        crate::stage(&dir, &["conflict"]).unwrap();
+        crate::commit_ssh_signed(&dir, "", |_: &[u8]| {
+            Ok::<_, String>(STUB_SIGNATURE.to_string())
+        })
+        .unwrap();
+
+        let merge = repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(vec![ours, theirs], merge.parent_ids().collect::<Vec<_>>());
+        assert_eq!(prepared, merge.message().unwrap());
+        assert!(merge.header_field_bytes("gpgsig").is_ok());
+        assert_eq!(git2::RepositoryState::Clean, repository.state());
+        assert!(!repository.path().join("MERGE_HEAD").exists());
+    }
+}
@@ -0,0 +1,351 @@
+//! Listing and verification of the signatures of commits and annotated
+//! tags. The cryptographic verification is left to the caller.
+
+use std::collections::HashSet;
+
+use git2::{
+    ErrorCode,
+    Oid,
+    Repository,
+};
+
+use crate::{
+    sign::SIGNATURE_FIELD,
+    Error,
+    GitHelper,
+};
+
+/// Format of a signature, guessed from its armor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureKind {
+    /// OpenPGP signature as created by `gpg`.
+    OpenPgp,
+
+    /// SSH signature as created by `ssh-keygen -Y sign`.
+    Ssh,
+
+    /// X.509 signature as created by `gpgsm` or `smimesign`.
+    X509,
+
+    /// Signature with an armor that is not known.
+    Unknown,
+}
+
+/// Signature of a commit or tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectSignature {
+    /// Format of the signature.
+    pub kind: SignatureKind,
+
+    /// Armored signature as stored in the object.
+    pub armored: String,
+}
+
+/// Kind of object a signature entry describes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignedObject {
+    /// A commit.
+    Commit,
+
+    /// An annotated tag with the given name.
+    Tag(String),
+}
+
+/// Signature state of a single commit or tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureEntry {
+    /// Id of the commit or tag object.
+    pub oid: Oid,
+
+    /// Kind of the object.
+    pub object: SignedObject,
+
+    /// Signature of the object. None if the object is not signed.
+    pub signature: Option<ObjectSignature>,
+}
+
+/// Armor lines that start a signature and the format they belong to.
+const SIGNATURE_ARMORS: &[(&str, SignatureKind)] = &[
+    ("-----BEGIN PGP SIGNATURE-----", SignatureKind::OpenPgp),
+    ("-----BEGIN SSH SIGNATURE-----", SignatureKind::Ssh),
+    ("-----BEGIN SIGNED MESSAGE-----", SignatureKind::X509),
+];
+
+impl GitHelper {
+    /// List the signatures of the commits in the range and of the annotated
+    /// tags pointing to one of these commits. The range is either a single
+    /// revision, which lists the revision and all its ancestors, or a range
+    /// like `v1.0..HEAD`. Commits are listed newest first, followed by the
+    /// tags sorted by name.
+    ///
+    /// The signatures are not verified, use `gpg --verify` or `ssh-keygen -Y
+    /// verify` on the armored signatures for that.
+    pub fn list_signatures(&self, range: &str) -> Result<Vec<SignatureEntry>, Error> {
+        let repository = self.repository();
+        let mut revwalk = repository.revwalk().map_err(Error::RepositoryRevwalk)?;
+
+        if range.contains("..") {
+            revwalk.push_range(range)
+        } else {
+            repository
+                .revparse_single(range)
+                .and_then(|object| object.peel_to_commit())
+                .and_then(|commit| revwalk.push(commit.id()))
+        }
+        .map_err(|_| Error::RevisionNotFound(range.to_string()))?;
+
+        let mut entries = Vec::new();
+        let mut commits = HashSet::new();
+        for oid in revwalk {
+            let oid = oid.map_err(Error::RepositoryRevwalk)?;
+            commits.insert(oid);
+
+            entries.push(SignatureEntry {
+                oid,
+                object: SignedObject::Commit,
+                signature: commit_signature(repository, oid)?,
+            });
+        }
+
+        let mut tags = Vec::new();
+        for reference in repository
+            .references_glob("refs/tags/*")
+            .map_err(Error::RepositoryReferences)?
+        {
+            let reference = reference.map_err(Error::RepositoryReferences)?;
+            let tag = match reference.peel_to_tag() {
+                Ok(tag) => tag,
+                // lightweight tags can not be signed
+                Err(_) => continue,
+            };
+
+            let target = tag.target().map_err(Error::RepositoryFindCommit)?;
+            let target = match target.peel_to_commit() {
+                Ok(target) => target,
+                // tags of trees and blobs are not part of any range
+                Err(_) => continue,
+            };
+            if !commits.contains(&target.id()) {
+                continue;
+            }
+
+            let name = String::from_utf8_lossy(tag.name_bytes()).into_owned();
+            tags.push((
+                name,
+                tag.id(),
+                tag_signature(tag.message_bytes().unwrap_or_default()),
+            ));
+        }
+        tags.sort_by(|a, b| a.0.cmp(&b.0));
+
+        entries.extend(
+            tags.into_iter()
+                .map(|(name, oid, signature)| SignatureEntry {
+                    oid,
+                    object: SignedObject::Tag(name),
+                    signature,
+                }),
+        );
+
+        Ok(entries)
+    }
+
+    /// Extract the signature and the signed content of the commit the revspec
+    /// resolves to and pass them to the `verify` callback in this order. The
+    /// callback does the cryptographic verification, e.g. with `gpg --verify`
+    /// or `ssh-keygen -Y verify`, and its result is returned. Fails with
+    /// Error::NotSigned if the commit has no signature.
+    pub fn verify_signature<V>(&self, revspec: &str, verify: V) -> Result<bool, Error>
+    where
+        V: Fn(&[u8], &[u8]) -> bool,
+    {
+        let commit = self.find_commit(revspec)?;
+
+        match self
+            .repository()
+            .extract_signature(&commit.id(), Some(SIGNATURE_FIELD))
+        {
+            Ok((signature, content)) => Ok(verify(&signature, &content)),
+            Err(ref err) if err.code() == ErrorCode::NotFound => {
+                Err(Error::NotSigned(revspec.to_string()))
+            }
+            Err(err) => Err(Error::RepositoryFindCommit(err)),
+        }
+    }
+}
+
+fn commit_signature(repository: &Repository, oid: Oid) -> Result<Option<ObjectSignature>, Error> {
+    match repository.extract_signature(&oid, Some(SIGNATURE_FIELD)) {
+        Ok((signature, _)) => {
+            let armored = String::from_utf8_lossy(&signature).into_owned();
+
+            Ok(Some(ObjectSignature {
+                kind: signature_kind(&armored),
+                armored,
+            }))
+        }
+        Err(ref err) if err.code() == ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(Error::RepositoryFindCommit(err)),
+    }
+}
+
+/// Git appends the signature of a tag to its message. The signature starts
+/// at the last armor line of a known format.
+fn tag_signature(message: &[u8]) -> Option<ObjectSignature> {
+    let message = String::from_utf8_lossy(message);
+
+    let start = SIGNATURE_ARMORS
+        .iter()
+        .filter_map(|(armor, _)| message.rfind(armor))
+        .max()?;
+    let armored = message[start..].to_string();
+
+    Some(ObjectSignature {
+        kind: signature_kind(&armored),
+        armored,
+    })
+}
+
+fn signature_kind(armored: &str) -> SignatureKind {
+    SIGNATURE_ARMORS
+        .iter()
+        .find(|(armor, _)| armored.trim_start().starts_with(armor))
+        .map(|(_, kind)| *kind)
+        .unwrap_or(SignatureKind::Unknown)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::tempdir;
+
+    use super::{
+        SignatureKind,
+        SignedObject,
+    };
+    use crate::GitHelper;
+
+    const STUB_SSH_SIGNATURE: &str =
+        "-----BEGIN SSH SIGNATURE-----\nc3R1YiBzaWduYXR1cmU=\n-----END SSH SIGNATURE-----\n";
+
+    const STUB_PGP_SIGNATURE: &str =
+        "-----BEGIN PGP SIGNATURE-----\n\nc3R1YiBzaWduYXR1cmU=\n-----END PGP SIGNATURE-----\n";
+
+    #[test]
+    fn list_signatures() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Unsigned commit").unwrap();
+
+        fs::write(dir.path().join("second_file"), "second data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit_ssh_signed(&dir, "SSH signed commit", |_: &[u8]| {
+            Ok::<_, String>(STUB_SSH_SIGNATURE.to_string())
+        })
+        .unwrap();
+
+        fs::write(dir.path().join("third_file"), "third data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit_ssh_signed(&dir, "PGP signed commit", |_: &[u8]| {
+            Ok::<_, String>(STUB_PGP_SIGNATURE.to_string())
+        })
+        .unwrap();
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        let signature = repository.signature().unwrap();
+        repository
+            .tag(
+                "v1.0",
+                head.as_object(),
+                &signature,
+                &format!("Release 1.0\n{}", STUB_PGP_SIGNATURE),
+                false,
+            )
+            .unwrap();
+        repository
+            .tag("v0.9", head.as_object(), &signature, "Release 0.9\n", false)
+            .unwrap();
+        repository
+            .tag_lightweight("latest", head.as_object(), false)
+            .unwrap();
+
+        let entries = crate::list_signatures(&dir, "HEAD~2..HEAD").unwrap();
+        assert_eq!(4, entries.len());
+
+        assert_eq!(head.id(), entries[0].oid);
+        assert_eq!(SignedObject::Commit, entries[0].object);
+        let pgp = entries[0].signature.as_ref().unwrap();
+        assert_eq!(SignatureKind::OpenPgp, pgp.kind);
+        assert_eq!(STUB_PGP_SIGNATURE, pgp.armored);
+
+        let ssh = entries[1].signature.as_ref().unwrap();
+        assert_eq!(SignatureKind::Ssh, ssh.kind);
+        assert_eq!(STUB_SSH_SIGNATURE, ssh.armored);
+
+        assert_eq!(SignedObject::Tag("v0.9".to_string()), entries[2].object);
+        assert!(entries[2].signature.is_none());
+        assert_eq!(SignedObject::Tag("v1.0".to_string()), entries[3].object);
+        let tag = entries[3].signature.as_ref().unwrap();
+        assert_eq!(SignatureKind::OpenPgp, tag.kind);
+        assert_eq!(STUB_PGP_SIGNATURE, tag.armored);
+
+        // a single revision lists all ancestors, including the unsigned root
+        let entries = crate::list_signatures(&dir, "HEAD~1").unwrap();
+        assert_eq!(2, entries.len());
+        assert!(entries[1].signature.is_none());
+
+        match crate::list_signatures(&dir, "missing..HEAD") {
+            Err(crate::Error::RevisionNotFound(range)) => assert_eq!("missing..HEAD", range),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn list_signatures_tag_on_blob() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        helper.commit("Initial commit").unwrap();
+
+        let repository = helper.repository();
+        let blob = repository.blob(b"key material").unwrap();
+        let blob = repository.find_object(blob, None).unwrap();
+        let signature = repository.signature().unwrap();
+        repository
+            .tag("public-key", &blob, &signature, "Public key\n", false)
+            .unwrap();
+
+        let entries = crate::list_signatures(&dir, "HEAD").unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(SignedObject::Commit, entries[0].object);
+    }
+
+    #[test]
+    fn verify_signature() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        crate::commit(&dir, "Unsigned commit").unwrap();
+
+        let mut signed_content = Vec::new();
+        crate::commit_ssh_signed(&dir, "Signed commit", |content: &[u8]| {
+            signed_content = content.to_vec();
+            Ok::<_, String>(STUB_SSH_SIGNATURE.to_string())
+        })
+        .unwrap();
+
+        let matching = |signature: &[u8], content: &[u8]| {
+            signature == STUB_SSH_SIGNATURE.as_bytes() && content == signed_content.as_slice()
+        };
+        assert!(crate::verify_signature(&dir, "HEAD", matching).unwrap());
+        assert!(!crate::verify_signature(&dir, "HEAD", |_: &[u8], _: &[u8]| false).unwrap());
+
+        match crate::verify_signature(&dir, "HEAD~1", matching) {
+            Err(crate::Error::NotSigned(revspec)) => assert_eq!("HEAD~1", revspec),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
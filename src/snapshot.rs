@@ -0,0 +1,254 @@
+//! Throwaway view of a repository where staging and committing happen in
+//! memory only.
+
+use std::{
+    cell::Cell,
+    path::Path,
+};
+
+use git2::{
+    Index,
+    Odb,
+    Oid,
+    Repository,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// Priority of the in-memory object database backend. It has to be higher
+/// than the priorities of the loose and packed backends so new objects are
+/// written to memory instead of to disk.
+pub(crate) const MEMPACK_PRIORITY: i32 = 1000;
+
+/// State of a GitHelper opened with GitHelper::open_snapshot.
+#[derive(Debug, Default)]
+pub(crate) struct Snapshot {
+    /// Commit HEAD of the snapshot points to. None until the first commit in
+    /// the snapshot, HEAD of the repository is used until then.
+    head: Cell<Option<Oid>>,
+
+    /// The repository was created with GitHelper::init_in_memory and has no
+    /// git directory.
+    in_memory: bool,
+}
+
+impl GitHelper {
+    /// Open the repository in the given path as snapshot. Objects written
+    /// through the snapshot are kept in memory on top of the object database
+    /// of the repository and the index is an in-memory copy of the index of
+    /// the repository, so `stage`, `stage_blob` and `commit` do not change
+    /// anything on disk. Commits do not update references either, the commit
+    /// HEAD of the snapshot points to is returned by `snapshot_head`.
+    /// Dropping the snapshot discards everything.
+    ///
+    /// Only staging and committing are isolated. Other operations like fetch
+    /// or checkout still write to the repository.
+    pub fn open_snapshot<P: AsRef<Path>>(repo_path: P) -> Result<Self, Error> {
+        let mut helper = Self::open(repo_path)?;
+        let repository = helper.repository();
+
+        repository
+            .odb()
+            .and_then(|odb| odb.add_new_mempack_backend(MEMPACK_PRIORITY).map(|_| ()))
+            .map_err(Error::OdbOpen)?;
+
+        let mut index = Index::new().map_err(Error::IndexOpen)?;
+        repository
+            .index()
+            .map_err(Error::IndexOpen)?
+            .iter()
+            .try_for_each(|entry| index.add(&entry))
+            .map_err(Error::IndexAdd)?;
+        repository.set_index(&mut index).map_err(Error::IndexOpen)?;
+
+        helper.snapshot = Some(Snapshot::default());
+
+        Ok(helper)
+    }
+
+    /// Create an empty repository that only exists in memory, e.g. for fast
+    /// tests of code that creates blobs, trees and commits. It behaves like a
+    /// snapshot without a repository below it: `stage_blob` and `commit`
+    /// work and the latest commit is returned by `snapshot_head`. There are
+    /// no references, no working directory and no configuration, so
+    /// commits need an author and committer in the CommitOptions unless
+    /// they are set in the environment or the global configuration.
+    pub fn init_in_memory() -> Result<Self, Error> {
+        let odb = Odb::new().map_err(Error::OdbOpen)?;
+        odb.add_new_mempack_backend(MEMPACK_PRIORITY)
+            .map_err(Error::OdbOpen)?;
+
+        let repository = Repository::from_odb(odb).map_err(Error::RepositoryInit)?;
+        let mut index = Index::new().map_err(Error::IndexOpen)?;
+        repository.set_index(&mut index).map_err(Error::IndexOpen)?;
+
+        let mut helper = Self::from_repository(repository);
+        helper.snapshot = Some(Snapshot {
+            in_memory: true,
+            ..Snapshot::default()
+        });
+
+        Ok(helper)
+    }
+
+    /// True if the repository was opened with `open_snapshot`.
+    pub fn is_snapshot(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    /// True if the repository was created with `init_in_memory`.
+    pub(crate) fn is_in_memory(&self) -> bool {
+        self.snapshot
+            .as_ref()
+            .is_some_and(|snapshot| snapshot.in_memory)
+    }
+
+    /// Return the commit HEAD of the snapshot points to after a commit in the
+    /// snapshot. None if nothing was committed in the snapshot yet or if the
+    /// repository was not opened as snapshot.
+    pub fn snapshot_head(&self) -> Option<Oid> {
+        self.snapshot
+            .as_ref()
+            .and_then(|snapshot| snapshot.head.get())
+    }
+
+    /// Reference new commits should update. None for snapshots which only
+    /// remember the commit in memory.
+    pub(crate) fn head_update_ref(&self) -> Option<&'static str> {
+        if self.is_snapshot() {
+            None
+        } else {
+            Some("HEAD")
+        }
+    }
+
+    /// Remember the commit as HEAD of the snapshot. Does nothing if the
+    /// repository was not opened as snapshot.
+    pub(crate) fn set_snapshot_head(&self, oid: Oid) {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.head.set(Some(oid));
+        }
+    }
+
+    /// Write the index to disk. The in-memory index of a snapshot is not
+    /// written.
+    pub(crate) fn write_index(&self, index: &mut Index) -> Result<(), Error> {
+        if self.is_snapshot() {
+            return Ok(());
+        }
+
+        index.write().map_err(Error::IndexWrite)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use tempfile::tempdir;
+
+    use crate::{
+        CommitOptions,
+        GitHelper,
+        Sig,
+        Timestamp,
+    };
+
+    #[test]
+    fn snapshot_commit() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added first_file").unwrap();
+
+        let head = git2::Repository::open(&dir)
+            .unwrap()
+            .head()
+            .unwrap()
+            .target()
+            .unwrap();
+        let index = fs::read(dir.path().join(".git/index")).unwrap();
+
+        fs::write(dir.path().join("first_file"), "changed data").unwrap();
+        fs::write(dir.path().join("second_file"), "second data").unwrap();
+
+        let snapshot = GitHelper::open_snapshot(&dir).unwrap();
+        assert!(snapshot.is_snapshot());
+        assert_eq!(None, snapshot.snapshot_head());
+        snapshot.stage_all().unwrap();
+        snapshot
+            .stage_blob("third_file", b"third data", false)
+            .unwrap();
+        snapshot.commit("Changed files in snapshot").unwrap();
+
+        let snapshot_commit = {
+            let commit = snapshot
+                .repository()
+                .find_commit(snapshot.snapshot_head().unwrap())
+                .unwrap();
+            assert_eq!(vec![head], commit.parent_ids().collect::<Vec<_>>());
+            let tree = commit.tree().unwrap();
+            assert!(tree.get_name("second_file").is_some());
+            assert!(tree.get_name("third_file").is_some());
+            let blob = snapshot
+                .repository()
+                .find_blob(tree.get_name("first_file").unwrap().id())
+                .unwrap();
+            assert_eq!(b"changed data", blob.content());
+
+            commit.id()
+        };
+        drop(snapshot);
+
+        // HEAD, index and object database of the repository are untouched
+        let helper = GitHelper::open(&dir).unwrap();
+        assert!(!helper.is_snapshot());
+        let repository = helper.repository();
+        assert_eq!(head, repository.head().unwrap().target().unwrap());
+        assert_eq!(index, fs::read(dir.path().join(".git/index")).unwrap());
+        assert!(repository.find_commit(snapshot_commit).is_err());
+    }
+
+    #[test]
+    fn init_in_memory() {
+        let helper = GitHelper::init_in_memory().unwrap();
+        assert!(helper.is_snapshot());
+        assert!(helper.workdir().is_err());
+
+        let sig = Sig {
+            name: "Tester".to_string(),
+            email: "tester@example.com".to_string(),
+            when: Timestamp::from_seconds(1_600_000_000, 0),
+        };
+        let options = CommitOptions {
+            author: Some(sig.clone()),
+            committer: Some(sig),
+            ..CommitOptions::default()
+        };
+
+        helper.stage_blob("dir/file", b"in memory", false).unwrap();
+        helper.commit_with("First commit", &options).unwrap();
+        let first = helper.snapshot_head().unwrap();
+
+        helper.stage_blob("other", b"other data", false).unwrap();
+        helper.commit_with("Second commit", &options).unwrap();
+
+        let repository = helper.repository();
+        let commit = repository
+            .find_commit(helper.snapshot_head().unwrap())
+            .unwrap();
+        assert_eq!("Second commit", commit.message().unwrap());
+        assert_eq!(vec![first], commit.parent_ids().collect::<Vec<_>>());
+
+        let tree = commit.tree().unwrap();
+        let entry = tree.get_path(std::path::Path::new("dir/file")).unwrap();
+        assert_eq!(
+            b"in memory",
+            repository.find_blob(entry.id()).unwrap().content()
+        );
+        assert!(tree.get_name("other").is_some());
+    }
+}
@@ -0,0 +1,112 @@
+//! Listing, applying and dropping of stash entries.
+
+use git2::Repository;
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+impl GitHelper {
+    /// List the stash entries with their index and message, newest first
+    /// like `git stash list`. The index is the one used in `stash@{index}`.
+    pub fn list_stashes(&self) -> Result<Vec<(usize, String)>, Error> {
+        let mut stashes = Vec::new();
+        self.stash_handle()?
+            .stash_foreach(|index, message, _| {
+                stashes.push((index, message.to_string()));
+                true
+            })
+            .map_err(Error::Stash)?;
+
+        Ok(stashes)
+    }
+
+    /// Apply the stash entry with the given index to the working directory
+    /// and the index and keep it in the stash.
+    pub fn stash_apply(&self, index: usize) -> Result<(), Error> {
+        self.stash_handle()?
+            .stash_apply(index, None)
+            .map_err(Error::Stash)
+    }
+
+    /// Remove the stash entry with the given index from the stash without
+    /// applying it. Newer entries keep their index, older ones move up.
+    pub fn stash_drop(&self, index: usize) -> Result<(), Error> {
+        self.stash_handle()?.stash_drop(index).map_err(Error::Stash)
+    }
+
+    /// Return a separate handle of the repository as the stash functions of
+    /// git2 need a mutable repository.
+    fn stash_handle(&self) -> Result<Repository, Error> {
+        Repository::open(self.repository().path()).map_err(Error::Stash)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::Repository;
+    use std::fs;
+    use tempfile::tempdir;
+
+    use crate::GitHelper;
+
+    #[test]
+    fn list_stashes() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("file"), "committed").unwrap();
+        helper.stage(&["file"]).unwrap();
+        helper.commit("Added file").unwrap();
+        assert!(crate::list_stashes(&dir).unwrap().is_empty());
+
+        let mut repository = Repository::open(&dir).unwrap();
+        let signature = git2::Signature::now("Tester", "tester@example.com").unwrap();
+        for message in &["first change", "second change"] {
+            fs::write(dir.path().join("file"), message).unwrap();
+            repository.stash_save(&signature, message, None).unwrap();
+        }
+
+        assert_eq!(
+            vec![
+                (0, "On master: second change".to_string()),
+                (1, "On master: first change".to_string()),
+            ],
+            crate::list_stashes(&dir).unwrap()
+        );
+    }
+
+    #[test]
+    fn stash_apply_and_drop() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("file"), "committed").unwrap();
+        helper.stage(&["file"]).unwrap();
+        helper.commit("Added file").unwrap();
+
+        let signature = git2::Signature::now("Tester", "tester@example.com").unwrap();
+        fs::write(dir.path().join("file"), "stashed").unwrap();
+        Repository::open(&dir)
+            .unwrap()
+            .stash_save(&signature, "change", None)
+            .unwrap();
+        assert_eq!(
+            "committed",
+            fs::read_to_string(dir.path().join("file")).unwrap()
+        );
+
+        crate::stash_apply(&dir, 0).unwrap();
+        assert_eq!(
+            "stashed",
+            fs::read_to_string(dir.path().join("file")).unwrap()
+        );
+        assert_eq!(1, crate::list_stashes(&dir).unwrap().len());
+
+        crate::stash_drop(&dir, 0).unwrap();
+        assert!(crate::list_stashes(&dir).unwrap().is_empty());
+        assert_eq!(
+            "stashed",
+            fs::read_to_string(dir.path().join("file")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Inspection of operations like merges or rebases that are in progress.
+
+use git2::RepositoryState;
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// Operation that is in progress in a repository, mirroring
+/// git2::RepositoryState.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepoState {
+    /// No operation is in progress.
+    Clean,
+    /// A merge stopped before it was committed.
+    Merge,
+    /// A revert of a single commit stopped before it was committed.
+    Revert,
+    /// A revert of multiple commits is in progress.
+    RevertSequence,
+    /// A cherry-pick of a single commit stopped before it was committed.
+    CherryPick,
+    /// A cherry-pick of multiple commits is in progress.
+    CherryPickSequence,
+    /// A bisect is in progress.
+    Bisect,
+    /// A rebase with the apply backend is in progress.
+    Rebase,
+    /// An interactive rebase is in progress.
+    RebaseInteractive,
+    /// A rebase with the merge backend is in progress.
+    RebaseMerge,
+    /// `git am` is in progress.
+    ApplyMailbox,
+    /// `git am` or a rebase with the apply backend is in progress.
+    ApplyMailboxOrRebase,
+}
+
+impl RepoState {
+    /// True if no operation is in progress.
+    pub fn is_clean(self) -> bool {
+        self == RepoState::Clean
+    }
+
+    /// True if a merge is in progress.
+    pub fn is_merging(self) -> bool {
+        self == RepoState::Merge
+    }
+
+    /// True if a rebase of any kind is in progress.
+    pub fn is_rebasing(self) -> bool {
+        matches!(
+            self,
+            RepoState::Rebase
+                | RepoState::RebaseInteractive
+                | RepoState::RebaseMerge
+                | RepoState::ApplyMailboxOrRebase
+        )
+    }
+
+    /// True if a cherry-pick is in progress.
+    pub fn is_cherry_picking(self) -> bool {
+        matches!(self, RepoState::CherryPick | RepoState::CherryPickSequence)
+    }
+
+    /// True if a revert is in progress.
+    pub fn is_reverting(self) -> bool {
+        matches!(self, RepoState::Revert | RepoState::RevertSequence)
+    }
+
+    /// True if a bisect is in progress.
+    pub fn is_bisecting(self) -> bool {
+        self == RepoState::Bisect
+    }
+}
+
+impl From<RepositoryState> for RepoState {
+    fn from(state: RepositoryState) -> Self {
+        match state {
+            RepositoryState::Clean => RepoState::Clean,
+            RepositoryState::Merge => RepoState::Merge,
+            RepositoryState::Revert => RepoState::Revert,
+            RepositoryState::RevertSequence => RepoState::RevertSequence,
+            RepositoryState::CherryPick => RepoState::CherryPick,
+            RepositoryState::CherryPickSequence => RepoState::CherryPickSequence,
+            RepositoryState::Bisect => RepoState::Bisect,
+            RepositoryState::Rebase => RepoState::Rebase,
+            RepositoryState::RebaseInteractive => RepoState::RebaseInteractive,
+            RepositoryState::RebaseMerge => RepoState::RebaseMerge,
+            RepositoryState::ApplyMailbox => RepoState::ApplyMailbox,
+            RepositoryState::ApplyMailboxOrRebase => RepoState::ApplyMailboxOrRebase,
+        }
+    }
+}
+
+impl GitHelper {
+    /// Return the operation that is in progress in the repository.
+    pub fn repo_state(&self) -> RepoState {
+        self.repository_state().into()
+    }
+
+    /// Fail with Error::OperationInProgress if an operation is in progress.
+    /// Helpers that would destroy the state of the operation call this before
+    /// they change anything.
+    pub(crate) fn ensure_clean_state(&self) -> Result<(), Error> {
+        match self.repo_state() {
+            RepoState::Clean => Ok(()),
+            state => Err(Error::OperationInProgress(state)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::RepoState;
+    use crate::{
+        test_util::{
+            conflicted_merge,
+            conflicted_rebase,
+        },
+        CheckoutOptions,
+    };
+
+    #[test]
+    fn repo_state() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+        let state = crate::repo_state(&dir).unwrap();
+        assert_eq!(RepoState::Clean, state);
+        assert!(state.is_clean());
+
+        let dir = conflicted_merge();
+        let state = crate::repo_state(&dir).unwrap();
+        assert_eq!(RepoState::Merge, state);
+        assert!(state.is_merging());
+        assert!(!state.is_clean());
+        assert!(!state.is_rebasing());
+
+        let dir = conflicted_rebase();
+        let state = crate::repo_state(&dir).unwrap();
+        assert_eq!(RepoState::RebaseMerge, state);
+        assert!(state.is_rebasing());
+    }
+
+    #[test]
+    fn checkout_refuses_during_merge() {
+        let dir = conflicted_merge();
+
+        match crate::checkout_branch(&dir, "feature", &CheckoutOptions::default()) {
+            Err(crate::Error::OperationInProgress(RepoState::Merge)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(RepoState::Merge, crate::repo_state(&dir).unwrap());
+
+        let options = CheckoutOptions {
+            force: true,
+            ..CheckoutOptions::default()
+        };
+        crate::checkout_branch(&dir, "feature", &options).unwrap();
+    }
+}
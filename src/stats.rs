@@ -0,0 +1,211 @@
+//! Object counts and size statistics of a repository.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use git2::{
+    ErrorCode,
+    ObjectType,
+    Oid,
+    Repository,
+    TreeWalkMode,
+    TreeWalkResult,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// Size statistics of a repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoStats {
+    /// Number of loose objects in the object database.
+    pub loose_objects: usize,
+
+    /// Number of packfiles in the object database.
+    pub packfiles: usize,
+
+    /// Size in bytes of all files in the pack directory.
+    pub pack_size: u64,
+
+    /// Number of references in the repository.
+    pub references: usize,
+
+    /// True if the repository is bare.
+    pub bare: bool,
+
+    /// True if the repository is a shallow clone.
+    pub shallow: bool,
+
+    /// Largest blob reachable from HEAD. None if HEAD has no commits yet.
+    pub largest_blob: Option<BlobInfo>,
+}
+
+/// Information about a blob in the repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobInfo {
+    /// Id of the blob.
+    pub oid: Oid,
+
+    /// Path under which the blob was first found.
+    pub path: PathBuf,
+
+    /// Size of the blob in bytes.
+    pub size: usize,
+}
+
+impl GitHelper {
+    /// Count the objects stored in the object database of the repository.
+    /// This includes loose objects as well as objects stored in packfiles.
+    pub fn object_count(&self) -> Result<usize, Error> {
+        let odb = self.repository().odb().map_err(Error::OdbOpen)?;
+
+        let mut count = 0;
+        odb.foreach(|_| {
+            count += 1;
+            true
+        })
+        .map_err(Error::OdbForeach)?;
+
+        Ok(count)
+    }
+
+    /// Gather size statistics of the repository. Object and pack counts are
+    /// read from the filesystem under the git directory. The largest blob is
+    /// searched in all commits reachable from HEAD. The repository is not
+    /// modified.
+    pub fn repo_stats(&self) -> Result<RepoStats, Error> {
+        let repository = self.repository();
+        let objects_path = repository.path().join("objects");
+
+        let loose_objects = loose_objects(&objects_path)?.len();
+
+        let mut packfiles = 0;
+        let mut pack_size = 0;
+        let pack_path = objects_path.join("pack");
+        if pack_path.is_dir() {
+            for entry in fs::read_dir(&pack_path).map_err(Error::ReadDirectory)? {
+                let entry = entry.map_err(Error::ReadDirectory)?;
+                let metadata = entry.metadata().map_err(Error::ReadDirectory)?;
+
+                if entry.path().extension() == Some("pack".as_ref()) {
+                    packfiles += 1;
+                }
+                pack_size += metadata.len();
+            }
+        }
+
+        let references = repository
+            .references()
+            .map_err(Error::RepositoryReferences)?
+            .count();
+
+        Ok(RepoStats {
+            loose_objects,
+            packfiles,
+            pack_size,
+            references,
+            bare: repository.is_bare(),
+            shallow: repository.is_shallow(),
+            largest_blob: largest_blob(repository)?,
+        })
+    }
+}
+
+/// Return the ids and file paths of all loose objects in the given objects
+/// directory. Files that are not named like an object are ignored.
+pub(crate) fn loose_objects(objects_path: &Path) -> Result<Vec<(Oid, PathBuf)>, Error> {
+    let mut objects = Vec::new();
+
+    for entry in fs::read_dir(objects_path).map_err(Error::ReadDirectory)? {
+        let entry = entry.map_err(Error::ReadDirectory)?;
+        let prefix = entry.file_name();
+        let prefix = prefix.to_string_lossy();
+
+        let is_fanout = prefix.len() == 2 && prefix.chars().all(|c| c.is_ascii_hexdigit());
+        if !is_fanout || !entry.path().is_dir() {
+            continue;
+        }
+
+        for object in fs::read_dir(entry.path()).map_err(Error::ReadDirectory)? {
+            let object = object.map_err(Error::ReadDirectory)?;
+            let suffix = object.file_name();
+            let suffix = suffix.to_string_lossy();
+
+            if suffix.len() != 38 {
+                continue;
+            }
+
+            if let Ok(oid) = Oid::from_str(&format!("{}{}", prefix, suffix)) {
+                objects.push((oid, object.path()));
+            }
+        }
+    }
+
+    Ok(objects)
+}
+
+fn largest_blob(repository: &Repository) -> Result<Option<BlobInfo>, Error> {
+    match repository.head() {
+        Ok(_) => {}
+        Err(ref err)
+            if err.code() == ErrorCode::UnbornBranch || err.code() == ErrorCode::NotFound =>
+        {
+            return Ok(None)
+        }
+        Err(err) => return Err(Error::RepositoryHead(err)),
+    }
+
+    let odb = repository.odb().map_err(Error::OdbOpen)?;
+    let mut revwalk = repository.revwalk().map_err(Error::RepositoryRevwalk)?;
+    revwalk.push_head().map_err(Error::RepositoryRevwalk)?;
+
+    let mut seen = HashSet::new();
+    let mut largest: Option<BlobInfo> = None;
+
+    for oid in revwalk {
+        let oid = oid.map_err(Error::RepositoryRevwalk)?;
+        let commit = repository
+            .find_commit(oid)
+            .map_err(Error::RepositoryFindCommit)?;
+        let tree = commit.tree().map_err(Error::CommitTree)?;
+
+        let mut read_error = None;
+        tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() != Some(ObjectType::Blob) || !seen.insert(entry.id()) {
+                return TreeWalkResult::Ok;
+            }
+
+            match odb.read_header(entry.id()) {
+                Ok((size, _)) => {
+                    if largest.as_ref().is_none_or(|blob| size > blob.size) {
+                        largest = Some(BlobInfo {
+                            oid: entry.id(),
+                            path: Path::new(root).join(entry.name().unwrap_or_default()),
+                            size,
+                        });
+                    }
+                    TreeWalkResult::Ok
+                }
+                Err(err) => {
+                    read_error = Some(err);
+                    TreeWalkResult::Abort
+                }
+            }
+        })
+        .map_err(Error::TreeWalk)?;
+
+        if let Some(err) = read_error {
+            return Err(Error::OdbRead(err));
+        }
+    }
+
+    Ok(largest)
+}
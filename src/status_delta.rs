@@ -0,0 +1,180 @@
+//! Comparison of the status of a repository at two points in time for
+//! callers that poll the status, e.g. after filesystem events.
+
+use std::{
+    collections::{
+        hash_map::DefaultHasher,
+        BTreeMap,
+    },
+    hash::{
+        Hash,
+        Hasher,
+    },
+    path::PathBuf,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+    StatusEntry,
+};
+
+/// Status of a repository at one point in time, returned by
+/// GitHelper::status_snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusSnapshot {
+    entries: Vec<StatusEntry>,
+}
+
+/// Difference between two status snapshots. Every list is sorted by path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusDelta {
+    /// Paths that are reported now but were not before, e.g. files that were
+    /// clean and are modified now.
+    pub appeared: Vec<PathBuf>,
+
+    /// Paths that were reported before but are not now, e.g. files that were
+    /// committed or whose changes were reverted.
+    pub disappeared: Vec<PathBuf>,
+
+    /// Paths that are reported in both snapshots with different status
+    /// flags or a different rename source.
+    pub changed: Vec<PathBuf>,
+}
+
+impl StatusDelta {
+    /// True if the snapshots had the same entries.
+    pub fn is_empty(&self) -> bool {
+        self.appeared.is_empty() && self.disappeared.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl StatusSnapshot {
+    /// Create a snapshot from status entries.
+    pub fn new(mut entries: Vec<StatusEntry>) -> Self {
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Self { entries }
+    }
+
+    /// Return the entries of the snapshot sorted by path.
+    pub fn entries(&self) -> &[StatusEntry] {
+        &self.entries
+    }
+
+    /// Return a hash over the entries. Snapshots with the same entries have
+    /// the same fingerprint, so callers can skip further work if it did not
+    /// change. The fingerprint is only comparable within the same build of a
+    /// program and should not be persisted.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for entry in &self.entries {
+            entry.path.hash(&mut hasher);
+            entry.status.bits().hash(&mut hasher);
+            entry.old_path.hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    /// Return how this snapshot differs from an older one. Only the status
+    /// is compared, further edits of a file that was already modified in the
+    /// older snapshot are not detected.
+    pub fn changed_since(&self, other: &StatusSnapshot) -> StatusDelta {
+        let before = other
+            .entries
+            .iter()
+            .map(|entry| (&entry.path, entry))
+            .collect::<BTreeMap<_, _>>();
+        let now = self
+            .entries
+            .iter()
+            .map(|entry| (&entry.path, entry))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut delta = StatusDelta::default();
+        for (path, entry) in &now {
+            match before.get(path) {
+                None => delta.appeared.push(path.to_path_buf()),
+                Some(old) if old != entry => delta.changed.push(path.to_path_buf()),
+                Some(_) => {}
+            }
+        }
+        delta.disappeared = before
+            .keys()
+            .filter(|path| !now.contains_key(*path))
+            .map(|path| path.to_path_buf())
+            .collect();
+
+        delta
+    }
+}
+
+impl GitHelper {
+    /// Return the status of the repository like `status` as snapshot that
+    /// can be compared with later snapshots.
+    pub fn status_snapshot(&self) -> Result<StatusSnapshot, Error> {
+        Ok(StatusSnapshot::new(self.status()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::PathBuf,
+    };
+    use tempfile::tempdir;
+
+    use super::StatusDelta;
+    use crate::GitHelper;
+
+    #[test]
+    fn changed_since() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("tracked"), "data").unwrap();
+        fs::write(dir.path().join("untracked"), "data").unwrap();
+        helper.stage(&["tracked"]).unwrap();
+        helper.commit("Added tracked").unwrap();
+
+        let clean = crate::status_snapshot(&dir).unwrap();
+        assert_eq!(clean, crate::status_snapshot(&dir).unwrap());
+        assert_eq!(
+            clean.fingerprint(),
+            crate::status_snapshot(&dir).unwrap().fingerprint()
+        );
+        assert!(clean.changed_since(&clean).is_empty());
+
+        fs::write(dir.path().join("tracked"), "changed data").unwrap();
+        let edited = crate::status_snapshot(&dir).unwrap();
+        assert_ne!(clean.fingerprint(), edited.fingerprint());
+        assert_eq!(
+            StatusDelta {
+                appeared: vec![PathBuf::from("tracked")],
+                ..StatusDelta::default()
+            },
+            edited.changed_since(&clean)
+        );
+
+        helper.stage(&["tracked"]).unwrap();
+        fs::remove_file(dir.path().join("untracked")).unwrap();
+        let staged = crate::status_snapshot(&dir).unwrap();
+        assert_eq!(
+            StatusDelta {
+                disappeared: vec![PathBuf::from("untracked")],
+                changed: vec![PathBuf::from("tracked")],
+                ..StatusDelta::default()
+            },
+            staged.changed_since(&edited)
+        );
+        assert_eq!(
+            vec![PathBuf::from("tracked")],
+            staged
+                .entries()
+                .iter()
+                .map(|entry| entry.path.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+}
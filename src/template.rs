@@ -0,0 +1,192 @@
+//! Commit messages built from templates with placeholders, e.g. for tools
+//! that commit automatically.
+
+use std::collections::HashMap;
+
+use crate::{
+    identity::Sig,
+    repo::CommitOptions,
+    DiffConfig,
+    Error,
+    GitHelper,
+};
+
+impl GitHelper {
+    /// Commit the current stage with a message rendered from the template.
+    /// Placeholders like `{host}` are replaced with the value of the
+    /// variable with the same name. Variables take precedence over the
+    /// built-in placeholders which describe the staged changes:
+    ///
+    /// - `{files_changed}`: number of changed files
+    /// - `{insertions}`: number of added lines
+    /// - `{deletions}`: number of removed lines
+    /// - `{branch}`: name of the current branch, `HEAD` if it is detached
+    /// - `{date}`: time of the commit as ISO 8601 date
+    ///
+    /// `{{` and `}}` produce literal braces. Fails with
+    /// Error::UnknownPlaceholder for placeholders without a value, nothing
+    /// is committed then.
+    pub fn commit_templated(
+        &self,
+        template: &str,
+        vars: &HashMap<&str, &str>,
+    ) -> Result<(), Error> {
+        let (author, committer) = self.commit_signatures(&CommitOptions::default())?;
+        let committer = Sig::from(&committer);
+
+        let mut values = self.staged_stats()?;
+        let branch = self.head_branch()?;
+        values.insert("branch", branch.unwrap_or_else(|| "HEAD".to_string()));
+        values.insert("date", committer.when.iso8601());
+        for (name, value) in vars {
+            values.insert(name, value.to_string());
+        }
+
+        let message = render_template(template, &values)?;
+
+        self.commit_with(
+            &message,
+            &CommitOptions {
+                author: Some(Sig::from(&author)),
+                committer: Some(committer),
+                ..CommitOptions::default()
+            },
+        )
+    }
+
+    /// Return the number of changed files, added and removed lines between
+    /// HEAD and the index.
+    fn staged_stats(&self) -> Result<HashMap<&str, String>, Error> {
+        let head_tree = match self.head_commit() {
+            Some(commit) => Some(commit.tree().map_err(Error::CommitTree)?),
+            None => None,
+        };
+
+        let config = DiffConfig::default();
+        let mut diff = self
+            .repository()
+            .diff_tree_to_index(head_tree.as_ref(), None, Some(&mut config.diff_options()))
+            .map_err(Error::Diff)?;
+        config.find_similar(&mut diff)?;
+        let stats = diff.stats().map_err(Error::Diff)?;
+
+        let mut values = HashMap::new();
+        values.insert("files_changed", stats.files_changed().to_string());
+        values.insert("insertions", stats.insertions().to_string());
+        values.insert("deletions", stats.deletions().to_string());
+
+        Ok(values)
+    }
+}
+
+/// Replace the placeholders in the template with their values. A `{`
+/// without a closing `}` is kept as is.
+fn render_template(template: &str, values: &HashMap<&str, String>) -> Result<String, Error> {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(index) = rest.find(['{', '}']) {
+        output.push_str(&rest[..index]);
+        let tail = &rest[index..];
+
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            output.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+
+        match (tail.starts_with('{'), tail.find('}')) {
+            (true, Some(end)) => {
+                let name = &tail[1..end];
+                let value = values
+                    .get(name)
+                    .ok_or_else(|| Error::UnknownPlaceholder(name.to_string()))?;
+                output.push_str(value);
+                rest = &tail[end + 1..];
+            }
+            _ => {
+                output.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        fs,
+    };
+    use tempfile::tempdir;
+
+    use super::render_template;
+    use crate::{
+        Error,
+        GitHelper,
+    };
+
+    #[test]
+    fn commit_templated() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("edited"), "one\ntwo\nthree\n").unwrap();
+        helper.stage(&["edited"]).unwrap();
+        helper.commit("Added edited").unwrap();
+
+        fs::write(dir.path().join("edited"), "one\n2\nthree\nfour\n").unwrap();
+        fs::write(dir.path().join("added"), "first\nsecond\n").unwrap();
+        helper.stage(&["edited", "added"]).unwrap();
+
+        let mut vars = HashMap::new();
+        vars.insert("host", "laptop");
+        crate::commit_templated(
+            &dir,
+            "sync: {files_changed} files changed (+{insertions} -{deletions}) on {host} at {date} \
+             [{branch}] {{literal}}",
+            &vars,
+        )
+        .unwrap();
+
+        let repository = helper.repository();
+        let head = repository.head().unwrap();
+        let commit = head.peel_to_commit().unwrap();
+        let date = crate::Timestamp::from(commit.committer().when()).iso8601();
+        assert_eq!(
+            format!(
+                "sync: 2 files changed (+4 -1) on laptop at {} [{}] {{literal}}",
+                date,
+                head.shorthand().unwrap()
+            ),
+            commit.message().unwrap()
+        );
+    }
+
+    #[test]
+    fn commit_templated_unknown_placeholder() {
+        let dir = tempdir().unwrap();
+        let helper = GitHelper::init(&dir).unwrap();
+        fs::write(dir.path().join("file"), "data").unwrap();
+        helper.stage(&["file"]).unwrap();
+
+        assert_eq!(
+            Err(Error::UnknownPlaceholder("typo".to_string())),
+            crate::commit_templated(&dir, "changed {typo}", &HashMap::new())
+        );
+        assert!(helper.repository().head().is_err());
+    }
+
+    #[test]
+    fn render_template_braces() {
+        let mut values = HashMap::new();
+        values.insert("name", "value".to_string());
+
+        assert_eq!(
+            "value {name} } { and {",
+            render_template("{name} {{name}} }} { and {", &values).unwrap()
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Helpers shared by the tests of the different modules.
+
+use std::fs;
+
+use git2::{
+    Oid,
+    Repository,
+};
+use tempfile::{
+    tempdir,
+    TempDir,
+};
+
+use crate::GitHelper;
+
+/// Create a repository that is in the middle of a merge of the branch
+/// `feature` into `master`. Both branches changed the file `conflict` so the
+/// merge stopped with a conflict on that file.
+pub(crate) fn conflicted_merge() -> TempDir {
+    let (dir, theirs) = diverged_branches();
+    let repository = Repository::open(&dir).unwrap();
+
+    let annotated = repository.find_annotated_commit(theirs).unwrap();
+    repository.merge(&[&annotated], None, None).unwrap();
+
+    dir
+}
+
+/// Create a repository that is in the middle of a cherry-pick of the tip of
+/// the branch `feature` onto `master`. Both branches changed the file
+/// `conflict` so the cherry-pick stopped with a conflict on that file.
+pub(crate) fn conflicted_cherry_pick() -> TempDir {
+    let (dir, theirs) = diverged_branches();
+    let repository = Repository::open(&dir).unwrap();
+
+    let commit = repository.find_commit(theirs).unwrap();
+    repository.cherrypick(&commit, None).unwrap();
+
+    dir
+}
+
+/// Create a repository that is in the middle of a rebase of `master` onto
+/// the branch `feature`. Both branches changed the file `conflict` so the
+/// rebase stopped with a conflict on that file.
+pub(crate) fn conflicted_rebase() -> TempDir {
+    let (dir, theirs) = diverged_branches();
+    let repository = Repository::open(&dir).unwrap();
+
+    let upstream = repository.find_annotated_commit(theirs).unwrap();
+    let mut rebase = repository
+        .rebase(None, Some(&upstream), None, None)
+        .unwrap();
+    rebase.next().unwrap().unwrap();
+    assert!(repository.index().unwrap().has_conflicts());
+
+    dir
+}
+
+/// Create a repository with the branches `master` and `feature` which both
+/// changed the file `conflict` of their common base commit differently.
+/// Returns the tip of `feature`.
+pub(crate) fn diverged_branches() -> (TempDir, Oid) {
+    let dir = tempdir().unwrap();
+    let helper = GitHelper::init(&dir).unwrap();
+    let repository = helper.repository();
+
+    fs::write(dir.path().join("conflict"), "base\n").unwrap();
+    helper.stage(&["conflict"]).unwrap();
+    helper.commit("base").unwrap();
+
+    let base = repository.head().unwrap().peel_to_commit().unwrap();
+    repository.branch("feature", &base, false).unwrap();
+
+    fs::write(dir.path().join("conflict"), "ours\n").unwrap();
+    helper.stage(&["conflict"]).unwrap();
+    helper.commit("ours").unwrap();
+
+    let signature = repository.signature().unwrap();
+    let blob = repository.blob(b"theirs\n").unwrap();
+    let mut builder = repository.treebuilder(Some(&base.tree().unwrap())).unwrap();
+    builder.insert("conflict", blob, 0o100644).unwrap();
+    let tree = repository.find_tree(builder.write().unwrap()).unwrap();
+    let theirs = repository
+        .commit(
+            Some("refs/heads/feature"),
+            &signature,
+            &signature,
+            "theirs",
+            &tree,
+            &[&base],
+        )
+        .unwrap();
+
+    (dir, theirs)
+}
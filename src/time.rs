@@ -0,0 +1,421 @@
+//! Timestamps of commits and tags.
+
+use std::{
+    convert::TryFrom,
+    time::{
+        Duration,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use git2::Time;
+
+/// Point in time together with the timezone offset it was recorded in, as
+/// git stores it for authors, committers and taggers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    /// Point in time.
+    pub time: SystemTime,
+
+    /// Offset of the timezone from UTC in minutes.
+    pub offset_minutes: i32,
+}
+
+impl Timestamp {
+    /// Create a timestamp from seconds since the unix epoch and a timezone
+    /// offset in minutes. Seconds the platform's `SystemTime` can't
+    /// represent, e.g. from a corrupt commit, are clamped towards the epoch
+    /// instead of panicking.
+    pub fn from_seconds(seconds: i64, offset_minutes: i32) -> Self {
+        let mut duration = Duration::from_secs(seconds.unsigned_abs());
+        let time = loop {
+            let time = if seconds >= 0 {
+                UNIX_EPOCH.checked_add(duration)
+            } else {
+                UNIX_EPOCH.checked_sub(duration)
+            };
+
+            // halving always ends at the epoch itself which is representable
+            match time {
+                Some(time) => break time,
+                None => duration /= 2,
+            }
+        };
+
+        Self {
+            time,
+            offset_minutes,
+        }
+    }
+
+    /// Seconds since the unix epoch. Git only stores whole seconds, smaller
+    /// units are truncated.
+    pub fn seconds(&self) -> i64 {
+        match self.time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => i64::try_from(duration.as_secs()).unwrap_or(i64::MAX),
+            Err(err) => {
+                i64::try_from(err.duration().as_secs()).map_or(i64::MIN, |seconds| -seconds)
+            }
+        }
+    }
+
+    /// Return the day of the timestamp in its timezone, e.g. the day a
+    /// commit was authored on from the point of view of its author.
+    pub fn date(&self) -> Date {
+        let (year, month, day) = civil_from_days(self.local_seconds().div_euclid(86_400));
+
+        Date { year, month, day }
+    }
+
+    /// Format the timestamp as ISO 8601 date in its timezone, e.g.
+    /// `2005-04-07T22:13:13+02:00`.
+    pub fn iso8601(&self) -> String {
+        let Date { year, month, day } = self.date();
+        let clock = self.local_seconds().rem_euclid(86_400);
+        let offset = self.offset_minutes.unsigned_abs();
+
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+            year,
+            month,
+            day,
+            clock / 3600,
+            clock % 3600 / 60,
+            clock % 60,
+            if self.offset_minutes < 0 { '-' } else { '+' },
+            offset / 60,
+            offset % 60
+        )
+    }
+
+    /// Seconds since the unix epoch shifted by the timezone offset.
+    fn local_seconds(&self) -> i64 {
+        self.seconds()
+            .saturating_add(i64::from(self.offset_minutes) * 60)
+    }
+}
+
+/// Day of the proleptic Gregorian calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Date {
+    /// Year, e.g. 2005.
+    pub year: i64,
+
+    /// Month from 1 to 12.
+    pub month: u32,
+
+    /// Day of the month from 1 to 31.
+    pub day: u32,
+}
+
+/// Names of the months as used by RFC 2822 dates.
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Highest year accepted by parse_git_date.
+const MAX_YEAR: i64 = 9999;
+
+/// Seconds since the unix epoch of the end of MAX_YEAR in UTC, the latest
+/// time accepted by parse_git_date.
+const MAX_SECONDS: i64 = 253_402_300_799;
+
+/// Parse a date in one of the formats git accepts for `GIT_AUTHOR_DATE` and
+/// `GIT_COMMITTER_DATE`:
+///
+/// - git internal format: `1112911993 +0200` or `@1112911993 +0200`
+/// - RFC 2822: `Thu, 07 Apr 2005 22:13:13 +0200`
+/// - ISO 8601: `2005-04-07T22:13:13+02:00` or `2005-04-07 22:13:13 +0200`
+///
+/// Dates without timezone are taken as UTC. Returns None for other formats
+/// and for dates after the year 9999.
+pub fn parse_git_date(date: &str) -> Option<Timestamp> {
+    let date = date.trim();
+
+    parse_internal(date)
+        .or_else(|| parse_rfc2822(date))
+        .or_else(|| parse_iso8601(date))
+}
+
+fn parse_internal(date: &str) -> Option<Timestamp> {
+    let date = date.strip_prefix('@').unwrap_or(date);
+    let mut parts = date.split_whitespace();
+
+    let seconds = parts.next()?;
+    if !seconds.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let seconds = seconds
+        .parse()
+        .ok()
+        .filter(|seconds| *seconds <= MAX_SECONDS)?;
+
+    let offset = match parts.next() {
+        Some(offset) => parse_offset(offset)?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some(Timestamp::from_seconds(seconds, offset))
+}
+
+fn parse_rfc2822(date: &str) -> Option<Timestamp> {
+    // the day of the week is optional and redundant
+    let date = match date.find(',') {
+        Some(index) => &date[index + 1..],
+        None => date,
+    };
+
+    let parts = date.split_whitespace().collect::<Vec<_>>();
+    if parts.len() != 5 {
+        return None;
+    }
+
+    let day = parts[0].parse().ok()?;
+    let month = MONTHS
+        .iter()
+        .position(|month| parts[1].eq_ignore_ascii_case(month))? as u32
+        + 1;
+    let year = parts[2].parse().ok()?;
+    let (hour, minute, second) = parse_clock(parts[3])?;
+    let offset = parse_offset(parts[4])?;
+
+    local_timestamp(year, month, day, hour, minute, second, offset)
+}
+
+fn parse_iso8601(date: &str) -> Option<Timestamp> {
+    let (day, rest) = date.split_at(date.find(['T', ' '])?);
+    let rest = rest[1..].trim_start();
+
+    let mut day = day.split('-');
+    let year = day.next()?.parse().ok()?;
+    let month = day.next()?.parse().ok()?;
+    let day_of_month = day.next()?.parse().ok()?;
+    if day.next().is_some() {
+        return None;
+    }
+
+    let (clock, offset) = match rest.find(['+', '-', 'Z', ' ']) {
+        Some(index) => (&rest[..index], rest[index..].trim_start()),
+        None => (rest, ""),
+    };
+    let (hour, minute, second) = parse_clock(clock)?;
+    let offset = match offset {
+        "" | "Z" => 0,
+        offset => parse_offset(offset)?,
+    };
+
+    local_timestamp(year, month, day_of_month, hour, minute, second, offset)
+}
+
+/// Parse `hh:mm:ss` or `hh:mm`.
+fn parse_clock(clock: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = clock.split(':');
+    let hour = parts.next()?.parse().ok()?;
+    let minute = parts.next()?.parse().ok()?;
+    let second = match parts.next() {
+        Some(second) => second.parse().ok()?,
+        None => 0,
+    };
+
+    if parts.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    Some((hour, minute, second))
+}
+
+/// Parse a timezone offset like `+0200`, `-05:30` or `+02` into minutes.
+fn parse_offset(offset: &str) -> Option<i32> {
+    let (sign, digits) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+
+    let digits = digits.replace(':', "");
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+
+    if minutes > 59 {
+        return None;
+    }
+
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// Create a timestamp from a date and time in the timezone with the given
+/// offset.
+fn local_timestamp(
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    offset_minutes: i32,
+) -> Option<Timestamp> {
+    if !(0..=MAX_YEAR).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let seconds = days_from_civil(year, month, day)
+        .checked_mul(86_400)?
+        .checked_add(i64::from(hour) * 3600 + i64::from(minute) * 60 + i64::from(second))?
+        .checked_sub(i64::from(offset_minutes) * 60)?;
+
+    Some(Timestamp::from_seconds(seconds, offset_minutes))
+}
+
+/// Number of days between the unix epoch and the given date of the proleptic
+/// Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of the proleptic Gregorian calendar the given number of days after
+/// the unix epoch. Inverse of days_from_civil.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+impl From<Time> for Timestamp {
+    fn from(time: Time) -> Self {
+        Self::from_seconds(time.seconds(), time.offset_minutes())
+    }
+}
+
+impl From<Timestamp> for Time {
+    fn from(timestamp: Timestamp) -> Self {
+        Time::new(timestamp.seconds(), timestamp.offset_minutes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::Time;
+
+    use super::{
+        parse_git_date,
+        Timestamp,
+    };
+
+    #[test]
+    fn timestamp_roundtrip() {
+        for (seconds, offset) in &[(1_600_000_000, 120), (0, 0), (-86_400, -300)] {
+            let timestamp = Timestamp::from(Time::new(*seconds, *offset));
+            assert_eq!(*seconds, timestamp.seconds());
+            assert_eq!(*offset, timestamp.offset_minutes);
+
+            let time = Time::from(timestamp);
+            assert_eq!(*seconds, time.seconds());
+            assert_eq!(*offset, time.offset_minutes());
+        }
+    }
+
+    #[test]
+    fn iso8601() {
+        for (date, seconds, offset) in &[
+            ("2005-04-07T22:13:13+02:00", 1_112_904_793, 120),
+            ("1970-01-01T00:00:00+00:00", 0, 0),
+            ("1969-12-31T18:30:00-05:30", 0, -330),
+            ("2000-02-29T12:00:00+00:00", 951_825_600, 0),
+        ] {
+            let timestamp = Timestamp::from_seconds(*seconds, *offset);
+            assert_eq!(*date, timestamp.iso8601());
+            assert_eq!(Some(timestamp), parse_git_date(date));
+        }
+    }
+
+    #[test]
+    fn parse_git_dates() {
+        let expected = Timestamp::from_seconds(1_112_904_793, 120);
+
+        for date in &[
+            "1112904793 +0200",
+            "@1112904793 +0200",
+            "Thu, 07 Apr 2005 22:13:13 +0200",
+            "7 Apr 2005 22:13:13 +0200",
+            "2005-04-07T22:13:13+02:00",
+            "2005-04-07 22:13:13 +0200",
+        ] {
+            assert_eq!(Some(expected), parse_git_date(date), "{}", date);
+        }
+
+        assert_eq!(
+            Some(Timestamp::from_seconds(1_112_904_793, 0)),
+            parse_git_date("2005-04-07T20:13:13Z")
+        );
+        assert_eq!(
+            Some(Timestamp::from_seconds(0, -330)),
+            parse_git_date("1969-12-31T18:30:00-05:30")
+        );
+
+        for date in &["yesterday", "2005-13-07 22:13:13", "1112911993 0200", ""] {
+            assert_eq!(None, parse_git_date(date), "{}", date);
+        }
+    }
+
+    #[test]
+    fn parse_git_date_out_of_range() {
+        for date in &[
+            "300000000000-01-01T00:00:00Z",
+            "Thu, 07 Apr 300000000000 22:13:13 +0200",
+            "10000-01-01T00:00:00Z",
+            "@9223372036854775807 +1400",
+            "253402300800 +0000",
+        ] {
+            assert_eq!(None, parse_git_date(date), "{}", date);
+        }
+
+        assert_eq!(
+            Some("9999-12-31T23:59:59+00:00".to_string()),
+            parse_git_date("@253402300799 +0000").map(|timestamp| timestamp.iso8601())
+        );
+    }
+
+    #[test]
+    fn date_of_extreme_timestamp() {
+        let timestamp = Timestamp::from_seconds(i64::MAX, 14 * 60);
+        assert!(timestamp.seconds() > 0);
+        timestamp.date();
+        timestamp.iso8601();
+
+        let timestamp = Timestamp::from_seconds(i64::MIN, -14 * 60);
+        assert!(timestamp.seconds() < 0);
+        timestamp.date();
+        timestamp.iso8601();
+    }
+}
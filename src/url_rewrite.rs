@@ -0,0 +1,195 @@
+//! Rewriting of remote urls with the `url.<base>.insteadOf` and
+//! `url.<base>.pushInsteadOf` rules of the git configuration.
+
+use git2::{
+    Config,
+    Direction,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+impl GitHelper {
+    /// Return the url git would connect to for the given url after applying
+    /// the `insteadOf` rules of the repository, global and system
+    /// configuration. For pushes `pushInsteadOf` rules take precedence.
+    ///
+    /// libgit2 applies the same rules when it looks up a named remote, so
+    /// fetch and push already connect to the rewritten url.
+    pub fn resolve_url(&self, url: &str, direction: Direction) -> Result<String, Error> {
+        let config = self.config()?;
+
+        rewrite_url(&config, url, direction)
+    }
+}
+
+/// Apply the `insteadOf` rules of the given configuration to the url. The
+/// rule with the longest matching prefix wins. If several bases use the same
+/// prefix the first one in the configuration wins like in git.
+pub(crate) fn rewrite_url(
+    config: &Config,
+    url: &str,
+    direction: Direction,
+) -> Result<String, Error> {
+    if direction == Direction::Push {
+        if let Some(rewritten) = longest_match(config, url, "pushinsteadof")? {
+            return Ok(rewritten);
+        }
+    }
+
+    Ok(longest_match(config, url, "insteadof")?.unwrap_or_else(|| url.to_string()))
+}
+
+fn longest_match(config: &Config, url: &str, variable: &str) -> Result<Option<String>, Error> {
+    let suffix = format!(".{}", variable);
+    let mut longest: Option<(usize, String)> = None;
+
+    let mut entries = config
+        .entries(Some(&format!("^url\\..*\\.{}$", variable)))
+        .map_err(Error::Config)?;
+
+    while let Some(entry) = entries.next() {
+        let entry = entry.map_err(Error::Config)?;
+
+        let name = String::from_utf8_lossy(entry.name_bytes()).into_owned();
+        let base = match name
+            .strip_prefix("url.")
+            .and_then(|name| strip_suffix_ignore_case(name, &suffix))
+        {
+            Some(base) => base,
+            None => continue,
+        };
+
+        let prefix = String::from_utf8_lossy(entry.value_bytes());
+        if prefix.is_empty() || !url.starts_with(prefix.as_ref()) {
+            continue;
+        }
+
+        if longest
+            .as_ref()
+            .is_none_or(|(length, _)| prefix.len() > *length)
+        {
+            longest = Some((prefix.len(), format!("{}{}", base, &url[prefix.len()..])));
+        }
+    }
+
+    Ok(longest.map(|(_, rewritten)| rewritten))
+}
+
+fn strip_suffix_ignore_case<'a>(value: &'a str, suffix: &str) -> Option<&'a str> {
+    let split = value.len().checked_sub(suffix.len())?;
+
+    if value.is_char_boundary(split) && value[split..].eq_ignore_ascii_case(suffix) {
+        Some(&value[..split])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git2::{
+        Config,
+        Direction,
+    };
+    use std::fs;
+    use tempfile::tempdir;
+
+    use super::rewrite_url;
+
+    fn config(content: &str) -> (tempfile::TempDir, Config) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config");
+        fs::write(&path, content).unwrap();
+
+        let config = Config::open(&path).unwrap();
+        (dir, config)
+    }
+
+    #[test]
+    fn rewrite_longest_prefix() {
+        let (_dir, config) = config(
+            "[url \"ssh://git@github.com/\"]\n\
+             \tinsteadOf = https://github.com/\n\
+             \tinsteadOf = gh:\n\
+             [url \"ssh://git@github.com/Mirror.Org/\"]\n\
+             \tinsteadOf = https://github.com/org/\n",
+        );
+
+        assert_eq!(
+            "ssh://git@github.com/user/repo.git",
+            rewrite_url(
+                &config,
+                "https://github.com/user/repo.git",
+                Direction::Fetch
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            "ssh://git@github.com/user/repo.git",
+            rewrite_url(&config, "gh:user/repo.git", Direction::Fetch).unwrap()
+        );
+        assert_eq!(
+            "ssh://git@github.com/Mirror.Org/repo.git",
+            rewrite_url(&config, "https://github.com/org/repo.git", Direction::Fetch).unwrap()
+        );
+        assert_eq!(
+            "https://gitlab.com/user/repo.git",
+            rewrite_url(
+                &config,
+                "https://gitlab.com/user/repo.git",
+                Direction::Fetch
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn rewrite_push() {
+        let (_dir, config) = config(
+            "[url \"ssh://git@example.com/\"]\n\
+             \tpushInsteadOf = https://example.com/\n\
+             [url \"https://mirror.example.com/\"]\n\
+             \tinsteadOf = https://example.com/\n",
+        );
+
+        assert_eq!(
+            "https://mirror.example.com/repo.git",
+            rewrite_url(&config, "https://example.com/repo.git", Direction::Fetch).unwrap()
+        );
+        assert_eq!(
+            "ssh://git@example.com/repo.git",
+            rewrite_url(&config, "https://example.com/repo.git", Direction::Push).unwrap()
+        );
+    }
+
+    #[test]
+    fn resolve_url_from_repository_config() {
+        let dir = tempdir().unwrap();
+        let helper = crate::GitHelper::init(&dir).unwrap();
+        helper
+            .repository()
+            .config()
+            .unwrap()
+            .set_str(
+                "url.ssh://git@example.com/.insteadOf",
+                "https://example.com/",
+            )
+            .unwrap();
+
+        assert_eq!(
+            "ssh://git@example.com/repo.git",
+            crate::resolve_url(&dir, "https://example.com/repo.git", Direction::Fetch).unwrap()
+        );
+
+        // libgit2 rewrites the url of named remotes the same way
+        helper
+            .repository()
+            .remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        let remote = helper.repository().find_remote("origin").unwrap();
+        assert_eq!("ssh://git@example.com/repo.git", remote.url().unwrap());
+    }
+}
@@ -0,0 +1,327 @@
+//! Integrity check of the objects reachable from the references of a
+//! repository, similar to a lightweight `git fsck`.
+
+use std::{
+    collections::{
+        HashSet,
+        VecDeque,
+    },
+    path::PathBuf,
+};
+
+use git2::{
+    ErrorCode,
+    ObjectType,
+    Oid,
+    ReferenceType,
+};
+
+use crate::{
+    Error,
+    GitHelper,
+};
+
+/// Options for verify.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyOptions {
+    /// Only check the objects reachable from HEAD instead of from all
+    /// references. Useful as a fast check before pushing.
+    pub head_only: bool,
+}
+
+/// Result of verify.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of distinct objects that were checked.
+    pub checked: usize,
+
+    /// Objects that could not be read or whose content does not match their
+    /// id. Empty if the repository is healthy.
+    pub problems: Vec<ObjectProblem>,
+}
+
+impl VerifyReport {
+    /// True if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Kind of problem found for an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// The object does not exist in the object database.
+    Missing,
+
+    /// The object exists but can not be read or its content does not hash to
+    /// its id.
+    Corrupt,
+}
+
+/// Object that failed verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectProblem {
+    /// Id of the object.
+    pub oid: Oid,
+
+    /// What is wrong with the object.
+    pub kind: ProblemKind,
+
+    /// Name of the reference through which the object was reached.
+    pub reference: String,
+
+    /// Path of the object in the tree of the referencing commit. None for
+    /// commits, tags and root trees.
+    pub path: Option<PathBuf>,
+}
+
+struct Pending {
+    oid: Oid,
+    reference: String,
+    path: Option<PathBuf>,
+}
+
+impl GitHelper {
+    /// Check that every object reachable from the references of the
+    /// repository can be read from the object database and that its content
+    /// hashes to its id. Missing and corrupt objects are collected in the
+    /// returned report instead of failing the whole check.
+    pub fn verify(&self, options: &VerifyOptions) -> Result<VerifyReport, Error> {
+        let repository = self.repository();
+        let odb = repository.odb().map_err(Error::OdbOpen)?;
+
+        let mut queue = VecDeque::new();
+        if options.head_only {
+            if let Some(commit) = self.head_commit() {
+                queue.push_back(Pending {
+                    oid: commit.id(),
+                    reference: "HEAD".to_string(),
+                    path: None,
+                });
+            }
+        } else {
+            for reference in repository
+                .references()
+                .map_err(Error::RepositoryReferences)?
+            {
+                let reference = reference.map_err(Error::RepositoryReferences)?;
+                if reference.kind() != Some(ReferenceType::Direct) {
+                    continue;
+                }
+
+                if let Some(oid) = reference.target() {
+                    queue.push_back(Pending {
+                        oid,
+                        reference: String::from_utf8_lossy(reference.name_bytes()).into_owned(),
+                        path: None,
+                    });
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut report = VerifyReport::default();
+
+        while let Some(pending) = queue.pop_front() {
+            if !seen.insert(pending.oid) {
+                continue;
+            }
+            report.checked += 1;
+
+            let object = match odb.read(pending.oid) {
+                Ok(object) => object,
+                Err(err) => {
+                    let kind = if err.code() == ErrorCode::NotFound {
+                        ProblemKind::Missing
+                    } else {
+                        ProblemKind::Corrupt
+                    };
+
+                    report.problems.push(ObjectProblem {
+                        oid: pending.oid,
+                        kind,
+                        reference: pending.reference,
+                        path: pending.path,
+                    });
+                    continue;
+                }
+            };
+
+            let hashes_to_oid = Oid::hash_object(object.kind(), object.data())
+                .map(|oid| oid == pending.oid)
+                .unwrap_or(false);
+            if !hashes_to_oid {
+                report.problems.push(ObjectProblem {
+                    oid: pending.oid,
+                    kind: ProblemKind::Corrupt,
+                    reference: pending.reference,
+                    path: pending.path,
+                });
+                continue;
+            }
+
+            match object.kind() {
+                ObjectType::Commit => {
+                    let commit = repository
+                        .find_commit(pending.oid)
+                        .map_err(Error::RepositoryFindCommit)?;
+
+                    queue.push_back(Pending {
+                        oid: commit.tree_id(),
+                        reference: pending.reference.clone(),
+                        path: None,
+                    });
+
+                    for parent in commit.parent_ids() {
+                        queue.push_back(Pending {
+                            oid: parent,
+                            reference: pending.reference.clone(),
+                            path: None,
+                        });
+                    }
+                }
+                ObjectType::Tree => {
+                    let tree = repository
+                        .find_tree(pending.oid)
+                        .map_err(Error::RepositoryFindTree)?;
+
+                    for entry in tree.iter() {
+                        // submodule commits live in another repository
+                        if entry.kind() == Some(ObjectType::Commit) {
+                            continue;
+                        }
+
+                        let name = String::from_utf8_lossy(entry.name_bytes()).into_owned();
+                        let path = match &pending.path {
+                            Some(parent) => parent.join(name),
+                            None => PathBuf::from(name),
+                        };
+
+                        queue.push_back(Pending {
+                            oid: entry.id(),
+                            reference: pending.reference.clone(),
+                            path: Some(path),
+                        });
+                    }
+                }
+                ObjectType::Tag => {
+                    let tag = repository
+                        .find_tag(pending.oid)
+                        .map_err(Error::RepositoryFindTag)?;
+
+                    queue.push_back(Pending {
+                        oid: tag.target_id(),
+                        reference: pending.reference,
+                        path: None,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::PathBuf,
+    };
+    use tempfile::tempdir;
+
+    use super::{
+        ProblemKind,
+        VerifyOptions,
+    };
+    use crate::GitHelper;
+
+    #[test]
+    fn verify_healthy_repository() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added first_file").unwrap();
+
+        let report = crate::verify(&dir, &VerifyOptions::default()).unwrap();
+        assert!(report.is_ok());
+        // one blob, one tree and one commit
+        assert_eq!(3, report.checked);
+    }
+
+    #[test]
+    fn verify_missing_blob() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        fs::create_dir_all(dir.path().join("subdir")).unwrap();
+        fs::write(dir.path().join("subdir/first_file"), "first data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added first_file").unwrap();
+
+        let blob = GitHelper::open(&dir)
+            .unwrap()
+            .repository()
+            .blob(b"first data")
+            .unwrap()
+            .to_string();
+        let object_path = dir
+            .path()
+            .join(".git/objects")
+            .join(&blob[..2])
+            .join(&blob[2..]);
+        fs::remove_file(object_path).unwrap();
+
+        for head_only in &[false, true] {
+            let options = VerifyOptions {
+                head_only: *head_only,
+            };
+            let report = crate::verify(&dir, &options).unwrap();
+
+            assert_eq!(1, report.problems.len());
+            let problem = &report.problems[0];
+            assert_eq!(blob, problem.oid.to_string());
+            assert_eq!(ProblemKind::Missing, problem.kind);
+            assert_eq!(Some(PathBuf::from("subdir/first_file")), problem.path);
+        }
+
+        let report = crate::verify(&dir, &VerifyOptions::default()).unwrap();
+        assert_eq!("refs/heads/master", report.problems[0].reference);
+    }
+
+    #[test]
+    fn verify_corrupt_blob() {
+        let dir = tempdir().unwrap();
+        crate::init(&dir).unwrap();
+
+        fs::write(dir.path().join("first_file"), "first data").unwrap();
+        crate::stage_all(&dir).unwrap();
+        crate::commit(&dir, "Added first_file").unwrap();
+
+        let helper = GitHelper::open(&dir).unwrap();
+        let repository = helper.repository();
+        let object_path = |oid: git2::Oid| {
+            let oid = oid.to_string();
+            dir.path()
+                .join(".git/objects")
+                .join(&oid[..2])
+                .join(&oid[2..])
+        };
+
+        // replace the content of the blob with the content of another object
+        let blob = object_path(repository.blob(b"first data").unwrap());
+        let other = object_path(repository.blob(b"other data").unwrap());
+        let mut permissions = fs::metadata(&blob).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&blob, permissions).unwrap();
+        fs::copy(&other, &blob).unwrap();
+
+        let report = crate::verify(&dir, &VerifyOptions::default()).unwrap();
+        assert_eq!(1, report.problems.len());
+        assert_eq!(ProblemKind::Corrupt, report.problems[0].kind);
+    }
+}